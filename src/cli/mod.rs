@@ -1,15 +1,29 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use lazy_static::lazy_static;
+use simple_gopher_in_rust::gopher::config::{IpPreference, TlsMode};
+use simple_gopher_in_rust::gopher::error::FailureKind;
 
 lazy_static! {
     pub static ref CLI_ARGS: CliArgs = CliArgs::parse();
 }
 
 #[derive(Parser, Debug, Clone)]
+#[command(args_conflicts_with_subcommands=true, subcommand_negates_reqs=true)]
 pub struct CliArgs {
-    /// Specify 
-    #[arg(short='H', long="HOST")]
-    pub host: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Specify the server to scan as host[:port] or as a gopher:// URL, a URL path starts the scan from that menu. A gophers:// URL is scanned over TLS. IPv6 addresses go in brackets, as in [::1]:70.
+    #[arg(short='H', long="HOST", required_unless_present="resume")]
+    pub host: Option<String>,
+
+    /// Specify a checkpoint file to resume an interrupted run from, the host is taken from the checkpoint. Checkpoints are saved to checkpoint.txt under the log or download path.
+    #[arg(long="RESUME", default_value=None)]
+    pub resume: Option<String>,
+
+    /// Specify the checkpoint file of an earlier run to report changes against. Files that have not changed since are not downloaded again. Changes are logged and saved to diff.tsv under the log path.
+    #[arg(long="SNAPSHOT", default_value=None)]
+    pub snapshot: Option<String>,
 
     /// Specify the path prefix for which logs file will be saved to. Do not specify if no logs file are to be saved.
     #[arg(short='L', long="LOG_LOC", default_value=None)]
@@ -19,6 +33,14 @@ pub struct CliArgs {
     #[arg(short='D', long="DL_LOC", default_value=None)]
     pub download_path_prefix: Option<String>,
 
+    /// Specify a file to export the items found to, as JSON Lines (.jsonl), CSV (.csv) or a Graphviz graph of the menus (.dot). Repeat to export more files.
+    #[arg(long="EXPORT")]
+    pub exports: Vec<String>,
+
+    /// Specify to also save every menu of the server under the download path, as a gophermap and an index.html page linking to the downloaded files
+    #[arg(long="MIRROR", requires="download_path_prefix")]
+    pub mirror: bool,
+
     /// Specify to trace external references. 
     #[arg(long="EXT")]
     pub trace_external: bool,
@@ -31,7 +53,140 @@ pub struct CliArgs {
     #[arg(long="RESP_TIME", default_value="5")]
     pub resp_timeout: u64,
 
+    /// Specify a search term to send to every search item (type 7) found, repeat to send more terms. Search items are not scanned when none is given.
+    #[arg(long="QUERY")]
+    pub search_queries: Vec<String>,
+
+    /// Specify to fetch gopher+ attributes of gopher+ menus and download gopher+ items with gopher+ requests
+    #[arg(long="PLUS")]
+    pub plus: bool,
+
+    /// Specify the number of menus fetched at the same time while scanning
+    #[arg(long="WORKERS", default_value="4")]
+    pub workers: usize,
+
+    /// Specify the maximum number of connections open to a single server at the same time
+    #[arg(long="HOST_CONN", default_value="2")]
+    pub max_conns_per_host: usize,
+
+    /// Specify the least time in milliseconds between the starts of two requests to the same server, for scans and downloads alike
+    #[arg(long="DELAY", default_value="0")]
+    pub min_delay: u64,
+
+    /// Specify the maximum number of requests started per second on a single server, fractions allowed (0.5 is one request every 2 seconds)
+    #[arg(long="RATE", default_value=None)]
+    pub max_rate: Option<f64>,
+
+    /// Specify the maximum number of bytes per second read from all servers together
+    #[arg(long="BANDWIDTH", default_value=None)]
+    pub bandwidth: Option<u64>,
+
+    /// Specify a file of per-server politeness rules, one server a line as host[:port] delay=<ms> rate=<requests per second> conns=<connections>, settings left out are taken from --DELAY, --RATE and --HOST_CONN
+    #[arg(long="POLITENESS_FILE", default_value=None)]
+    pub politeness_file: Option<String>,
+
+    /// Specify to crawl and download whatever the robots.txt of a server disallows, and leave its crawl delay aside
+    #[arg(long="IGNORE_ROBOTS")]
+    pub ignore_robots: bool,
+
+    /// Specify to skip fetching the caps.txt of servers, selectors are then followed exactly as their menus list them
+    #[arg(long="IGNORE_CAPS")]
+    pub ignore_caps: bool,
+
+    /// Specify how many menus deep the scan goes below the starting menu
+    #[arg(long="MAX_DEPTH", default_value=None)]
+    pub max_depth: Option<usize>,
+
+    /// Specify the maximum number of menus to scan
+    #[arg(long="MAX_MENUS", default_value=None)]
+    pub max_menus: Option<usize>,
+
+    /// Specify the number of items after which the scan stops following menus
+    #[arg(long="MAX_ITEMS", default_value=None)]
+    pub max_items: Option<usize>,
+
+    /// Specify the time in seconds after which the scan stops sending requests
+    #[arg(long="MAX_TIME", default_value=None)]
+    pub max_duration: Option<u64>,
+
+    /// Specify the attempts given to each menu and download, the first one included, 1 turns retries off
+    #[arg(long="RETRIES", default_value="3")]
+    pub retries: u32,
+
+    /// Specify the wait before the first retry in milliseconds, doubled for every retry after it
+    #[arg(long="RETRY_DELAY", default_value="500")]
+    pub retry_delay: u64,
+
+    /// Specify the longest wait between retries in milliseconds
+    #[arg(long="RETRY_MAX_DELAY", default_value="30000")]
+    pub retry_max_delay: u64,
+
+    /// Specify the share of each wait, from 0 to 1, that may randomly be taken off it so retries of many requests do not line up
+    #[arg(long="RETRY_JITTER", default_value="0.5")]
+    pub retry_jitter: f64,
+
+    /// Specify the kinds of error retried, separated by commas: dns, connect-timeout, connect, write-failure, read-timeout, read, invalid-path, local-io, invalid-item, invalid-request, parse, non-utf8, server-error, handshake, bind. Defaults to connect-timeout, connect, write-failure, read-timeout and read.
+    #[arg(long="RETRY_ON", value_delimiter=',')]
+    pub retry_on: Vec<FailureKind>,
+
+    /// Specify which addresses of a server are tried: auto, prefer-ipv4, prefer-ipv6, ipv4 (IPv4 only) or ipv6 (IPv6 only). Addresses of both families are tried in turn, each getting a 250 ms head start on the next.
+    #[arg(long="IP", default_value="auto")]
+    pub ip: IpPreference,
+
+    /// Specify how servers without a --TLS_HOST mode are connected to: off (plain TCP), on (TLS) or auto (TLS, falling back to plain TCP for servers that do not speak it)
+    #[arg(long="TLS", default_value="off")]
+    pub tls: TlsMode,
+
+    /// Specify the TLS mode of a single server as host[:port][=off|on|auto], on when no mode is given and for every port when no port is given. Repeat for more servers.
+    #[arg(long="TLS_HOST")]
+    pub tls_hosts: Vec<String>,
+
+    /// Specify to accept TLS certificates that do not check out against the webpki roots, for self-signed servers. Pair it with --PIN_FILE to still notice changed certificates.
+    #[arg(long="INSECURE")]
+    pub insecure: bool,
+
+    /// Specify a file of pinned TLS certificate fingerprints, servers not in it yet are pinned to the certificate they present first
+    #[arg(long="PIN_FILE", default_value=None)]
+    pub pin_file: Option<String>,
+
+    /// Specify a SOCKS5 proxy every request goes through, as socks5h://[user:password@]host:port to have the proxy resolve domains (as Tor needs for .onion servers) or socks5://... to resolve them locally
+    #[arg(long="PROXY", default_value=None)]
+    pub proxy: Option<String>,
+
+    /// Specify the proxy of a single server as host[:port]=socks5h://... or host[:port]=direct to connect to it without one, for every port when no port is given. Repeat for more servers.
+    #[arg(long="PROXY_HOST")]
+    pub proxy_hosts: Vec<String>,
+
     /// Specify to turn off verbose output in STDOUT
     #[arg(long="DV")]
     pub disable_verbose: bool,
-}
\ No newline at end of file
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Serve a folder over gopher instead of scanning a server
+    Serve(ServeArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ServeArgs {
+    /// Specify the folder to serve, its gophermap files are used as menus and other folders are listed
+    #[arg(short='R', long="ROOT")]
+    pub root: String,
+
+    /// Specify the address to listen on
+    #[arg(short='B', long="BIND", default_value="127.0.0.1")]
+    pub bind: String,
+
+    /// Specify the port to listen on
+    #[arg(short='P', long="PORT", default_value="70")]
+    pub port: u16,
+
+    /// Specify the host name written into menus for clients to connect back to, defaults to the bind address
+    #[arg(short='N', long="NAME", default_value=None)]
+    pub name: Option<String>,
+
+    /// Specify the maximum number of connections answered at the same time, further clients wait their turn
+    #[arg(short='C', long="CONNS", default_value="16")]
+    pub conns: usize,
+}
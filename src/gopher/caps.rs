@@ -0,0 +1,100 @@
+use std::fmt::{self, Display};
+
+// The capabilities a server lists in its caps.txt, "Key=Value" lines after an optional "CAPS" line
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Caps {
+    // in file order
+    pub fields: Vec<(String, String)>,
+}
+
+impl Caps {
+    // lines that are no "Key=Value" pair and comments are skipped
+    pub fn parse(content: &str) -> Self {
+        let fields = content.lines()
+            .map(|line| { line.trim() })
+            .filter(|line| { !line.starts_with('#') })
+            .filter_map(|line| { line.split_once('=') })
+            .map(|(key, value)| { (String::from(key.trim()), String::from(value.trim())) })
+            .filter(|(key, _)| { !key.is_empty() })
+            .collect();
+        return Self { fields }
+    }
+
+    // value of a key, keys are matched whatever their case and the last one listed wins
+    pub fn get(&self, key: &str) -> Option<&str> {
+        return self.fields.iter().rev()
+            .find(|(field, _)| { field.eq_ignore_ascii_case(key) })
+            .map(|(_, value)| { value.as_str() })
+            .filter(|value| { !value.is_empty() })
+    }
+
+    // separator of the parts of a selector, "/" when not listed. The spelling of the caps.txt
+    // specification, "PathDelimeter", is read as well
+    pub fn path_delimiter(&self) -> &str {
+        return self.get("PathDelimiter").or_else(|| { self.get("PathDelimeter") }).unwrap_or("/")
+    }
+
+    // part standing for the current directory, "." when not listed
+    pub fn path_identity(&self) -> &str {
+        return self.get("PathIdentity").unwrap_or(".")
+    }
+
+    // part standing for the parent directory, ".." when not listed
+    pub fn path_parent(&self) -> &str {
+        return self.get("PathParent").unwrap_or("..")
+    }
+
+    // a doubled delimiter goes up to the parent directory as well
+    pub fn path_parent_double(&self) -> bool {
+        return self.get("PathParentDouble").is_some_and(|value| { value.eq_ignore_ascii_case("true") })
+    }
+
+    pub fn server_software(&self) -> Option<&str> {
+        return self.get("ServerSoftware")
+    }
+
+    pub fn server_software_version(&self) -> Option<&str> {
+        return self.get("ServerSoftwareVersion")
+    }
+
+    pub fn server_description(&self) -> Option<&str> {
+        return self.get("ServerDescription")
+    }
+
+    // selector with its identity and parent parts resolved by the path rules of the server, nothing going above
+    // the root. Only selectors starting with the delimiter are paths, anything else, like "URL:" links or "GET /",
+    // is left as it is. Empty and trailing parts are kept, "/dir/" and "/dir" may well be different selectors,
+    // unless the server has PathParentDouble on, then an empty part before the last goes up like a parent part
+    pub fn normalize(&self, selector: &str) -> String {
+        let (delimiter, identity, parent) = (self.path_delimiter(), self.path_identity(), self.path_parent());
+        let parent_double = self.path_parent_double();
+        let Some(path) = selector.strip_prefix(delimiter) else {
+            return String::from(selector)
+        };
+        let segments = path.split(delimiter).collect::<Vec<&str>>();
+        let mut parts: Vec<&str> = Vec::new();
+
+        for (i, segment) in segments.iter().enumerate() {
+            if *segment == parent || (parent_double && segment.is_empty() && i + 1 < segments.len()) {
+                parts.pop();
+            } else if *segment != identity {
+                parts.push(segment);
+            }
+        }
+        // "/dir/sub/.." is the folder "/dir/", not the selector "/dir"
+        if segments.last().is_some_and(|last| { *last == identity || *last == parent }) {
+            parts.push("");
+        }
+
+        return format!("{}{}", delimiter, parts.join(delimiter))
+    }
+}
+
+impl Display for Caps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in &self.fields {
+            writeln!(f, "{}={}", key, value)?;
+        }
+        return Ok(())
+    }
+}
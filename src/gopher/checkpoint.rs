@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::str::FromStr;
+use path_clean::clean;
+
+use super::Client;
+use super::crawl::{menu_key, Frontier, MenuKey};
+use super::diff::FileRecord;
+use super::error::GopherError;
+use super::io::Request;
+use super::types::{Item, Referer};
+use super::url::GopherUrl;
+
+// Checkpoints
+// ======
+// State of a run written between the levels of a scan and while downloading, so an interrupted
+// run can be resumed. One record per line, fields separated by tabs:
+//     checkpoint  1
+//     root        <url of the menu the scan started from>
+//     scan        pending | complete
+//     depth       <depth of the pending level>
+//     counted     <menus fetched>  <items found>
+//     frontier    <url>  menus of the pending level in the order they are fetched
+//     visited     <url>  menus fetched or queued
+//     referer     <depth | ->  <url>
+//     item        <url of the menu the item is listed on>  <menu line>
+//     download    <size>  <url>
+//     hash        <sha256>  <url>  digest of the download on the line before
+//     menu        <sha256>  <url>  digest of the lines of a scanned menu
+// URLs are percent-encoded gopher URLs, so the only field that can hold a tab is the menu line, which comes last.
+// Gopher+ attribute blocks and failures are not saved. The checkpoint of a complete run is also
+// the snapshot a later run is compared against (see diff).
+
+const VERSION: &str = "1";
+
+pub struct Checkpoint {
+    pub root: GopherUrl,
+    // the crawl left to do, nothing left once the scan is complete
+    pub frontier: Frontier,
+    pub referers: Vec<Referer>,
+    pub items: Vec<Item>,
+    // digest of every menu scanned, by URL
+    pub menu_hashes: HashMap<String, String>,
+    // size and digest of every item downloaded, by URL
+    pub downloads: HashMap<String, FileRecord>,
+}
+
+impl Checkpoint {
+    pub fn load(path: &str) -> Result<Self, GopherError> {
+        let file = fs::File::open(path).map_err(|source| {
+            GopherError::InvalidPath { path: String::from(path), source: Some(source) }
+        })?;
+        return Self::read(BufReader::new(file))
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, GopherError> {
+        let mut lines = reader.lines();
+        match lines.next() {
+            Some(Ok(line)) if line == format!("checkpoint\t{}", VERSION) => {}
+            Some(Err(error)) => return Err(GopherError::from_read(error)),
+            _ => return Err(GopherError::parse("Not a checkpoint file or unsupported version")),
+        }
+
+        let mut root: Option<GopherUrl> = None;
+        let mut frontier = Frontier::complete();
+        let mut pending = false;
+        let mut referers: Vec<Referer> = Vec::new();
+        let mut items: Vec<Item> = Vec::new();
+        let mut menu_hashes: HashMap<String, String> = HashMap::new();
+        let mut downloads: HashMap<String, FileRecord> = HashMap::new();
+
+        for line in lines {
+            let line = line.map_err(GopherError::from_read)?;
+            let mut fields = line.splitn(3, '\t');
+            let record = fields.next().unwrap_or_default();
+            let (first, second) = (fields.next().unwrap_or_default(), fields.next().unwrap_or_default());
+
+            match record {
+                "root" => { root = Some(GopherUrl::parse(first)?) }
+                "scan" => { pending = first == "pending" }
+                "depth" => { frontier.depth = parse_number(first)? }
+                "counted" => {
+                    frontier.menu_count = parse_number(first)?;
+                    frontier.item_count = parse_number(second)?;
+                }
+                "frontier" => { frontier.level.push(Request::from_url(&GopherUrl::parse(first)?)) }
+                "visited" => { frontier.visited.insert(menu_key(&Referer::from_url(&GopherUrl::parse(first)?))); }
+                "referer" => {
+                    let mut referer = Referer::from_url(&GopherUrl::parse(second)?);
+                    referer.update_depth(if first == "-" { None } else { Some(parse_number(first)?) });
+                    referers.push(referer);
+                }
+                "item" => {
+                    let from = Referer::from_url(&GopherUrl::parse(first)?);
+                    items.push(Item::from_str(&from, second).map_err(|message| { GopherError::Parse { message } })?);
+                }
+                "download" => { downloads.insert(String::from(second), FileRecord::new(parse_number(first)?, None)); }
+                "hash" => {
+                    let record = downloads.get_mut(second)
+                        .ok_or(GopherError::parse("Checkpoint hash without a download"))?;
+                    record.hash = Some(String::from(first));
+                }
+                "menu" => { menu_hashes.insert(String::from(second), String::from(first)); }
+                "" => {}
+                record => return Err(GopherError::Parse { message: format!("Unknown checkpoint record: {}", record) }),
+            }
+        }
+
+        if !pending {
+            frontier = Frontier::complete();
+        }
+        return Ok(Self {
+            root: root.ok_or(GopherError::parse("Checkpoint without a root"))?,
+            frontier,
+            referers,
+            items,
+            menu_hashes,
+            downloads,
+        })
+    }
+}
+
+// counts parse as usize, download sizes as u64 so files over 4 GiB survive 32-bit targets
+fn parse_number<T: FromStr>(field: &str) -> Result<T, GopherError> {
+    return field.parse::<T>()
+        .map_err(|_| { GopherError::Parse { message: format!("Invalid number in checkpoint: {:?}", field) } })
+}
+
+fn key_url(key: &MenuKey) -> GopherUrl {
+    let (domain, port, path, query) = key;
+    let mut referer = Referer::new(domain, *port, path);
+    referer.update_query(query.as_deref());
+    return referer.url()
+}
+
+// write the state of client, frontier is the crawl left to do or None once the scan is complete
+pub fn write<W: Write>(writer: &mut W, client: &Client, frontier: Option<&Frontier>) -> io::Result<()> {
+    writeln!(writer, "checkpoint\t{}", VERSION)?;
+    writeln!(writer, "root\t{}", client.url())?;
+
+    match frontier.filter(|frontier| { !frontier.is_complete() }) {
+        Some(frontier) => {
+            writeln!(writer, "scan\tpending")?;
+            writeln!(writer, "depth\t{}", frontier.depth)?;
+            writeln!(writer, "counted\t{}\t{}", frontier.menu_count, frontier.item_count)?;
+            for req in &frontier.level {
+                writeln!(writer, "frontier\t{}", req.url())?;
+            }
+            // sorted so the same state always makes the same file
+            let mut visited = frontier.visited.iter().map(key_url).map(|url| { url.to_string() }).collect::<Vec<String>>();
+            visited.sort();
+            for url in visited {
+                writeln!(writer, "visited\t{}", url)?;
+            }
+        }
+        None => { writeln!(writer, "scan\tcomplete")?; }
+    }
+
+    for referer in &client.referers {
+        let depth = referer.depth.map(|depth| { depth.to_string() }).unwrap_or(String::from("-"));
+        writeln!(writer, "referer\t{}\t{}", depth, referer)?;
+    }
+    for item in &client.items {
+        let from = match item {
+            Item::INFO { from, .. } | Item::UNKNOWN { from, .. } => from,
+            Item::DATA { referer, .. } => referer,
+        };
+        writeln!(writer, "item\t{}\t{}", from, item.menu_line())?;
+    }
+    let mut menu_hashes = client.menu_hashes.iter().collect::<Vec<(&String, &String)>>();
+    menu_hashes.sort();
+    for (url, hash) in menu_hashes {
+        writeln!(writer, "menu\t{}\t{}", hash, url)?;
+    }
+    let mut downloads = client.downloads.iter().collect::<Vec<(&String, &FileRecord)>>();
+    downloads.sort_by(|a, b| { a.0.cmp(b.0) });
+    for (url, record) in downloads {
+        writeln!(writer, "download\t{}\t{}", record.size, url)?;
+        if let Some(hash) = &record.hash {
+            writeln!(writer, "hash\t{}\t{}", hash, url)?;
+        }
+    }
+    return writer.flush()
+}
+
+// write the checkpoint next to path first and move it over the old one, an interruption
+// while writing leaves the previous checkpoint in place
+pub fn save(path: &str, client: &Client, frontier: Option<&Frontier>) -> Result<(), GopherError> {
+    let full_path = clean(path);
+    let temp_path = clean(format!("{}.tmp", path));
+    let local_io = |source| { GopherError::LocalIo { path: full_path.to_string_lossy().to_string(), source } };
+
+    if let Some(dir_prefix) = full_path.parent().filter(|dir| { !dir.as_os_str().is_empty() }) {
+        fs::create_dir_all(dir_prefix).map_err(|source| {
+            GopherError::InvalidPath { path: full_path.to_string_lossy().to_string(), source: Some(source) }
+        })?;
+    }
+    let file = fs::File::create(&temp_path).map_err(local_io)?;
+    write(&mut BufWriter::new(file), client, frontier).map_err(local_io)?;
+    return fs::rename(&temp_path, &full_path).map_err(local_io)
+}
@@ -0,0 +1,711 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::error::{FailureKind, GopherError};
+use super::politeness::Politeness;
+use super::transport::{AddressLog, Transport};
+use super::url::{percent_decode, percent_encode};
+
+// How connections to a server are secured
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "lowercase"))]
+pub enum TlsMode {
+    // plain TCP
+    Off,
+    // TLS, servers that do not speak it fail the request
+    On,
+    // TLS, servers that do not speak it are connected to again over plain TCP
+    Auto,
+}
+
+impl FromStr for TlsMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "off" | "plain" => Ok(Self::Off),
+            "on" | "tls" => Ok(Self::On),
+            "auto" => Ok(Self::Auto),
+            _ => Err(format!("Unknown TLS mode: {:?} (off, on or auto)", name)),
+        }
+    }
+}
+
+// Which addresses of a server are connected to, see gopher::transport. Addresses of both families are tried
+// alternately, starting with the preferred family, or with the family of the first address resolved for Auto.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "kebab-case"))]
+pub enum IpPreference {
+    Auto,
+    PreferIpv4,
+    PreferIpv6,
+    // addresses of the other family are left out
+    Ipv4,
+    Ipv6,
+}
+
+impl FromStr for IpPreference {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "prefer-ipv4" | "prefer4" => Ok(Self::PreferIpv4),
+            "prefer-ipv6" | "prefer6" => Ok(Self::PreferIpv6),
+            "ipv4" | "4" => Ok(Self::Ipv4),
+            "ipv6" | "6" => Ok(Self::Ipv6),
+            _ => Err(format!("Unknown IP preference: {:?} (auto, prefer-ipv4, prefer-ipv6, ipv4 or ipv6)", name)),
+        }
+    }
+}
+
+// TLS settings of a request, see gopher::transport
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TlsConfig {
+    // mode of servers without one of their own
+    pub mode: TlsMode,
+    // modes of single servers, keyed "host:port", or "host" for every port of the host
+    pub hosts: HashMap<String, TlsMode>,
+    // check certificates against the webpki roots
+    pub verify: bool,
+    // file of certificate fingerprints, servers missing from it are pinned on first use
+    pub pin_file: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn new() -> Self {
+        return Self {
+            mode: TlsMode::Off,
+            hosts: HashMap::new(),
+            verify: true,
+            pin_file: None,
+        }
+    }
+
+    pub fn update_mode(&mut self, mode: TlsMode) -> &mut Self {
+        self.mode = mode;
+        return self
+    }
+
+    // host is "host:port" or "host", IPv6 literals in brackets
+    pub fn update_host(&mut self, host: &str, mode: TlsMode) -> &mut Self {
+        self.hosts.insert(host.to_lowercase(), mode);
+        return self
+    }
+
+    pub fn update_verify(&mut self, verify: bool) -> &mut Self {
+        self.verify = verify;
+        return self
+    }
+
+    pub fn update_pin_file(&mut self, pin_file: Option<&str>) -> &mut Self {
+        self.pin_file = pin_file.map(String::from);
+        return self
+    }
+
+    // mode of the server, its own for the port, then for the host, then the default one
+    pub fn mode_for(&self, domain: &str, port: u16) -> TlsMode {
+        return host_rule(&self.hosts, domain, port).copied().unwrap_or(self.mode)
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        return Self::new()
+    }
+}
+
+// rule of a server in hosts keyed "host:port" or "host", the one for the port first
+fn host_rule<'a, T>(hosts: &'a HashMap<String, T>, domain: &str, port: u16) -> Option<&'a T> {
+    let host = if domain.contains(':') { format!("[{}]", domain) } else { String::from(domain) }.to_lowercase();
+    return hosts.get(&format!("{}:{}", host, port)).or(hosts.get(&host))
+}
+
+// A SOCKS5 proxy, from socks5h://[username:password@]host[:port] to have the proxy resolve domains, as Tor needs
+// for onion names, or socks5:// to resolve them locally and hand the proxy addresses. The port defaults to 1080.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Proxy {
+    pub domain: String,
+    pub port: u16,
+    // the proxy resolves the domains connected to
+    pub remote_dns: bool,
+    // username and password, never serialised so a saved config or crawl holds no password,
+    // they come back as None and are given again with the proxy URL
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub credentials: Option<(String, String)>,
+}
+
+impl FromStr for Proxy {
+    type Err = String;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let invalid = || { format!("Invalid proxy: {:?} (socks5h://host:port or socks5://host:port)", url) };
+        let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+        let remote_dns = match scheme.to_lowercase().as_str() {
+            "socks5h" => true,
+            "socks5" => false,
+            _ => return Err(invalid()),
+        };
+
+        let rest = rest.trim_end_matches('/');
+        let (credentials, address) = match rest.rsplit_once('@') {
+            Some((userinfo, address)) => {
+                let (username, password) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+                let decode = |part: &str| { percent_decode(part).map_err(|_| { invalid() }) };
+                (Some((decode(username)?, decode(password)?)), address)
+            }
+            None => (None, rest),
+        };
+        // bracketed IPv6 literals keep their colons
+        let (domain, port) = match address.strip_prefix('[') {
+            Some(bracketed) => {
+                let (domain, after) = bracketed.split_once(']').ok_or_else(invalid)?;
+                (domain, after.strip_prefix(':'))
+            }
+            None => match address.split_once(':') {
+                Some((domain, port)) => (domain, Some(port)),
+                None => (address, None),
+            },
+        };
+        if domain.is_empty() {
+            return Err(invalid())
+        }
+        let port = match port {
+            Some(port) => port.parse::<u16>().map_err(|_| { invalid() })?,
+            None => 1080,
+        };
+        return Ok(Self { domain: String::from(domain), port, remote_dns, credentials })
+    }
+}
+
+// the proxy URL with the password left out
+impl Display for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://", if self.remote_dns { "socks5h" } else { "socks5" })?;
+        if let Some((username, _)) = &self.credentials {
+            write!(f, "{}@", percent_encode(username))?;
+        }
+        if self.domain.contains(':') {
+            return write!(f, "[{}]:{}", self.domain, self.port)
+        }
+        return write!(f, "{}:{}", self.domain, self.port)
+    }
+}
+
+// Proxy settings of a request, see gopher::transport
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProxyConfig {
+    // proxy of servers without a rule of their own, None connects to them directly
+    pub proxy: Option<Proxy>,
+    // rules of single servers, keyed "host:port", or "host" for every port of the host, None connects directly
+    pub hosts: HashMap<String, Option<Proxy>>,
+}
+
+impl ProxyConfig {
+    pub fn new() -> Self {
+        return Self::default()
+    }
+
+    pub fn update_proxy(&mut self, proxy: Option<Proxy>) -> &mut Self {
+        self.proxy = proxy;
+        return self
+    }
+
+    // host is "host:port" or "host", IPv6 literals in brackets
+    pub fn update_host(&mut self, host: &str, proxy: Option<Proxy>) -> &mut Self {
+        self.hosts.insert(host.to_lowercase(), proxy);
+        return self
+    }
+
+    // proxy the server is reached through, its own rule for the port, then for the host, then the default proxy
+    pub fn proxy_for(&self, domain: &str, port: u16) -> Option<&Proxy> {
+        return match host_rule(&self.hosts, domain, port) {
+            Some(rule) => rule.as_ref(),
+            None => self.proxy.as_ref(),
+        }
+    }
+}
+
+// Settings for a single request, handed to every Request a Client spawns
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequestConfig {
+    pub conn_timeout: Duration,
+    pub resp_timeout: Duration,
+    pub verbose: bool,
+    pub ip: IpPreference,
+    pub tls: TlsConfig,
+    pub proxy: ProxyConfig,
+    // opens the connections, plain TCP unless replaced, see gopher::transport
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub transport: Transport,
+    // pace of the requests to each server and of the bytes read, shared by the clones of the config
+    pub politeness: Politeness,
+    // address each request connected to, shared by the clones of the config like politeness
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub addresses: AddressLog,
+}
+
+impl RequestConfig {
+    pub fn new() -> Self {
+        return Self {
+            conn_timeout: Duration::from_secs(10),
+            resp_timeout: Duration::from_secs(5),
+            verbose: true,
+            ip: IpPreference::Auto,
+            tls: TlsConfig::new(),
+            proxy: ProxyConfig::new(),
+            transport: Transport::tcp(),
+            politeness: Politeness::default(),
+            addresses: AddressLog::default(),
+        }
+    }
+
+    pub fn update_conn_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.conn_timeout = timeout;
+        return self
+    }
+
+    pub fn update_resp_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.resp_timeout = timeout;
+        return self
+    }
+
+    pub fn update_verbose(&mut self, verbose: bool) -> &mut Self {
+        self.verbose = verbose;
+        return self
+    }
+
+    pub fn update_ip(&mut self, ip: IpPreference) -> &mut Self {
+        self.ip = ip;
+        return self
+    }
+
+    pub fn update_tls(&mut self, tls: TlsConfig) -> &mut Self {
+        self.tls = tls;
+        return self
+    }
+
+    pub fn update_proxy(&mut self, proxy: ProxyConfig) -> &mut Self {
+        self.proxy = proxy;
+        return self
+    }
+
+    pub fn update_transport(&mut self, transport: Transport) -> &mut Self {
+        self.transport = transport;
+        return self
+    }
+
+    // requests already under way keep the pace they started with
+    pub fn update_politeness(&mut self, politeness: PolitenessConfig) -> &mut Self {
+        self.politeness = Politeness::new(politeness);
+        return self
+    }
+}
+
+impl Default for RequestConfig {
+    fn default() -> Self {
+        return Self::new()
+    }
+}
+
+// How often and how patiently a failed menu fetch or download is tried again. The n-th retry waits
+// base_delay * 2^(n-1), capped at max_delay, less a random share of up to jitter of that wait.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetryPolicy {
+    // attempts per request, the first one included, 1 turns retries off
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // from 0, every retry waits the full backoff, to 1, a retry waits anywhere between nothing and the backoff
+    pub jitter: f64,
+    // kinds of error worth another attempt, the others fail at once
+    pub retry_on: HashSet<FailureKind>,
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        return Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.5,
+            retry_on: HashSet::from(FailureKind::TRANSIENT),
+        }
+    }
+
+    // a single attempt per request
+    pub fn none() -> Self {
+        let mut policy = Self::new();
+        policy.update_max_attempts(1);
+        return policy
+    }
+
+    pub fn update_max_attempts(&mut self, max_attempts: u32) -> &mut Self {
+        self.max_attempts = max_attempts.max(1);
+        return self
+    }
+
+    pub fn update_base_delay(&mut self, base_delay: Duration) -> &mut Self {
+        self.base_delay = base_delay;
+        return self
+    }
+
+    pub fn update_max_delay(&mut self, max_delay: Duration) -> &mut Self {
+        self.max_delay = max_delay;
+        return self
+    }
+
+    // clamped to 0..=1, a jitter that is no number means none
+    pub fn update_jitter(&mut self, jitter: f64) -> &mut Self {
+        self.jitter = if jitter.is_nan() { 0.0 } else { jitter.clamp(0.0, 1.0) };
+        return self
+    }
+
+    pub fn update_retry_on(&mut self, retry_on: &[FailureKind]) -> &mut Self {
+        self.retry_on = retry_on.iter().copied().collect();
+        return self
+    }
+
+    // true when error, met on the given attempt counting from 1, is worth another one
+    pub fn retries(&self, error: &GopherError, attempt: u32) -> bool {
+        return attempt < self.max_attempts && self.retry_on.contains(&error.kind())
+    }
+
+    // longest wait before the given retry counting from 1, jitter takes a random share off it
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        return self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        return Self::new()
+    }
+}
+
+// longest wait between the starts of two requests to a server, slower settings are cut down to it
+// so the start of the next request always fits in an Instant
+pub const MAX_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// How hard a single server is pressed
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HostPoliteness {
+    // least time between the starts of two requests to the server
+    pub min_delay: Duration,
+    // requests started per second, unlimited when None
+    pub max_rate: Option<f64>,
+    // connections open to the server at once
+    pub max_conns: usize,
+}
+
+impl HostPoliteness {
+    pub fn new() -> Self {
+        return Self { min_delay: Duration::ZERO, max_rate: None, max_conns: 2 }
+    }
+
+    pub fn update_min_delay(&mut self, min_delay: Duration) -> &mut Self {
+        self.min_delay = min_delay;
+        return self
+    }
+
+    // rates of 0 or less, and rates that are no number, are taken as unlimited
+    pub fn update_max_rate(&mut self, max_rate: Option<f64>) -> &mut Self {
+        self.max_rate = max_rate.filter(|rate| { rate.is_finite() && *rate > 0.0 });
+        return self
+    }
+
+    // false for rates that are no number or would start requests more than MAX_INTERVAL apart,
+    // settings read from users are checked against it
+    pub fn valid_rate(rate: f64) -> bool {
+        return rate.is_finite() && (rate <= 0.0 || 1.0 / rate <= MAX_INTERVAL.as_secs_f64())
+    }
+
+    pub fn update_max_conns(&mut self, max_conns: usize) -> &mut Self {
+        self.max_conns = max_conns.max(1);
+        return self
+    }
+
+    // least time between the starts of two requests, from min_delay or max_rate, whichever is slower,
+    // up to MAX_INTERVAL
+    pub fn interval(&self) -> Duration {
+        let rate_interval = self.max_rate
+            .map(|rate| { Duration::try_from_secs_f64(1.0 / rate).unwrap_or(MAX_INTERVAL) })
+            .unwrap_or_default();
+        return self.min_delay.max(rate_interval).min(MAX_INTERVAL)
+    }
+
+    // settings of a host rule, "delay=<ms> rate=<requests per second> conns=<connections>" in any order,
+    // settings left out stay as they are
+    fn apply(&mut self, settings: &str) -> Result<(), String> {
+        for setting in settings.split_whitespace() {
+            let (name, value) = setting.split_once('=').ok_or_else(|| { format!("{:?} is no name=value setting", setting) })?;
+            let invalid = || { format!("Invalid {}: {:?}", name, value) };
+            match name.to_lowercase().as_str() {
+                "delay" => { self.update_min_delay(Duration::from_millis(value.parse::<u64>().map_err(|_| { invalid() })?)); }
+                "rate" => {
+                    let rate = value.parse::<f64>().ok().filter(|rate| { Self::valid_rate(*rate) }).ok_or_else(invalid)?;
+                    self.update_max_rate(Some(rate));
+                }
+                "conns" => { self.update_max_conns(value.parse::<usize>().map_err(|_| { invalid() })?); }
+                _ => return Err(format!("Unknown setting: {:?} (delay, rate or conns)", name)),
+            }
+        }
+        return Ok(())
+    }
+}
+
+impl Default for HostPoliteness {
+    fn default() -> Self {
+        return Self::new()
+    }
+}
+
+// Politeness towards the servers of a crawl, applied to every request and download, see gopher::politeness
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PolitenessConfig {
+    // settings of servers without a rule of their own
+    pub host: HostPoliteness,
+    // rules of single servers, keyed "host:port", or "host" for every port of the host
+    pub hosts: HashMap<String, HostPoliteness>,
+    // bytes per second read from all servers together, unlimited when None
+    pub bandwidth: Option<u64>,
+}
+
+impl PolitenessConfig {
+    pub fn new() -> Self {
+        return Self::default()
+    }
+
+    pub fn update_host_default(&mut self, host: HostPoliteness) -> &mut Self {
+        self.host = host;
+        return self
+    }
+
+    // host is "host:port" or "host", IPv6 literals in brackets
+    pub fn update_host(&mut self, host: &str, politeness: HostPoliteness) -> &mut Self {
+        self.hosts.insert(host.to_lowercase(), politeness);
+        return self
+    }
+
+    // bandwidths of 0 are taken as unlimited
+    pub fn update_bandwidth(&mut self, bandwidth: Option<u64>) -> &mut Self {
+        self.bandwidth = bandwidth.filter(|bandwidth| { *bandwidth > 0 });
+        return self
+    }
+
+    // settings of the server, its own rule for the port, then for the host, then those of every server
+    pub fn for_host(&self, domain: &str, port: u16) -> &HostPoliteness {
+        return host_rule(&self.hosts, domain, port).unwrap_or(&self.host)
+    }
+
+    // add the rules of a politeness file, one server a line as "host[:port] delay=<ms> rate=<per second> conns=<n>",
+    // settings a rule leaves out are those of every server, blank lines and lines starting with # are skipped
+    pub fn load_hosts(&mut self, path: &str) -> Result<&mut Self, GopherError> {
+        let rules = std::fs::read_to_string(path).map_err(|source| {
+            GopherError::InvalidPath { path: String::from(path), source: Some(source) }
+        })?;
+        for (i, line) in rules.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (host, settings) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let mut politeness = self.host.clone();
+            politeness.apply(settings).map_err(|message| {
+                GopherError::parse(&format!("{}, line {}: {}", path, i + 1, message))
+            })?;
+            self.update_host(host, politeness);
+        }
+        return Ok(self)
+    }
+}
+
+// Settings for a whole crawl, owned by Client
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientConfig {
+    pub request: RequestConfig,
+    // follow menus hosted on other servers than the one being scanned
+    pub trace_external: bool,
+    // folder used by download_all(), no downloads when left empty
+    pub download_root: Option<String>,
+    // search terms run against every search item found while scanning,
+    // search items are not followed when left empty
+    pub search_queries: Vec<String>,
+    // fetch attributes of gopher+ menus and download gopher+ items with gopher+ requests
+    pub plus: bool,
+    // number of threads fetching menus during a scan
+    pub workers: usize,
+    // limits of a scan, unlimited when None. The depth of a menu counts the menus between it and the scan root,
+    // items are counted as menus are fetched so the scan stops once the limit is passed
+    pub max_depth: Option<usize>,
+    pub max_menus: Option<usize>,
+    pub max_items: Option<usize>,
+    pub max_duration: Option<Duration>,
+    // file the state of a run is saved to between levels of a scan and while downloading, see gopher::checkpoint
+    pub checkpoint_path: Option<String>,
+    // write every menu of the scanned server under download_root as it is scanned, see gopher::mirror
+    pub mirror: bool,
+    // retries of failed menu fetches and downloads, see gopher::retry
+    pub retry: RetryPolicy,
+    // fetch the robots.txt of every server crawled and keep to its disallow rules and crawl delay, see gopher::robots
+    pub robots: bool,
+    // fetch the caps.txt of every server crawled and normalise its selectors by its path rules
+    pub caps: bool,
+}
+
+impl ClientConfig {
+    pub fn new() -> Self {
+        return Self {
+            request: RequestConfig::new(),
+            trace_external: false,
+            download_root: None,
+            search_queries: Vec::new(),
+            plus: false,
+            workers: 1,
+            max_depth: None,
+            max_menus: None,
+            max_items: None,
+            max_duration: None,
+            checkpoint_path: None,
+            mirror: false,
+            retry: RetryPolicy::new(),
+            robots: true,
+            caps: true,
+        }
+    }
+
+    pub fn update_conn_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.request.update_conn_timeout(timeout);
+        return self
+    }
+
+    pub fn update_resp_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.request.update_resp_timeout(timeout);
+        return self
+    }
+
+    pub fn update_verbose(&mut self, verbose: bool) -> &mut Self {
+        self.request.update_verbose(verbose);
+        return self
+    }
+
+    pub fn update_ip(&mut self, ip: IpPreference) -> &mut Self {
+        self.request.update_ip(ip);
+        return self
+    }
+
+    pub fn update_tls(&mut self, tls: TlsConfig) -> &mut Self {
+        self.request.update_tls(tls);
+        return self
+    }
+
+    pub fn update_proxy(&mut self, proxy: ProxyConfig) -> &mut Self {
+        self.request.update_proxy(proxy);
+        return self
+    }
+
+    pub fn update_transport(&mut self, transport: Transport) -> &mut Self {
+        self.request.update_transport(transport);
+        return self
+    }
+
+    pub fn update_politeness(&mut self, politeness: PolitenessConfig) -> &mut Self {
+        self.request.update_politeness(politeness);
+        return self
+    }
+
+    pub fn update_trace_external(&mut self, trace_external: bool) -> &mut Self {
+        self.trace_external = trace_external;
+        return self
+    }
+
+    pub fn update_download_root(&mut self, download_root: Option<&str>) -> &mut Self {
+        self.download_root = download_root.map(String::from);
+        return self
+    }
+
+    pub fn update_search_queries(&mut self, search_queries: &[String]) -> &mut Self {
+        self.search_queries = search_queries.to_vec();
+        return self
+    }
+
+    pub fn update_plus(&mut self, plus: bool) -> &mut Self {
+        self.plus = plus;
+        return self
+    }
+
+    pub fn update_workers(&mut self, workers: usize) -> &mut Self {
+        self.workers = workers.max(1);
+        return self
+    }
+
+    // open connections allowed to servers without a politeness rule of their own, whatever the number of workers
+    pub fn update_max_conns_per_host(&mut self, max_conns_per_host: usize) -> &mut Self {
+        let mut politeness = self.request.politeness.config().clone();
+        politeness.host.update_max_conns(max_conns_per_host);
+        self.request.update_politeness(politeness);
+        return self
+    }
+
+    pub fn update_max_depth(&mut self, max_depth: Option<usize>) -> &mut Self {
+        self.max_depth = max_depth;
+        return self
+    }
+
+    pub fn update_max_menus(&mut self, max_menus: Option<usize>) -> &mut Self {
+        self.max_menus = max_menus;
+        return self
+    }
+
+    pub fn update_max_items(&mut self, max_items: Option<usize>) -> &mut Self {
+        self.max_items = max_items;
+        return self
+    }
+
+    pub fn update_max_duration(&mut self, max_duration: Option<Duration>) -> &mut Self {
+        self.max_duration = max_duration;
+        return self
+    }
+
+    pub fn update_checkpoint_path(&mut self, checkpoint_path: Option<&str>) -> &mut Self {
+        self.checkpoint_path = checkpoint_path.map(String::from);
+        return self
+    }
+
+    pub fn update_mirror(&mut self, mirror: bool) -> &mut Self {
+        self.mirror = mirror;
+        return self
+    }
+
+    pub fn update_retry(&mut self, retry: RetryPolicy) -> &mut Self {
+        self.retry = retry;
+        return self
+    }
+
+    pub fn update_robots(&mut self, robots: bool) -> &mut Self {
+        self.robots = robots;
+        return self
+    }
+
+    pub fn update_caps(&mut self, caps: bool) -> &mut Self {
+        self.caps = caps;
+        return self
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        return Self::new()
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+#[cfg(feature = "async")]
+use std::collections::VecDeque;
+#[cfg(feature = "async")]
+use tokio::task::JoinSet;
+
+use super::config::ClientConfig;
+#[cfg(feature = "async")]
+use super::config::RetryPolicy;
+use super::error::GopherError;
+use super::io::Request;
+use super::plus::{PlusAttributes, PlusRequest};
+use super::retry;
+use super::robots::ServerRules;
+use super::types::{Item, ItemClass, Referer};
+
+// (domain, port, selector, search terms) of a menu
+pub type MenuKey = (String, u16, String, Option<String>);
+
+pub fn menu_key(referer: &Referer) -> MenuKey {
+    return (referer.domain.clone(), referer.port, referer.path.clone(), referer.query.clone())
+}
+
+// Outcome of fetching one menu
+pub struct MenuFetch {
+    // false when no connection could be made to the server on the last attempt
+    pub answered: bool,
+    pub items: Result<Vec<Item>, GopherError>,
+    // attempts the retry policy allowed, the first one included
+    pub attempts: u32,
+    // gopher+ attribute blocks of the menu, when requested
+    pub attributes: Option<Result<Vec<PlusAttributes>, GopherError>>,
+}
+
+pub struct CrawledMenu {
+    pub request: Request,
+    // menus between the root and this one along the shortest path
+    pub depth: usize,
+    pub fetch: MenuFetch,
+}
+
+// Limit of ClientConfig that cut a crawl short
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrawlLimit {
+    Depth,
+    Menus,
+    Items,
+    Duration,
+}
+
+impl Display for CrawlLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Depth => write!(f, "max depth"),
+            Self::Menus => write!(f, "max menus"),
+            Self::Items => write!(f, "max items"),
+            Self::Duration => write!(f, "max duration"),
+        }
+    }
+}
+
+// Where a crawl stands between two levels, breadth first
+pub struct Frontier {
+    // menus of the next level, in the order they were found
+    pub level: Vec<Request>,
+    pub depth: usize,
+    pub visited: HashSet<MenuKey>,
+    pub item_count: usize,
+    pub menu_count: usize,
+    // menus fetched by the last step, in menu order
+    pub menus: Vec<CrawledMenu>,
+    // menus the robots.txt of their server kept out of the crawl, until taken
+    pub disallowed: Vec<Referer>,
+    pub limit_reached: Option<CrawlLimit>,
+}
+
+impl Frontier {
+    pub fn new(root: Request) -> Self {
+        return Self {
+            visited: HashSet::from([menu_key(&root.referer())]),
+            level: vec![root],
+            depth: 0,
+            item_count: 0,
+            menu_count: 0,
+            menus: Vec::new(),
+            disallowed: Vec::new(),
+            limit_reached: None,
+        }
+    }
+
+    // a crawl with nothing left to fetch
+    pub fn complete() -> Self {
+        return Self {
+            level: Vec::new(),
+            depth: 0,
+            visited: HashSet::new(),
+            item_count: 0,
+            menu_count: 0,
+            menus: Vec::new(),
+            disallowed: Vec::new(),
+            limit_reached: None,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        return self.level.is_empty()
+    }
+}
+
+// Fetches the menus of a Frontier one level at a time, the menus of a level are fetched by a pool
+// of worker threads and put together in menu order so the crawl does not depend on scheduling.
+// Workers wait for the connections and turns the politeness of the config gives each server.
+// The robots.txt and caps.txt of a server are fetched before the first menu of it, see gopher::robots
+pub struct Crawler {
+    config: ClientConfig,
+    // server the crawl is kept on, every server when None
+    scope: Option<(String, u16)>,
+    deadline: Option<Instant>,
+    // rules of every server reached so far
+    rules: HashMap<(String, u16), ServerRules>,
+}
+
+impl Crawler {
+    pub fn new(config: &ClientConfig, scope: Option<(&str, u16)>) -> Self {
+        return Self {
+            config: config.clone(),
+            scope: scope.map(|(domain, port)| { (String::from(domain), port) }),
+            deadline: config.max_duration.map(|duration| { Instant::now() + duration }),
+            rules: HashMap::new(),
+        }
+    }
+
+    // robots.txt and caps.txt of every server reached so far, by (domain, port)
+    pub fn rules(&self) -> &HashMap<(String, u16), ServerRules> {
+        return &self.rules
+    }
+
+    // requests for the menus to crawl next from an item, one per seed query for search items.
+    // Selectors of servers with a caps.txt are normalised by its path rules
+    pub fn follow(&self, item: &Item) -> Vec<Request> {
+        if let Item::DATA { port, domain, location, .. } = item {
+            let in_scope = self.scope.as_ref().is_none_or(|(scope_domain, scope_port)| {
+                return scope_domain == domain && scope_port == port
+            });
+            if !item.is_menu() || !in_scope {
+                return Vec::new()
+            }
+            let selector = self.rules.get(&(domain.clone(), *port)).and_then(|rules| { rules.normalize(location) });
+
+            if item.class() == Some(ItemClass::Search) {
+                let Ok(mut req) = item_request(&self.config, item) else { return Vec::new() };
+                if let Some(selector) = &selector {
+                    req.update_path(selector);
+                }
+                return self.config.search_queries.iter().map(|query| {
+                    let mut search_req = req.clone();
+                    search_req.with_query(query);
+                    return search_req
+                }).collect()
+            } else if let Ok(mut req) = Request::from_item(item) {
+                req.update_config(&self.config.request);
+                if let Some(selector) = &selector {
+                    req.update_path(selector);
+                }
+                return vec![req]
+            }
+        }
+        return Vec::new()
+    }
+
+    // fetch the next level of the frontier into frontier.menus, false once the crawl is over
+    pub fn step(&mut self, frontier: &mut Frontier) -> bool {
+        for (domain, port) in self.new_hosts(&frontier.level) {
+            let rules = ServerRules::fetch(&self.config, &domain, port);
+            self.rules.insert((domain, port), rules);
+        }
+        if !self.trim_level(frontier) {
+            return false
+        }
+        let fetches = self.fetch_level(&frontier.level);
+        self.end_level(frontier, fetches);
+        return true
+    }
+
+    // step on a tokio runtime, the menus of a level are fetched by up to workers tasks at a time
+    #[cfg(feature = "async")]
+    pub async fn async_step(&mut self, frontier: &mut Frontier) -> bool {
+        for (domain, port) in self.new_hosts(&frontier.level) {
+            let rules = ServerRules::async_fetch(&self.config, &domain, port).await;
+            self.rules.insert((domain, port), rules);
+        }
+        if !self.trim_level(frontier) {
+            return false
+        }
+        let fetches = self.async_fetch_level(&frontier.level).await;
+        self.end_level(frontier, fetches);
+        return true
+    }
+
+    fn deadline_passed(&self) -> bool {
+        return self.deadline.is_some_and(|deadline| { Instant::now() >= deadline })
+    }
+
+    // servers of level whose rules have not been fetched yet, in the order of the level
+    fn new_hosts(&self, level: &[Request]) -> Vec<(String, u16)> {
+        let mut hosts: Vec<(String, u16)> = Vec::new();
+        for req in level {
+            let referer = req.referer();
+            let host = (referer.domain, referer.port);
+            if !self.rules.contains_key(&host) && !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+        return hosts
+    }
+
+    // cut the next level down to the menus left to fetch, false when the crawl is over
+    fn trim_level(&self, frontier: &mut Frontier) -> bool {
+        frontier.menus.clear();
+        let (mut allowed, level) = (Vec::new(), std::mem::take(&mut frontier.level));
+        for req in level {
+            let referer = req.referer();
+            match self.rules.get(&(referer.domain.clone(), referer.port)) {
+                Some(rules) if !rules.allows(&referer.path) => frontier.disallowed.push(referer),
+                _ => allowed.push(req),
+            }
+        }
+        frontier.level = allowed;
+        if let Some(max_menus) = self.config.max_menus {
+            let room = max_menus.saturating_sub(frontier.menu_count);
+            if frontier.level.len() > room {
+                frontier.level.truncate(room);
+                frontier.limit_reached = Some(CrawlLimit::Menus);
+            }
+        }
+        for req in frontier.level.iter_mut() {
+            req.update_config(&self.config.request);
+        }
+        return !frontier.level.is_empty()
+    }
+
+    // collect the menus fetched on a level and queue the next one, None marks menus left out by the deadline
+    fn end_level(&self, frontier: &mut Frontier, fetches: Vec<Option<MenuFetch>>) {
+        let level = std::mem::take(&mut frontier.level);
+        let mut next_level: Vec<Request> = Vec::new();
+
+        for (request, fetch) in level.into_iter().zip(fetches) {
+            let Some(fetch) = fetch else {
+                frontier.limit_reached = Some(CrawlLimit::Duration);
+                continue;
+            };
+
+            if let Ok(items) = &fetch.items {
+                frontier.item_count += items.len();
+                let next = items.iter().flat_map(|item| { self.follow(item) });
+
+                if self.config.max_items.is_some_and(|max_items| { frontier.item_count >= max_items }) {
+                    frontier.limit_reached = Some(CrawlLimit::Items);
+                } else if self.config.max_depth.is_some_and(|max_depth| { frontier.depth >= max_depth }) {
+                    if next.count() > 0 {
+                        frontier.limit_reached.get_or_insert(CrawlLimit::Depth);
+                    }
+                } else {
+                    for next_req in next {
+                        if frontier.visited.insert(menu_key(&next_req.referer())) {
+                            next_level.push(next_req);
+                        }
+                    }
+                }
+            }
+
+            frontier.menu_count += 1;
+            frontier.menus.push(CrawledMenu { request, depth: frontier.depth, fetch });
+        }
+
+        if matches!(frontier.limit_reached, Some(CrawlLimit::Items | CrawlLimit::Duration)) {
+            next_level.clear();
+        }
+        frontier.level = next_level;
+        frontier.depth += 1;
+    }
+
+    // fetch a level on up to workers threads, results in the order of the level
+    fn fetch_level(&self, level: &[Request]) -> Vec<Option<MenuFetch>> {
+        let next = AtomicUsize::new(0);
+        let fetches: Mutex<Vec<Option<MenuFetch>>> = Mutex::new(level.iter().map(|_| { None }).collect());
+
+        thread::scope(|scope| {
+            for _ in 0..self.config.workers.clamp(1, level.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        if i >= level.len() || self.deadline_passed() {
+                            return
+                        }
+                        let fetch = self.fetch(&level[i]);
+                        fetches.lock().unwrap()[i] = Some(fetch);
+                    }
+                });
+            }
+        });
+
+        return fetches.into_inner().unwrap()
+    }
+
+    fn fetch(&self, req: &Request) -> MenuFetch {
+        let answered = AtomicBool::new(false);
+        let (items, attempts) = retry::run(&self.config.retry, || {
+            answered.store(false, Ordering::SeqCst);
+            let menu = req.menu()?;
+            answered.store(true, Ordering::SeqCst);
+            return menu.collect::<Result<Vec<Item>, GopherError>>()
+        });
+        if !answered.into_inner() {
+            return MenuFetch { answered: false, items, attempts, attributes: None }
+        }
+
+        let wants_attributes = self.config.plus
+            && items.as_ref().is_ok_and(|items| { items.iter().any(|item| { item.is_plus() }) });
+        let attributes = if wants_attributes {
+            let mut attr_req = req.clone();
+            attr_req.with_plus(PlusRequest::DirectoryAttributes);
+            Some(attr_req.send().and_then(|resp| { resp.as_attributes() }))
+        } else {
+            None
+        };
+
+        return MenuFetch { answered: true, items, attempts, attributes }
+    }
+
+    // fetch_level on tokio tasks, a menu waits while its host has no connection to spare
+    #[cfg(feature = "async")]
+    async fn async_fetch_level(&self, level: &[Request]) -> Vec<Option<MenuFetch>> {
+        let mut fetches: Vec<Option<MenuFetch>> = level.iter().map(|_| { None }).collect();
+        let mut queue: VecDeque<usize> = (0..level.len()).collect();
+        let mut active: HashMap<(String, u16), usize> = HashMap::new();
+        let mut tasks = JoinSet::new();
+
+        loop {
+            if self.deadline_passed() {
+                queue.clear();
+            }
+
+            // start queued menus in order, skipping those whose host has no connection to spare
+            let mut waiting: VecDeque<usize> = VecDeque::new();
+            while tasks.len() < self.config.workers.max(1) {
+                let Some(i) = queue.pop_front() else { break };
+                let referer = level[i].referer();
+                let max_conns = self.config.request.politeness.config().for_host(&referer.domain, referer.port).max_conns;
+                let host_conns = active.entry((referer.domain, referer.port)).or_insert(0);
+                if *host_conns >= max_conns {
+                    waiting.push_back(i);
+                    continue;
+                }
+                *host_conns += 1;
+                let req = level[i].clone();
+                let (plus, policy) = (self.config.plus, self.config.retry.clone());
+                tasks.spawn(async move { (i, async_fetch(&req, plus, &policy).await) });
+            }
+            waiting.append(&mut queue);
+            queue = waiting;
+
+            let (i, fetch) = match tasks.join_next().await {
+                Some(Ok(done)) => done,
+                Some(Err(error)) => std::panic::resume_unwind(error.into_panic()),
+                None => break,
+            };
+
+            let referer = level[i].referer();
+            if let Some(host_conns) = active.get_mut(&(referer.domain, referer.port)) {
+                *host_conns -= 1;
+            }
+            fetches[i] = Some(fetch);
+        }
+
+        return fetches
+    }
+}
+
+// request for an item carrying the request settings of config, gopher+ items are fetched
+// with a gopher+ request when config.plus is set
+pub fn item_request(config: &ClientConfig, item: &Item) -> Result<Request, GopherError> {
+    let mut req = Request::from_item(item)?;
+    req.update_config(&config.request);
+    if config.plus && item.is_plus() {
+        req.with_plus(PlusRequest::Fetch(item.mime().map(String::from)));
+    }
+    return Ok(req)
+}
+
+// Crawler::fetch on async sockets
+#[cfg(feature = "async")]
+async fn async_fetch(req: &Request, plus: bool, policy: &RetryPolicy) -> MenuFetch {
+    let answered = &AtomicBool::new(false);
+    let (items, attempts) = retry::async_run(policy, || async move {
+        answered.store(false, Ordering::SeqCst);
+        let resp = req.async_open().await?;
+        answered.store(true, Ordering::SeqCst);
+        return resp.menu().await
+    }).await;
+    if !answered.load(Ordering::SeqCst) {
+        return MenuFetch { answered: false, items, attempts, attributes: None }
+    }
+
+    let wants_attributes = plus
+        && items.as_ref().is_ok_and(|items| { items.iter().any(|item| { item.is_plus() }) });
+    let attributes = if wants_attributes {
+        let mut attr_req = req.clone();
+        attr_req.with_plus(PlusRequest::DirectoryAttributes);
+        Some(attr_req.async_send().await.and_then(|resp| { resp.as_attributes() }))
+    } else {
+        None
+    };
+
+    return MenuFetch { answered: true, items, attempts, attributes }
+}
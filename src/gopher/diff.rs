@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use path_clean::clean;
+use sha2::{Digest, Sha256};
+
+use super::Client;
+use super::checkpoint::Checkpoint;
+use super::error::GopherError;
+use super::types::Item;
+
+// Changes between a previous run (its last checkpoint, see checkpoint) and the current one.
+// Menus are compared by a digest of their lines and files by size and digest, so a re-crawl
+// can tell what moved without keeping the old responses around.
+
+// size and sha256 of a downloaded item
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileRecord {
+    pub size: u64,
+    // None for downloads recorded before digests were kept
+    pub hash: Option<String>,
+}
+
+impl FileRecord {
+    pub fn new(size: u64, hash: Option<String>) -> Self {
+        return Self { size, hash }
+    }
+}
+
+// lower-case hex of a digest
+pub fn hex(digest: &[u8]) -> String {
+    return digest.iter().map(|byte| { format!("{:02x}", byte) }).collect::<String>()
+}
+
+// sha256 of the lines of a menu in the order they were served
+pub fn menu_hash(items: &[Item]) -> String {
+    let mut hasher = Sha256::new();
+    for item in items {
+        hasher.update(item.menu_line().as_bytes());
+        hasher.update(b"\n");
+    }
+    return hex(&hasher.finalize())
+}
+
+// Reader passing a body through while taking its sha256, so a download is hashed as it is written
+pub struct HashReader<R: Read> {
+    reader: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> HashReader<R> {
+    pub fn new(reader: R) -> Self {
+        return Self { reader, hasher: Sha256::new() }
+    }
+
+    // sha256 of everything read so far
+    pub fn hash(self) -> String {
+        return hex(&self.hasher.finalize())
+    }
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.reader.read(buf)?;
+        self.hasher.update(&buf[..size]);
+        return Ok(size)
+    }
+}
+
+// sha256 of a local file, None when it can not be read
+pub fn file_hash<P: AsRef<Path>>(path: P) -> Option<String> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    let mut chunk: Vec<u8> = vec![0; 8192];
+    loop {
+        match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(size) => hasher.update(&chunk[..size]),
+            Err(_) => return None,
+        }
+    }
+    return Some(hex(&hasher.finalize()))
+}
+
+// a file downloaded by both runs whose content is not the same
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileChange {
+    pub url: String,
+    pub before: FileRecord,
+    pub after: FileRecord,
+}
+
+impl Display for FileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "URL: {}\nSize: {} -> {}\n", self.url, self.before.size, self.after.size)
+    }
+}
+
+// a menu scanned by both runs whose lines are not the same
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MenuChange {
+    pub url: String,
+    pub before: String,
+    pub after: String,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrawlDiff {
+    // items listed now but not by the previous run, in scan order
+    pub new_items: Vec<Item>,
+    // items listed by the previous run but not any more, in the previous run's order
+    pub removed_items: Vec<Item>,
+    pub changed_menus: Vec<MenuChange>,
+    pub changed_files: Vec<FileChange>,
+}
+
+impl CrawlDiff {
+    // compare the items, menus and downloads of client against those of an earlier run,
+    // info lines are left out of the new and removed items
+    pub fn between(previous: &Checkpoint, client: &Client) -> Self {
+        let data = |item: &&Item| { matches!(item, Item::DATA { .. }) };
+        let before = previous.items.iter().filter(data).collect::<HashSet<&Item>>();
+        let after = client.items.iter().filter(data).collect::<HashSet<&Item>>();
+
+        let changed_menus = client.referers.iter()
+            .map(|referer| { referer.to_string() })
+            .filter_map(|url| {
+                let (before, after) = (previous.menu_hashes.get(&url)?, client.menu_hashes.get(&url)?);
+                return (before != after).then(|| { MenuChange { url, before: before.clone(), after: after.clone() } })
+            })
+            .collect::<Vec<MenuChange>>();
+
+        let mut changed_files = client.downloads.iter()
+            .filter_map(|(url, after)| {
+                let before = previous.downloads.get(url)?;
+                let changed = match (&before.hash, &after.hash) {
+                    (Some(hash_before), Some(hash_after)) => hash_before != hash_after,
+                    _ => before.size != after.size,
+                };
+                return changed.then(|| { FileChange { url: url.clone(), before: before.clone(), after: after.clone() } })
+            })
+            .collect::<Vec<FileChange>>();
+        changed_files.sort_by(|a, b| { a.url.cmp(&b.url) });
+
+        return Self {
+            new_items: client.items.iter().filter(data).filter(|item| { !before.contains(item) }).cloned().collect(),
+            removed_items: previous.items.iter().filter(data).filter(|item| { !after.contains(item) }).cloned().collect(),
+            changed_menus,
+            changed_files,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.new_items.is_empty() && self.removed_items.is_empty()
+            && self.changed_menus.is_empty() && self.changed_files.is_empty()
+    }
+
+    // one change per line, fields separated by tabs:
+    //     new_item      <url>  <caption>
+    //     removed_item  <url>  <caption>
+    //     changed_menu  <url>  <sha256 before>  <sha256 after>
+    //     changed_file  <url>  <size before>  <size after>  <sha256 before | ->  <sha256 after | ->
+    pub fn write_tsv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for (kind, items) in [("new_item", &self.new_items), ("removed_item", &self.removed_items)] {
+            for item in items {
+                if let (Some(url), Item::DATA { caption, .. }) = (item.url(), item) {
+                    writeln!(writer, "{}\t{}\t{}", kind, url, caption)?;
+                }
+            }
+        }
+        for menu in &self.changed_menus {
+            writeln!(writer, "changed_menu\t{}\t{}\t{}", menu.url, menu.before, menu.after)?;
+        }
+        let hash = |record: &FileRecord| { record.hash.clone().unwrap_or(String::from("-")) };
+        for file in &self.changed_files {
+            writeln!(writer, "changed_file\t{}\t{}\t{}\t{}\t{}",
+                file.url, file.before.size, file.after.size, hash(&file.before), hash(&file.after)
+            )?;
+        }
+        return writer.flush()
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), GopherError> {
+        let full_path = clean(path);
+        if let Some(dir_prefix) = full_path.parent().filter(|dir| { !dir.as_os_str().is_empty() }) {
+            fs::create_dir_all(dir_prefix).map_err(|source| {
+                GopherError::InvalidPath { path: full_path.to_string_lossy().to_string(), source: Some(source) }
+            })?;
+        }
+        let local_io = |source| { GopherError::LocalIo { path: full_path.to_string_lossy().to_string(), source } };
+        let file = fs::File::create(&full_path).map_err(local_io)?;
+        return self.write_tsv(&mut BufWriter::new(file)).map_err(local_io)
+    }
+}
+
+// changes found by the scan, changed files are reported with the downloads
+impl Display for CrawlDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "New items: {}\nRemoved items: {}\nChanged menus: {}",
+            self.new_items.len(), self.removed_items.len(), self.changed_menus.len()
+        )?;
+        for item in &self.new_items {
+            write!(f, "\nAdded\n{}", item)?;
+        }
+        for item in &self.removed_items {
+            write!(f, "\nRemoved\n{}", item)?;
+        }
+        for menu in &self.changed_menus {
+            write!(f, "\nMenu changed\nURL: {}\n", menu.url)?;
+        }
+        return Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use std::{fmt::{self, Display}, io, str::{FromStr, Utf8Error}};
+
+use super::types::Item;
+
+// Failure kinds for every step of a gopher exchange, from resolving the
+// server address to writing the response to disk
+#[derive(Debug)]
+pub enum GopherError {
+    // domain could not be resolved to any socket address
+    Dns {
+        domain: String,
+        port: u16,
+        source: Option<io::Error>,
+    },
+    // no connection established within the connection timeout
+    ConnectTimeout {
+        domain: String,
+        port: u16,
+        source: io::Error,
+    },
+    // connection refused, reset or unreachable
+    Connect {
+        domain: String,
+        port: u16,
+        source: io::Error,
+    },
+    // request could not be written onto the stream
+    WriteFailure {
+        source: io::Error,
+    },
+    // server stopped responding within the response timeout
+    ReadTimeout {
+        source: io::Error,
+    },
+    // stream failed while reading the response
+    Read {
+        source: io::Error,
+    },
+    // local file or folder could not be created from the path given
+    InvalidPath {
+        path: String,
+        source: Option<io::Error>,
+    },
+    // local file could not be written to
+    LocalIo {
+        path: String,
+        source: io::Error,
+    },
+    // item can not be turned into a request (i.e. info items)
+    InvalidItem {
+        item: Box<Item>,
+    },
+    // search request whose selector or query holds a tab, CR or LF, which would add fields or request lines
+    InvalidRequest {
+        selector: String,
+        query: String,
+    },
+    // response or menu line is incomplete or mal-formatted
+    Parse {
+        message: String,
+    },
+    // response is expected as text but is not utf-8 encoded
+    NonUtf8 {
+        source: Utf8Error,
+    },
+    // server answered with an error item (type 3)
+    ServerError {
+        item: Box<Item>,
+    },
+    // TLS or proxy handshake failed after connecting
+    Handshake {
+        domain: String,
+        port: u16,
+        source: io::Error,
+    },
+    // gopher::server could not listen on the address given
+    Bind {
+        address: String,
+        source: io::Error,
+    },
+}
+
+impl GopherError {
+    pub fn parse(message: &str) -> Self {
+        return Self::Parse { message: String::from(message) }
+    }
+
+    // map a failed stream read, timeouts surface as WouldBlock or TimedOut depending on the platform
+    pub fn from_read(source: io::Error) -> Self {
+        return match source.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Self::ReadTimeout { source },
+            _ => Self::Read { source },
+        }
+    }
+
+    pub fn from_connect(domain: &str, port: u16, source: io::Error) -> Self {
+        let domain = String::from(domain);
+        return match source.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Self::ConnectTimeout { domain, port, source },
+            _ => Self::Connect { domain, port, source },
+        }
+    }
+
+    pub fn kind(&self) -> FailureKind {
+        return match self {
+            Self::Dns { .. } => FailureKind::Dns,
+            Self::ConnectTimeout { .. } => FailureKind::ConnectTimeout,
+            Self::Connect { .. } => FailureKind::Connect,
+            Self::WriteFailure { .. } => FailureKind::WriteFailure,
+            Self::ReadTimeout { .. } => FailureKind::ReadTimeout,
+            Self::Read { .. } => FailureKind::Read,
+            Self::InvalidPath { .. } => FailureKind::InvalidPath,
+            Self::LocalIo { .. } => FailureKind::LocalIo,
+            Self::InvalidItem { .. } => FailureKind::InvalidItem,
+            Self::InvalidRequest { .. } => FailureKind::InvalidRequest,
+            Self::Parse { .. } => FailureKind::Parse,
+            Self::NonUtf8 { .. } => FailureKind::NonUtf8,
+            Self::ServerError { .. } => FailureKind::ServerError,
+            Self::Handshake { .. } => FailureKind::Handshake,
+            Self::Bind { .. } => FailureKind::Bind,
+        }
+    }
+
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            Self::Dns { source, .. } | Self::InvalidPath { source, .. } => source.as_ref(),
+            Self::ConnectTimeout { source, .. }
+                | Self::Connect { source, .. }
+                | Self::WriteFailure { source }
+                | Self::ReadTimeout { source }
+                | Self::Read { source }
+                | Self::LocalIo { source, .. }
+                | Self::Handshake { source, .. }
+                | Self::Bind { source, .. } => Some(source),
+            Self::InvalidItem { .. } | Self::InvalidRequest { .. } | Self::Parse { .. } | Self::NonUtf8 { .. } | Self::ServerError { .. } => None,
+        }
+    }
+}
+
+impl Display for GopherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dns { domain, port, .. } => write!(f, "Domain resolution failed: {}:{}", domain, port),
+            Self::ConnectTimeout { domain, port, .. } => write!(f, "Connection timed out: {}:{}", domain, port),
+            Self::Connect { domain, port, source } => write!(f, "Connection failed: {}:{} ({})", domain, port, source),
+            Self::WriteFailure { source } => write!(f, "Stream write failed: {}", source),
+            Self::ReadTimeout { .. } => write!(f, "Response timed out"),
+            Self::Read { source } => write!(f, "Stream read failed: {}", source),
+            Self::InvalidPath { path, .. } => write!(f, "File path invalid: {}", path),
+            Self::LocalIo { path, source } => write!(f, "File write failed: {} ({})", path, source),
+            Self::InvalidItem { item } => write!(f, "Request initiation error: Invalid item!\n{}", item),
+            Self::InvalidRequest { selector, query } 
+                => write!(f, "Request initiation error: Tab, CR or LF in the search request: {:?} {:?}", selector, query),
+            Self::Parse { message } => write!(f, "Parse error: {}", message),
+            Self::NonUtf8 { .. } => write!(f, "Message does not support utf8 encoding or is corrupted"),
+            Self::ServerError { item } => write!(f, "Server responded with an error item\n{}", item),
+            Self::Handshake { domain, port, source } => write!(f, "Handshake failed: {}:{} ({})", domain, port, source),
+            Self::Bind { address, source } => write!(f, "Listening failed: {} ({})", address, source),
+        }
+    }
+}
+
+impl std::error::Error for GopherError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::NonUtf8 { source } => Some(source),
+            _ => self.io_error().map(|error| { error as &(dyn std::error::Error + 'static) }),
+        }
+    }
+}
+
+// GopherError without its details, what a retry policy picks the errors it retries by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "kebab-case"))]
+pub enum FailureKind {
+    Dns,
+    ConnectTimeout,
+    Connect,
+    WriteFailure,
+    ReadTimeout,
+    Read,
+    InvalidPath,
+    LocalIo,
+    InvalidItem,
+    InvalidRequest,
+    Parse,
+    NonUtf8,
+    ServerError,
+    Handshake,
+    Bind,
+}
+
+impl FailureKind {
+    pub const ALL: [FailureKind; 15] = [
+        Self::Dns, Self::ConnectTimeout, Self::Connect, Self::WriteFailure, Self::ReadTimeout, Self::Read,
+        Self::InvalidPath, Self::LocalIo, Self::InvalidItem, Self::InvalidRequest, Self::Parse, Self::NonUtf8, Self::ServerError,
+        Self::Handshake, Self::Bind,
+    ];
+
+    // network failures that tend to go away on their own, the kinds retried unless told otherwise
+    pub const TRANSIENT: [FailureKind; 5] = [Self::ConnectTimeout, Self::Connect, Self::WriteFailure, Self::ReadTimeout, Self::Read];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Self::Dns => "dns",
+            Self::ConnectTimeout => "connect-timeout",
+            Self::Connect => "connect",
+            Self::WriteFailure => "write-failure",
+            Self::ReadTimeout => "read-timeout",
+            Self::Read => "read",
+            Self::InvalidPath => "invalid-path",
+            Self::LocalIo => "local-io",
+            Self::InvalidItem => "invalid-item",
+            Self::InvalidRequest => "invalid-request",
+            Self::Parse => "parse",
+            Self::NonUtf8 => "non-utf8",
+            Self::ServerError => "server-error",
+            Self::Handshake => "handshake",
+            Self::Bind => "bind",
+        }
+    }
+}
+
+impl Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for FailureKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        return Self::ALL.into_iter()
+            .find(|kind| { kind.name().eq_ignore_ascii_case(name.trim()) })
+            .ok_or_else(|| {
+                let names = Self::ALL.iter().map(|kind| { kind.name() }).collect::<Vec<&str>>().join(", ");
+                return format!("Unknown error kind: {:?} ({})", name, names)
+            })
+    }
+}
+
+impl From<Utf8Error> for GopherError {
+    fn from(source: Utf8Error) -> Self {
+        return Self::NonUtf8 { source }
+    }
+}
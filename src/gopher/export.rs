@@ -0,0 +1,268 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use path_clean::clean;
+
+use super::Client;
+use super::error::GopherError;
+use super::types::Item;
+
+// Crawl exports
+// ======
+// Every item of a scan as one record with the fields
+//     parent    URL of the menu the item is listed on
+//     type      tag char of the item kind, empty for unparsed lines
+//     class     info | error | menu | search | text | binary | session | link
+//     caption   caption, the message of info lines or the raw unparsed line
+//     selector  selector the item points at
+//     host      host the item points at
+//     port      port the item points at, empty (null) for unparsed lines
+//     size      size of the downloaded file, empty (null) for info and unparsed lines
+//     url       gopher URL the item points at, empty (null) for info and unparsed lines
+// written as JSON Lines or CSV, or the menus as a Graphviz graph.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    JsonLines,
+    Csv,
+    Dot,
+}
+
+impl ExportFormat {
+    // format named by the extension of path
+    pub fn from_path(path: &str) -> Result<Self, GopherError> {
+        let extension = Path::new(path).extension().and_then(|extension| { extension.to_str() }).unwrap_or_default();
+        return extension.parse::<Self>().map_err(|message| { GopherError::Parse { message } })
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "jsonl" | "json" => Ok(Self::JsonLines),
+            "csv" => Ok(Self::Csv),
+            "dot" | "gv" => Ok(Self::Dot),
+            _ => Err(format!("Unknown export format: {:?} (jsonl, csv or dot)", name)),
+        }
+    }
+}
+
+struct ItemRecord {
+    parent: String,
+    kind: String,
+    class: String,
+    caption: String,
+    selector: String,
+    host: String,
+    port: Option<u16>,
+    size: Option<u64>,
+    url: Option<String>,
+}
+
+impl ItemRecord {
+    // the size is the one downloaded by client when the item was downloaded
+    fn new(item: &Item, client: &Client) -> Self {
+        let class = item.class().map(|class| { format!("{:?}", class).to_lowercase() }).unwrap_or_default();
+        match item {
+            Item::INFO { kind, from, message, domain, port } => Self {
+                parent: from.to_string(),
+                kind: kind.tag().to_string(),
+                class,
+                caption: message.clone(),
+                selector: String::new(),
+                host: domain.clone(),
+                port: Some(*port),
+                size: None,
+                url: None,
+            },
+            Item::DATA { kind, size, caption, referer, location, domain, port, .. } => Self {
+                parent: referer.to_string(),
+                kind: kind.tag().to_string(),
+                class,
+                caption: caption.clone(),
+                selector: location.clone(),
+                host: domain.clone(),
+                port: Some(*port),
+                size: item.url()
+                    .and_then(|url| { client.downloads.get(&url.to_string()) })
+                    .map(|record| { record.size })
+                    .or(Some(*size)),
+                url: item.url().map(|url| { url.to_string() }),
+            },
+            Item::UNKNOWN { from, unparsed } => Self {
+                parent: from.to_string(),
+                kind: String::new(),
+                class,
+                caption: unparsed.clone(),
+                selector: String::new(),
+                host: String::new(),
+                port: None,
+                size: None,
+                url: None,
+            },
+        }
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    return escaped
+}
+
+fn json_or_null<T: ToString>(value: Option<T>) -> String {
+    return value.map(|value| { value.to_string() }).unwrap_or(String::from("null"))
+}
+
+// quoted when it holds a separator, a quote or a line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        return format!("\"{}\"", value.replace('"', "\"\""))
+    }
+    return String::from(value)
+}
+
+fn dot_string(value: &str) -> String {
+    return format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+pub fn write_jsonl<W: Write>(writer: &mut W, client: &Client) -> io::Result<()> {
+    for record in client.items.iter().map(|item| { ItemRecord::new(item, client) }) {
+        writeln!(writer,
+            "{{\"parent\":{},\"type\":{},\"class\":{},\"caption\":{},\"selector\":{},\"host\":{},\"port\":{},\"size\":{},\"url\":{}}}",
+            json_string(&record.parent), json_string(&record.kind), json_string(&record.class),
+            json_string(&record.caption), json_string(&record.selector), json_string(&record.host),
+            json_or_null(record.port), json_or_null(record.size), json_or_null(record.url.as_deref().map(json_string))
+        )?;
+    }
+    return writer.flush()
+}
+
+pub fn write_csv<W: Write>(writer: &mut W, client: &Client) -> io::Result<()> {
+    writeln!(writer, "parent,type,class,caption,selector,host,port,size,url")?;
+    for record in client.items.iter().map(|item| { ItemRecord::new(item, client) }) {
+        writeln!(writer, "{},{},{},{},{},{},{},{},{}",
+            csv_field(&record.parent), csv_field(&record.kind), csv_field(&record.class),
+            csv_field(&record.caption), csv_field(&record.selector), csv_field(&record.host),
+            record.port.map(|port| { port.to_string() }).unwrap_or_default(),
+            record.size.map(|size| { size.to_string() }).unwrap_or_default(),
+            csv_field(record.url.as_deref().unwrap_or_default())
+        )?;
+    }
+    return writer.flush()
+}
+
+// menus as nodes labelled with their selector and menu items as edges from the menu listing them,
+// menus on the client's server at the top level and those of every other server in a cluster of its own
+pub fn write_dot<W: Write>(writer: &mut W, client: &Client) -> io::Result<()> {
+    let own_host = format!("{}:{}", client.domain, client.port);
+    // (url, host, label) of every menu in the order they are first seen
+    let mut nodes: Vec<(String, String, String)> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut edges: Vec<(String, String)> = Vec::new();
+    let mut linked: HashSet<(String, String)> = HashSet::new();
+
+    // menus are known by their gopher:// URL, whether the server is reached over TLS or not
+    let mut root = client.url();
+    root.update_tls(false);
+    seen.insert(root.to_string());
+    nodes.push((root.to_string(), own_host.clone(), client.root.clone()));
+    for referer in &client.referers {
+        if seen.insert(referer.to_string()) {
+            let label = match &referer.query {
+                Some(query) => format!("{} ? {}", referer.path, query),
+                None => referer.path.clone(),
+            };
+            nodes.push((referer.to_string(), format!("{}:{}", referer.domain, referer.port), label));
+        }
+    }
+    for item in client.items.iter().filter(|item| { item.is_menu() }) {
+        if let (Some(url), Item::DATA { referer, location, domain, port, .. }) = (item.url(), item) {
+            let (from, to) = (referer.to_string(), url.to_string());
+            if seen.insert(to.clone()) {
+                nodes.push((to.clone(), format!("{}:{}", domain, port), location.clone()));
+            }
+            if linked.insert((from.clone(), to.clone())) {
+                edges.push((from, to));
+            }
+        }
+    }
+
+    // the results of a search hang off the search item
+    for referer in client.referers.iter().filter(|referer| { referer.query.is_some() }) {
+        let mut url = referer.url();
+        url.update_search(None);
+        if linked.insert((url.to_string(), referer.to_string())) {
+            edges.push((url.to_string(), referer.to_string()));
+        }
+    }
+
+    let node_line = |(url, _, label): &(String, String, String)| {
+        return format!("{} [label={}];", dot_string(url), dot_string(label))
+    };
+
+    writeln!(writer, "digraph gopher {{")?;
+    writeln!(writer, "    label={};", dot_string(&own_host))?;
+    writeln!(writer, "    node [shape=box];")?;
+    for node in nodes.iter().filter(|(_, host, _)| { *host == own_host }) {
+        writeln!(writer, "    {}", node_line(node))?;
+    }
+
+    let mut external_hosts: Vec<&String> = Vec::new();
+    for (_, host, _) in &nodes {
+        if *host != own_host && !external_hosts.contains(&host) {
+            external_hosts.push(host);
+        }
+    }
+    for (i, external_host) in external_hosts.iter().enumerate() {
+        writeln!(writer, "    subgraph cluster_{} {{", i)?;
+        writeln!(writer, "        label={};", dot_string(external_host))?;
+        for node in nodes.iter().filter(|(_, host, _)| { host == *external_host }) {
+            writeln!(writer, "        {}", node_line(node))?;
+        }
+        writeln!(writer, "    }}")?;
+    }
+
+    for (from, to) in &edges {
+        writeln!(writer, "    {} -> {};", dot_string(from), dot_string(to))?;
+    }
+    writeln!(writer, "}}")?;
+    return writer.flush()
+}
+
+pub fn write<W: Write>(writer: &mut W, client: &Client, format: ExportFormat) -> io::Result<()> {
+    return match format {
+        ExportFormat::JsonLines => write_jsonl(writer, client),
+        ExportFormat::Csv => write_csv(writer, client),
+        ExportFormat::Dot => write_dot(writer, client),
+    }
+}
+
+// write the export to path in the format named by its extension
+pub fn save(path: &str, client: &Client) -> Result<(), GopherError> {
+    let format = ExportFormat::from_path(path)?;
+    let full_path = clean(path);
+    if let Some(dir_prefix) = full_path.parent().filter(|dir| { !dir.as_os_str().is_empty() }) {
+        fs::create_dir_all(dir_prefix).map_err(|source| {
+            GopherError::InvalidPath { path: full_path.to_string_lossy().to_string(), source: Some(source) }
+        })?;
+    }
+    let local_io = |source| { GopherError::LocalIo { path: full_path.to_string_lossy().to_string(), source } };
+    let file = fs::File::create(&full_path).map_err(local_io)?;
+    return write(&mut BufWriter::new(file), client, format).map_err(local_io)
+}
@@ -1,303 +1,468 @@
 use chrono::Utc;
 use std::{str, fs};
-use std::sync::Mutex;
-use path_clean::{clean, PathClean};
-use std::path::PathBuf;
-use std::time::Duration;
-use regex::{Regex, RegexBuilder};
-use std::io::{Write, Bytes, Read, ErrorKind};
-use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use path_clean::clean;
+use std::path::{Path, PathBuf};
+use std::net::SocketAddr;
+use std::io::{Write, Read, BufRead, BufReader, ErrorKind};
 
-use super::types::{Item, Referer};
-use crate::cli::CLI_ARGS;
+use super::config::RequestConfig;
+use super::diff::HashReader;
+use super::error::GopherError;
+use super::types::{Item, ItemKind, Referer};
+use super::url::GopherUrl;
+use super::plus::{PlusAttributes, PlusHeader, PlusRequest};
+use super::stream::{DataReader, MenuReader, Response, TextReader};
+use super::transport::{self, Connection};
+
+#[cfg(feature = "async")]
+mod nonblocking;
+#[cfg(feature = "async")]
+pub use nonblocking::AsyncResponse;
+
+// Selectors are opaque strings to the client. A Request sends its selector byte for byte as the item or URL gave
+// it, with "..", trailing slashes, backslashes, "URL:" prefixes and all. Only where a response is written to disk
+// is the selector turned into a path, by local_path.
+
+// path of a selector under a served root, without a leading "/". "/" and "\" separate folders,
+// "." and empty parts are left out and ".." goes up a folder without climbing out of the root.
+// Selectors mapping to no file at all, like "/", come out empty
+pub fn selector_path(selector: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in selector.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => { parts.pop(); }
+            part => parts.push(part),
+        }
+    }
+    return parts.join("/")
+}
+
+// path of a selector under a download or mirror root, its selector_path with a "~" put in front of every part
+// named like a file the mirror writes, after any number of "~". No download then takes the place of a mirrored
+// menu, or the other way round, and two selectors still never share a path
+pub fn local_path(selector: &str) -> String {
+    return selector_path(selector).split('/').map(|part| { local_name(part) }).collect::<Vec<String>>().join("/")
+}
+
+pub fn local_name(name: &str) -> String {
+    return match name.trim_start_matches('~') {
+        "gophermap" | "index.html" => format!("~{}", name),
+        _ => String::from(name),
+    }
+}
 
 #[derive(Clone)]
 pub struct Request {
     port: u16,
     path: String,
     domain: String,
-    conn_timeout: Duration,
-    resp_timeout: Duration,
+    // search terms sent after the selector, for search items (type 7)
+    query: Option<String>,
+    // gopher+ field sent after the selector and search terms
+    plus: Option<PlusRequest>,
+    config: RequestConfig,
 }
 
 impl Request {
     pub fn new(domain: &str, port: u16) -> Self {
         return Self { 
-            port: port,
+            port,
             path: "/".to_string(),
             domain: String::from(domain),
-            conn_timeout: Duration::from_secs(CLI_ARGS.conn_timeout),
-            resp_timeout: Duration::from_secs(CLI_ARGS.resp_timeout),
+            query: None,
+            plus: None,
+            config: RequestConfig::default(),
         }
     }
 
-    pub fn from_item(item: &Item) -> Result<Self, String> {
-        if let Item::DATA { tag, size, caption, referer, location, domain, port } = item {
-            let mut loc = clean(location).to_str().unwrap().replace(r"\", r"/");
-            if loc.is_empty() { loc = "/".to_string(); }
+    pub fn from_item(item: &Item) -> Result<Self, GopherError> {
+        if let Item::DATA { location, domain, port, .. } = item {
             return Ok(Self {
                 port: *port,
-                path: loc,
+                path: location.clone(),
                 domain: domain.clone(),
-                conn_timeout: Duration::from_secs(CLI_ARGS.conn_timeout),
-                resp_timeout: Duration::from_secs(CLI_ARGS.resp_timeout),
+                query: None,
+                plus: None,
+                config: RequestConfig::default(),
             })
         } else {
-            return Err("Request initiation error: Invalid item!".to_string())
+            return Err(GopherError::InvalidItem { item: Box::new(item.clone()) })
         }
     }
     
+    pub fn from_url(url: &GopherUrl) -> Self {
+        let mut req = Self::new(&url.domain, url.port);
+        req.update_path(&url.selector);
+        req.query = url.search.clone();
+        return req
+    }
+
+    pub fn url(&self) -> GopherUrl {
+        return self.referer().url()
+    }
+
+    // where the response of this request comes from
+    pub fn referer(&self) -> Referer {
+        let mut referer = Referer::new(&self.domain, self.port, &self.path);
+        referer.update_query(self.query.as_deref());
+        return referer
+    }
+
+    // send query as the search string, the request becomes "selector\tquery\r\n", a query or selector with a tab,
+    // CR or LF in it is refused with GopherError::InvalidRequest when the request is sent
+    pub fn with_query(&mut self, query: &str) -> &mut Self {
+        self.query = Some(String::from(query));
+        return self
+    }
+
+    // make this a gopher+ request, the response then starts with a gopher+ header
+    pub fn with_plus(&mut self, plus: PlusRequest) -> &mut Self {
+        self.plus = Some(plus);
+        return self
+    }
+
+    pub fn update_config(&mut self, config: &RequestConfig) -> &mut Self {
+        self.config = config.clone();
+        return self
+    }
+
+    // the selector is sent byte for byte as given, see local_path for where its response is saved
     pub fn update_path(&mut self, new_path: &str) -> &mut Self {
-        self.path = clean(new_path).to_str().unwrap().replace(r"\", r"/");
-        if self.path.is_empty() { self.path = "/".to_string(); }
+        self.path = String::from(new_path);
         return self
     }
 
+    // the line written to the server, a search that would carry extra fields or lines is refused
+    fn request_line(&self) -> Result<String, GopherError> {
+        if let Some(query) = &self.query {
+            let breaks_line = |field: &str| { field.contains(['\t', '\r', '\n']) };
+            if breaks_line(&self.path) || breaks_line(query) {
+                return Err(GopherError::InvalidRequest { selector: self.path.clone(), query: query.clone() })
+            }
+        }
+
+        // without domain
+        let req_buff = match (&self.query, &self.plus) {
+            (_, Some(plus @ (PlusRequest::Attributes | PlusRequest::DirectoryAttributes))) 
+                => format!("{}\t{}\r\n", self.path, plus.field()),
+            (Some(query), Some(plus)) => format!("{}\t{}\t{}\r\n", self.path, query, plus.field()),
+            (None, Some(plus)) => format!("{}\t{}\r\n", self.path, plus.field()),
+            (Some(query), None) => format!("{}\t{}\r\n", self.path, query),
+            (None, None) => format!("{}\r\n", self.path),
+        };
+        
+        // with domain
+        // let req_buff = format!("{}\t{}\r\n\r\n", self.path, self.domain);
+
+        return Ok(req_buff)
+    }
+
+    // report the request line written when verbose, with the address that answered when known,
+    // the address is recorded in the address log of the config either way
+    fn report_sent(&self, req_buff: &str, address: Option<SocketAddr>) {
+        self.config.addresses.record(&self.referer(), address);
+        if self.config.verbose {
+            let address = address.map(|address| { format!("Address: {}\n", address) }).unwrap_or_default();
+            println!("==Request sent==\nQuery: {:?}\nTarget: {}\nServer: {}:{}\n{}Time: {}\n", 
+                req_buff, 
+                self.path, 
+                GopherUrl::new(&self.domain, self.port).host(), 
+                self.port, 
+                address,
+                Utc::now().format("%Y-%m-%d %H:%M:%S %Z")
+            );
+        }
+    }
+
+    // connect through the transport of the config, over TLS when it says so, and write the request line
+    fn open_stream(&self) -> Result<Box<dyn Connection>, GopherError> {
+        let req_buff = self.request_line()?;
+        // an attempt that connects nowhere leaves no address behind
+        self.config.addresses.record(&self.referer(), None);
+        let mut stream = transport::open(&self.config, &self.domain, self.port)?;
+
+        self.report_sent(&req_buff, stream.peer_addr());
+        stream.write_all(req_buff.as_bytes()).map_err(|source| { GopherError::WriteFailure { source } })?;
+        stream.set_read_timeout(Some(self.config.resp_timeout)).map_err(|source| { GopherError::Read { source } })?;
+        return Ok(stream)
+    }
+
+    // error sent in place of a gopher+ body
+    fn plus_error(&self, body: &[u8]) -> GopherError {
+        return GopherError::ServerError { item: Box::new(Item::INFO {
+            kind: ItemKind::Error,
+            from: self.referer(),
+            message: String::from(String::from_utf8_lossy(body).trim()),
+            domain: self.domain.clone(),
+            port: self.port,
+        }) }
+    }
+
+    // send request to server and return the response body as a stream,
+    // a gopher+ header is read and checked before the body is handed out
+    pub fn open(&self) -> Result<Response, GopherError> {
+        let mut reader = BufReader::new(self.open_stream()?);
+
+        let plus = if self.plus.is_some() {
+            let mut header_line = String::new();
+            match reader.read_line(&mut header_line) {
+                Ok(0) => { return Err(GopherError::parse("Response ended before the gopher+ header!")) }
+                Ok(_) => {}
+                Err(error) => { return Err(read_error(error)) }
+            }
+            let header = PlusHeader::parse(&header_line)?;
+            if !header.success {
+                let mut message: Vec<u8> = Vec::new();
+                reader.read_to_end(&mut message).unwrap_or_default();
+                return Err(self.plus_error(&message))
+            }
+            Some(header)
+        } else {
+            None
+        };
+
+        return Ok(Response::new(reader, self.referer(), plus))
+    }
+
+    // send request to server and parse the menu items as they arrive
+    pub fn menu(&self) -> Result<MenuReader<BufReader<Response>>, GopherError> {
+        return Ok(self.open()?.menu())
+    }
+
     // send request to server and collect response
-    pub fn send(&self) -> Result<ResponseBuilder, String> {
-        if let Some(sock_addr) = (String::from(&self.domain), self.port).to_socket_addrs().unwrap().next() {
-            if let Ok(mut stream) = TcpStream::connect_timeout(
-                &sock_addr, 
-                self.conn_timeout
-            ) {
-                // without domain
-                let req_buff = format!("{}\r\n", self.path);
-                
-                // with domain
-                // let req_buff = format!("{}\t{}\r\n\r\n", self.path, self.domain);
-
-                if !CLI_ARGS.disable_verbose {
-                    println!("==Request sent==\nQuery: {:?}\nTarget: {}\nServer: {}:{}\nTime: {}\n", 
-                        req_buff, 
-                        self.path, 
-                        self.domain, 
-                        self.port, 
-                        Utc::now().format("%Y-%m-%d %H:%M:%S %Z").to_string()
-                    );
-                }
-
-                if let Ok(_) = stream.write(req_buff.as_bytes()) {
-                    let mut resp_buff: Vec<u8> = Vec::new();
-                    loop {
-                        let mut chunk: Vec<u8> = vec![0; 4096]; // upsize to 6144 or 8192 if network condition allows
-                        stream.set_read_timeout(Some(self.resp_timeout)).unwrap();
-
-                        match stream.read(&mut chunk) {
-                            Ok(0) => { break; }
-                            Ok(resp_size) => {
-                                resp_buff.extend_from_slice(&chunk[0..resp_size])
-                            }
-                            Err(error) => {
-                                if error.kind() == ErrorKind::WouldBlock {
-                                    println!("Error: response timed out\n");
-                                }
-                                return Err("Unknown read error ...".to_string());
-                            }
-                        }
-                    }
-
-                    if let Err(_) = stream.shutdown(std::net::Shutdown::Both) {
-                        println!("==Stream shutdown failed!==");
-                    }
-
-                    return Ok(ResponseBuilder {
-                        raw: resp_buff,
-                        referer: Referer::new(&self.domain, self.port, &self.path)
-                    })
-                } else { return Err("Stream write failed ...".to_string()) }
-            } else { return Err("Connection timed out ...".to_string()) }
-        } else { return Err("Ip address parse error ...".to_string()) }
+    pub fn send(&self) -> Result<ResponseBuilder, GopherError> {
+        let mut resp = self.open()?;
+        let mut resp_buff: Vec<u8> = Vec::new();
+        resp.read_to_end(&mut resp_buff).map_err(read_error)?;
+        resp.shutdown();
+
+        return Ok(ResponseBuilder {
+            raw: resp_buff,
+            referer: self.referer(),
+            verbose: self.config.verbose,
+            plus: resp.plus,
+        })
     }
 
-    // use this only for download large files
-    pub fn download(&self, dest_prefix: &str) -> Result<u64, (u16, String)> {
-        let full_path = clean(format!("{}/{}", dest_prefix, self.path));
-        if let (Some(dir_prefix), Some(file_name)) = (full_path.parent(), full_path.file_name()) {
-            return self.download_as(dir_prefix.to_str().unwrap(), file_name.to_str().unwrap())
+    // folder and file name the selector is saved as under dest_prefix, an error for selectors mapping to no file
+    fn local_target(&self, dest_prefix: &str) -> Result<(String, String), GopherError> {
+        let local = local_path(&self.path);
+        let full_path = clean(format!("{}/{}", dest_prefix, local));
+        if let (false, Some(dir_prefix), Some(file_name)) = (local.is_empty(), full_path.parent(), full_path.file_name()) {
+            return Ok((dir_prefix.to_string_lossy().to_string(), file_name.to_string_lossy().to_string()))
         } else {
-            return Err((0, "Download path parse error: check the prefix variable or use function download_as().".to_string()))
+            return Err(GopherError::InvalidPath { 
+                path: full_path.to_string_lossy().to_string(), 
+                source: None 
+            })
         }
     }
 
     // use this only for download large files
-    pub fn download_as(&self, dir_prefix: &str, file_name: &str) -> Result<u64, (u16, String)> {
+    pub fn download(&self, dest_prefix: &str) -> Result<u64, GopherError> {
+        let (dir_prefix, file_name) = self.local_target(dest_prefix)?;
+        return self.download_as(&dir_prefix, &file_name)
+    }
+
+    // use this only for download large files
+    pub fn download_as(&self, dir_prefix: &str, file_name: &str) -> Result<u64, GopherError> {
         let full_path = clean(format!("{}/{}", dir_prefix, file_name));
+        let mut file = create_file(dir_prefix, &full_path)?;
 
-        if let (Ok(()), Ok(mut file)) = 
-            (fs::create_dir_all(&dir_prefix), fs::File::create(&full_path)) 
-        {
-            if let Some(sock_addr) = (String::from(&self.domain), self.port).to_socket_addrs().unwrap().next() {
-                if let Ok(mut stream) = TcpStream::connect_timeout(
-                    &sock_addr, 
-                    self.conn_timeout
-                ) {
-                    // without domain
-                    let req_buff = format!("{}\r\n", self.path);
-                    
-                    // with domain
-                    // let req_buff = format!("{}\t{}\r\n\r\n", self.path, self.domain);
-    
-                    if !CLI_ARGS.disable_verbose {
-                        println!("==Request sent==\nQuery: {:?}\nTarget: {}\nServer: {}:{}\nTime: {}\n", 
-                            req_buff, 
-                            self.path, 
-                            self.domain, 
-                            self.port, 
-                            Utc::now().format("%Y-%m-%d %H:%M:%S %Z").to_string()
-                        );
-                    }
-    
-                    if let Ok(_) = stream.write(req_buff.as_bytes()) {
-                        let mut file_size: u64 = 0;
-                        loop {
-                            let mut chunk: Vec<u8> = vec![0; 2048];
-                            stream.set_read_timeout(Some(self.resp_timeout)).unwrap();
-    
-                            match stream.read(&mut chunk) {
-                                Ok(0) => { break; }
-                                Ok(resp_size) => {
-                                    file.write_all(&chunk[0..resp_size]).unwrap();
-                                    file_size += resp_size as u64;
-                                }
-                                Err(error) => {
-                                    if error.kind() == ErrorKind::WouldBlock {
-                                        println!("Error: response timed out\n");
-                                    }
-                                    return Err((4, "Unknown read error ...".to_string()));
-                                }
-                            }
-                        }
-    
-                        if let Err(_) = stream.shutdown(std::net::Shutdown::Both) {
-                            println!("==Stream shutdown failed!==");
-                        }
-
-                        if !CLI_ARGS.disable_verbose {
-                            println!("\n==File downloaded==\nFolder:{}\nName:{}\n", &dir_prefix, &file_name);
-                        }
-
-                        return Ok(file_size)
-                    } else { return Err((3, "Stream write failed!".to_string())) }
-                } else { return Err((2, "Connection timed out!".to_string())) }
-            } else { return Err((1, "Ip address parse error!".to_string())) }
+        let mut resp = self.open()?;
+        self.report_plus(resp.plus.as_ref());
+        let file_size = copy_to_file(&mut resp.data(), &mut file, &full_path)?;
+        resp.shutdown();
+
+        if self.config.verbose {
+            println!("\n==File downloaded==\nFolder:{}\nName:{}\n", &dir_prefix, &file_name);
+        }
+
+        return Ok(file_size)
+    }
+
+    // download a text file, the "." terminator line is not saved
+    pub fn download_txt(&self, dest_prefix: &str) -> Result<u64, GopherError> {
+        let (dir_prefix, file_name) = self.local_target(dest_prefix)?;
+        return self.download_txt_as(&dir_prefix, &file_name)
+    }
+
+    // download a text file, the "." terminator line is not saved
+    pub fn download_txt_as(&self, dir_prefix: &str, file_name: &str) -> Result<u64, GopherError> {
+        let full_path = clean(format!("{}/{}", dir_prefix, file_name));
+        let mut file = create_file(dir_prefix, &full_path)?;
+
+        let resp = self.open()?;
+        self.report_plus(resp.plus.as_ref());
+        let file_size = copy_to_file(&mut resp.text(), &mut file, &full_path)?;
+
+        if self.config.verbose {
+            println!("\n==File saved==\nFolder:{}\nName:{}\n", dir_prefix, file_name);
+        }
+
+        return Ok(file_size)
+    }
+
+    // download an item saved before with hash again, into a temporary file next to its target and hashed on the way.
+    // The target is only replaced when the hash changed, the size is returned with whether it was
+    pub fn download_changed(&self, dest_prefix: &str, hash: &str, text: bool) -> Result<(u64, bool), GopherError> {
+        let (dir_prefix, file_name) = self.local_target(dest_prefix)?;
+        let mut resp = self.open()?;
+        self.report_plus(resp.plus.as_ref());
+
+        let part_path = part_path(&dir_prefix, &file_name);
+        let mut file = create_file(&dir_prefix, &part_path)?;
+        let mut body = HashReader::new(if text { Box::new(resp.text()) as Box<dyn Read> } else { Box::new(resp.data()) });
+        let copied = copy_to_file(&mut body, &mut file, &part_path).map(|file_size| { (file_size, body.hash()) });
+        drop(file);
+        return self.replace_changed(copied, &part_path, &dir_prefix, &file_name, hash)
+    }
+
+    // put the temporary file of download_changed in place of its target when the hash changed, remove it otherwise
+    fn replace_changed(
+        &self, copied: Result<(u64, String), GopherError>, part_path: &Path, dir_prefix: &str, file_name: &str, hash: &str,
+    ) -> Result<(u64, bool), GopherError> {
+        let full_path = clean(format!("{}/{}", dir_prefix, file_name));
+        let local_io = |path: &Path, source| { GopherError::LocalIo { path: path.to_string_lossy().to_string(), source } };
+        let (file_size, new_hash) = match copied {
+            Ok(copied) => copied,
+            Err(error) => { fs::remove_file(part_path).unwrap_or_default(); return Err(error) }
+        };
+        if new_hash == hash {
+            fs::remove_file(part_path).map_err(|source| { local_io(part_path, source) })?;
+            return Ok((file_size, false))
+        }
+        fs::rename(part_path, &full_path).map_err(|source| { local_io(&full_path, source) })?;
+
+        if self.config.verbose {
+            println!("\n==File changed==\nFolder:{}\nName:{}\n", dir_prefix, file_name);
+        }
+        return Ok((file_size, true))
+    }
+
+    fn report_plus(&self, plus: Option<&PlusHeader>) {
+        if let (Some(header), true) = (plus, self.config.verbose) {
+            println!("==Gopher+ response==\nTarget: {}\nMIME: {}\nExpected size: {}\n",
+                self.url(),
+                self.mime().unwrap_or("unknown"),
+                header.size().map(|size| { size.to_string() }).unwrap_or("unknown".to_string())
+            );
+        }
+    }
+
+    // MIME type of the gopher+ view requested
+    pub fn mime(&self) -> Option<&str> {
+        if let Some(PlusRequest::Fetch(Some(view))) = &self.plus {
+            return view.split_whitespace().next()
         } else {
-            return Err((0, "File path invalid!".to_string()))
+            return None
         }
     }
 }
 
+fn read_error(error: std::io::Error) -> GopherError {
+    if error.kind() == ErrorKind::WouldBlock {
+        println!("Error: response timed out\n");
+    }
+    return GopherError::from_read(error)
+}
+
+// temporary file a download_changed is written to, next to its target
+fn part_path(dir_prefix: &str, file_name: &str) -> PathBuf {
+    return clean(format!("{}/.{}.part", dir_prefix, file_name))
+}
+
+// create dir_prefix and all parent folders before creating the file at full_path
+fn create_file<P: AsRef<Path>>(dir_prefix: P, full_path: &Path) -> Result<fs::File, GopherError> {
+    let invalid_path = |source| { 
+        GopherError::InvalidPath { path: full_path.to_string_lossy().to_string(), source: Some(source) } 
+    };
+    fs::create_dir_all(dir_prefix).map_err(invalid_path)?;
+    return fs::File::create(full_path).map_err(invalid_path)
+}
+
+// copy a response chunk by chunk into file, memory use stays the same whatever the file size
+fn copy_to_file<R: Read>(resp: &mut R, file: &mut fs::File, full_path: &Path) -> Result<u64, GopherError> {
+    let mut file_size: u64 = 0;
+    let mut chunk: Vec<u8> = vec![0; 2048];
+    loop {
+        match resp.read(&mut chunk) {
+            Ok(0) => { break; }
+            Ok(resp_size) => {
+                file.write_all(&chunk[0..resp_size]).map_err(|source| { 
+                    GopherError::LocalIo { path: full_path.to_string_lossy().to_string(), source } 
+                })?;
+                file_size += resp_size as u64;
+            }
+            Err(error) if error.kind() == ErrorKind::Interrupted => { continue; }
+            Err(error) => { return Err(read_error(error)) }
+        }
+    }
+    return Ok(file_size)
+}
+
+// write a response held in memory to a newly created file at full_path
+fn write_file<P: AsRef<Path>, R: Read>(dir_prefix: P, full_path: &Path, mut resp: R) -> Result<u64, GopherError> {
+    let mut file = create_file(dir_prefix, full_path)?;
+    return copy_to_file(&mut resp, &mut file, full_path)
+}
+
 pub struct ResponseBuilder {
     pub raw: Vec::<u8>,
     pub referer: Referer,
+    pub verbose: bool,
+    // header of a gopher+ response, already stripped from raw
+    pub plus: Option<PlusHeader>,
 }
 
 impl ResponseBuilder {
-    pub fn as_items(&self) -> Result<Vec<Item>, String> {
-        if let Ok(content) = str::from_utf8(&self.raw) {
-            // check if content ends with /r/n
-            let matcher = RegexBuilder::new("^(.*)\r??\n??.\r\n$").dot_matches_new_line(true).build().unwrap();
-
-            if let Some(captured) = matcher.captures(content) {
-                let items: Vec<Item> = captured.get(1).unwrap()
-                    .as_str().split("\r\n")
-                    .map(|line| {Item::from_str(&self.referer, line).unwrap()})
-                    .collect();
-                
-                return Ok(items)
-            }
+    // a response made up of an error item is the server refusing the selector
+    pub fn as_items(&self) -> Result<Vec<Item>, GopherError> {
+        return MenuReader::new(&self.raw[..], self.referer.clone()).collect()
+    }
 
-            return Err("Parse error: Response message incomplete or mal-formatted!".to_string())
-        } else {
-            return Err("Message does not support utf8 encoding or is corrupted".to_string())
+    pub fn save_to_file(&mut self, dest_prefix: &str) -> Result<u64, GopherError> {
+        let local = local_path(&self.referer.path);
+        let full_path = clean(format!("{}/{}", dest_prefix, local));
+        if let (false, Some(dir_prefix)) = (local.is_empty(), full_path.parent()) {
+            return write_file(dir_prefix, &full_path, DataReader::new(&self.raw[..], self.plus.as_ref()))
         }
+        return Err(GopherError::InvalidPath { path: full_path.to_string_lossy().to_string(), source: None })
     }
 
-    pub fn save_to_file(&mut self, dest_prefix: &str) -> Result<u64, String> {
-        let full_path = clean(format!("{}/{}", dest_prefix, self.referer.path));
-        if let Some(dir_prefix) = full_path.parent() {
-            if let (Ok(()), Ok(mut file)) = (
-                fs::create_dir_all(&dir_prefix), fs::File::create(&full_path)
-            ) {
-                file.write_all(&self.raw).unwrap();
-                return Ok(self.raw.len() as u64)
-            }
+    pub fn save_as_file(&mut self, dir_prefix: &str, file_name: &str) -> Result<u64, GopherError> {
+        let full_path = clean(format!("{}/{}", dir_prefix, file_name));
+        return write_file(dir_prefix, &full_path, DataReader::new(&self.raw[..], self.plus.as_ref()))
+    }
+
+    // save as a text file, the "." terminator line is not saved
+    pub fn save_to_txt(&mut self, dest_prefix: &str) -> Result<u64, GopherError> {
+        let local = local_path(&self.referer.path);
+        let full_path = clean(format!("{}/{}", dest_prefix, local));
+        if let (false, Some(dir_prefix), Some(file_name)) = (local.is_empty(), full_path.parent(), full_path.file_name()) {
+            return self.save_as_txt(dir_prefix.to_str().unwrap(), file_name.to_str().unwrap())
         }
-        return Err("File path invalid!".to_string())
+        return Err(GopherError::InvalidPath { path: full_path.to_string_lossy().to_string(), source: None })
     }
 
-    pub fn save_as_file(&mut self, dir_prefix: &str, file_name: &str) -> Result<u64, String> {
+    // save as a text file, the "." terminator line is not saved
+    pub fn save_as_txt(&mut self, dir_prefix: &str, file_name: &str) -> Result<u64, GopherError> {
         let full_path = clean(format!("{}/{}", dir_prefix, file_name));
+        let file_size = write_file(dir_prefix, &full_path, TextReader::new(&self.raw[..]))?;
 
-        if let (Ok(()), Ok(mut file)) = (
-            fs::create_dir_all(&dir_prefix), fs::File::create(&full_path)
-        ) {
-            file.write_all(&self.raw).unwrap();
-            return Ok(self.raw.len() as u64)
-        } else {
-            return Err("File path invalid!".to_string())
+        if self.verbose {
+            println!("\n==File saved==\nFolder:{}\nName:{}\n", dir_prefix, file_name);
         }
+        return Ok(file_size)
     }
 
-    pub fn save_to_txt(&mut self, dest_prefix: &str) -> Result<u64, String> {
-        if let Ok(content) = str::from_utf8(&self.raw) {
-            // check if content ends with ./r/n
-            let matcher = RegexBuilder::new("^(.*).\r\n$").dot_matches_new_line(true).build().unwrap();
-
-            if let Some(captured) = matcher.captures(content) {
-                let full_path = clean(format!("{}/{}", dest_prefix, self.referer.path));
-                if let Some(dir_prefix) = full_path.parent() {
-                    if let (Ok(()), Ok(mut file)) = (
-                        fs::create_dir_all(&dir_prefix), 
-                        fs::File::create(&full_path)
-                    ) {
-                        let write_buff = captured.get(1).unwrap().as_str().as_bytes();
-                        file.write_all(write_buff).unwrap();
-
-                        if !CLI_ARGS.disable_verbose {
-                            println!("\n==File saved==\nFolder:{}\nName:{}\n", 
-                                &dir_prefix.to_str().unwrap(), 
-                                &full_path.file_name().unwrap().to_str().unwrap()
-                            );
-                        }
-                        return Ok(write_buff.len() as u64)
-                    }
-                }
-                return Err("File path invalid!".to_string())
-            }
-        }
-        return self.save_to_file(dest_prefix)
-    }
-
-    pub fn save_as_txt(&mut self, dir_prefix: &str, file_name: &str) -> Result<u64, String> {
-        if let Ok(content) = str::from_utf8(&self.raw) {
-            // check if content ends with ./r/n
-            let matcher = RegexBuilder::new("^(.*).\r\n$").dot_matches_new_line(true).build().unwrap();
-            if let Some(captured) = matcher.captures(content) {
-                let full_path = clean(format!("{}/{}", dir_prefix, file_name));
-
-                if let (Ok(()), Ok(mut file)) = (
-                    fs::create_dir_all(&dir_prefix), fs::File::create(&full_path)
-                ) {
-                    let write_buff = captured.get(1).unwrap().as_str().as_bytes();
-                    file.write_all(write_buff).unwrap();
-
-                    if !CLI_ARGS.disable_verbose {
-                        println!("\n==File saved==\nFolder:{}\nName:{}\n", dir_prefix, file_name);
-                    }
-                    return Ok(write_buff.len() as u64)
-                } else {
-                    return Err("File path invalid!".to_string())
-                }
-            }
-        }
-        return self.save_as_file(dir_prefix, file_name)
+    // parse the response to a "!" or "$" request into attribute blocks
+    pub fn as_attributes(&self) -> Result<Vec<PlusAttributes>, GopherError> {
+        return PlusAttributes::parse_blocks(str::from_utf8(&self.raw)?)
     }
 
-    pub fn as_utf8_str(&self) -> Result<String, String> {
-        if let Ok(content) = str::from_utf8(&self.raw) {
-            return Ok(String::from(content))
-        } else {
-            return Err("Message does not support utf8 encoding or is corrupted!".to_string())
-        }
+    pub fn as_utf8_str(&self) -> Result<String, GopherError> {
+        return Ok(String::from(str::from_utf8(&self.raw)?))
     }
-}
\ No newline at end of file
+}
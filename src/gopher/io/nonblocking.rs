@@ -0,0 +1,433 @@
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::time::Duration;
+use path_clean::clean;
+use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout;
+use sha2::{Digest, Sha256};
+
+use super::{part_path, read_error, Request, ResponseBuilder};
+use crate::gopher::config::TlsMode;
+use crate::gopher::diff::hex;
+use crate::gopher::error::GopherError;
+use crate::gopher::plus::{PlusHeader, PlusLength};
+use crate::gopher::politeness::Permit;
+use crate::gopher::stream::{is_terminator, MenuReader};
+use crate::gopher::transport::{order_addresses, refuse_onion, Socks5Connector};
+use crate::gopher::types::{Item, Referer};
+
+// Async requests on tokio sockets and timers, the counterparts of the blocking ones in io.
+// Every read waits at most the response timeout, like the read timeout of a blocking stream.
+
+fn read_timeout() -> GopherError {
+    return read_error(io::Error::new(ErrorKind::TimedOut, "response timed out"))
+}
+
+// Body of a response read off an async connection, the gopher+ header
+// (if any) is already consumed and a "+size" body is cut off at its size
+pub struct AsyncResponse {
+    reader: BufReader<TcpStream>,
+    referer: Referer,
+    pub plus: Option<PlusHeader>,
+    // bytes left of a "+size" body
+    remaining: Option<u64>,
+    resp_timeout: Duration,
+    // counts the connection against the politeness of the request
+    permit: Permit,
+    // wait the bandwidth asks for before the next read
+    throttle: Duration,
+}
+
+impl AsyncResponse {
+    fn new(reader: BufReader<TcpStream>, referer: Referer, plus: Option<PlusHeader>, resp_timeout: Duration, permit: Permit) -> Self {
+        let remaining = plus.as_ref().and_then(|header| { header.size() });
+        return Self { reader, referer, plus, remaining, resp_timeout, permit, throttle: Duration::ZERO }
+    }
+
+    pub fn referer(&self) -> &Referer {
+        return &self.referer
+    }
+
+    // bytes of the body already received, waits for more when none are left, empty at the end of the body
+    async fn fill_buf(&mut self) -> Result<&[u8], GopherError> {
+        let remaining = self.remaining;
+        if remaining == Some(0) {
+            return Ok(&[])
+        }
+        if !self.throttle.is_zero() {
+            tokio::time::sleep(std::mem::take(&mut self.throttle)).await;
+        }
+
+        let buf = match timeout(self.resp_timeout, self.reader.fill_buf()).await {
+            Ok(Ok(buf)) => buf,
+            Ok(Err(error)) => return Err(read_error(error)),
+            Err(_) => return Err(read_timeout()),
+        };
+        return match remaining {
+            Some(remaining) if buf.is_empty() => Err(read_error(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("Response truncated: {} bytes missing", remaining)
+            ))),
+            Some(remaining) => Ok(&buf[..(remaining.min(buf.len() as u64)) as usize]),
+            None => Ok(buf),
+        }
+    }
+
+    fn consume(&mut self, size: usize) {
+        self.reader.consume(size);
+        self.throttle += self.permit.spend(size);
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= size as u64;
+        }
+    }
+
+    // read part of the body into buf, 0 at the end of the body
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, GopherError> {
+        let received = self.fill_buf().await?;
+        let size = received.len().min(buf.len());
+        buf[..size].copy_from_slice(&received[..size]);
+        self.consume(size);
+        return Ok(size)
+    }
+
+    // append one line including its line ending to line, 0 at the end of the body
+    pub async fn read_line(&mut self, line: &mut Vec<u8>) -> Result<usize, GopherError> {
+        let mut line_size = 0;
+        loop {
+            let received = self.fill_buf().await?;
+            if received.is_empty() {
+                return Ok(line_size)
+            }
+            let (size, complete) = match received.iter().position(|byte| { *byte == b'\n' }) {
+                Some(i) => (i + 1, true),
+                None => (received.len(), false),
+            };
+            line.extend_from_slice(&received[..size]);
+            self.consume(size);
+            line_size += size;
+            if complete {
+                return Ok(line_size)
+            }
+        }
+    }
+
+    // append the rest of the body to buf
+    pub async fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize, GopherError> {
+        let mut read_size = 0;
+        loop {
+            let received = self.fill_buf().await?;
+            if received.is_empty() {
+                return Ok(read_size)
+            }
+            let size = received.len();
+            buf.extend_from_slice(received);
+            self.consume(size);
+            read_size += size;
+        }
+    }
+
+    // parse the body as a menu, lines are read up to the "." line
+    pub async fn menu(mut self) -> Result<Vec<Item>, GopherError> {
+        let mut raw: Vec<u8> = Vec::new();
+        let mut line: Vec<u8> = Vec::new();
+        loop {
+            line.clear();
+            if self.read_line(&mut line).await? == 0 {
+                break;
+            }
+            raw.extend_from_slice(&line);
+            if is_terminator(&line) {
+                break;
+            }
+        }
+        self.shutdown().await;
+        return MenuReader::new(&raw[..], self.referer.clone()).collect()
+    }
+
+    // copy the body chunk by chunk into file, through hasher when given one. A "+-1" gopher+ body
+    // is copied up to its "." line, which is not saved
+    async fn copy_to_file(&mut self, file: &mut fs::File, full_path: &Path, mut hasher: Option<&mut Sha256>) -> Result<u64, GopherError> {
+        let dot_terminated = self.plus.as_ref().is_some_and(|header| { header.length == PlusLength::DotTerminated });
+        let mut file_size: u64 = 0;
+        let mut line_start = true;
+        loop {
+            if dot_terminated && line_start {
+                let head = self.read_head().await?;
+                if head.is_empty() || is_terminator(&head) || head == b".\r" {
+                    break;
+                }
+                write_chunk(file, &head, full_path, hasher.as_deref_mut()).await?;
+                file_size += head.len() as u64;
+                line_start = head.ends_with(b"\n");
+                continue;
+            }
+
+            let received = self.fill_buf().await?;
+            if received.is_empty() {
+                break;
+            }
+            let size = match dot_terminated {
+                true => received.iter().position(|byte| { *byte == b'\n' }).map_or(received.len(), |i| { i + 1 }),
+                false => received.len(),
+            };
+            line_start = received[size - 1] == b'\n';
+            write_chunk(file, &received[..size], full_path, hasher.as_deref_mut()).await?;
+            self.consume(size);
+            file_size += size as u64;
+        }
+        flush_file(file, full_path).await?;
+        return Ok(file_size)
+    }
+
+    // first bytes of a line, as many as it takes to tell a "." line apart
+    async fn read_head(&mut self) -> Result<Vec<u8>, GopherError> {
+        let mut head: Vec<u8> = Vec::new();
+        while head.len() < 3 && head.last() != Some(&b'\n') && head.first().is_none_or(|byte| { *byte == b'.' }) {
+            let Some(byte) = self.fill_buf().await?.first().copied() else { break };
+            head.push(byte);
+            self.consume(1);
+        }
+        return Ok(head)
+    }
+
+    // copy a text body line by line into file, without the "." terminator line and with ".." lines restored
+    async fn copy_txt_to_file(&mut self, file: &mut fs::File, full_path: &Path, mut hasher: Option<&mut Sha256>) -> Result<u64, GopherError> {
+        let mut file_size: u64 = 0;
+        let mut line: Vec<u8> = Vec::new();
+        loop {
+            line.clear();
+            if self.read_line(&mut line).await? == 0 || is_terminator(&line) {
+                break;
+            }
+            let content = if line.starts_with(b"..") { &line[1..] } else { &line[..] };
+            write_chunk(file, content, full_path, hasher.as_deref_mut()).await?;
+            file_size += content.len() as u64;
+        }
+        flush_file(file, full_path).await?;
+        return Ok(file_size)
+    }
+
+    pub async fn shutdown(&mut self) {
+        if self.reader.get_mut().shutdown().await.is_err() {
+            println!("==Stream shutdown failed!==");
+        }
+    }
+}
+
+impl Request {
+    // resolve the server, connect and write the request line without blocking the executor,
+    // once the politeness of the config lets the request start
+    async fn async_open_stream(&self) -> Result<(TcpStream, Permit), GopherError> {
+        let req_buff = self.request_line()?;
+        // an attempt that connects nowhere leaves no address behind
+        self.config.addresses.record(&self.referer(), None);
+        // going around TLS the server is set to would give the request away
+        if self.config.tls.mode_for(&self.domain, self.port) == TlsMode::On {
+            return Err(GopherError::Handshake {
+                domain: self.domain.clone(),
+                port: self.port,
+                source: io::Error::new(ErrorKind::Unsupported, "async requests connect without TLS"),
+            })
+        }
+        let proxy = self.config.proxy.proxy_for(&self.domain, self.port).cloned();
+        if proxy.is_none() {
+            refuse_onion(&self.domain, self.port)?;
+        }
+        let permit = self.config.politeness.async_acquire(&self.domain, self.port).await;
+        let mut stream = match &proxy {
+            Some(proxy) => {
+                let stream = self.async_connect(&proxy.domain, proxy.port).await?;
+                self.async_socks5(stream, Socks5Connector::from_proxy(proxy)).await?
+            }
+            None => self.async_connect(&self.domain, self.port).await?,
+        };
+
+        self.report_sent(&req_buff, stream.peer_addr().ok());
+        match timeout(self.config.resp_timeout, stream.write_all(req_buff.as_bytes())).await {
+            Ok(written) => written.map_err(|source| { GopherError::WriteFailure { source } })?,
+            Err(_) => return Err(GopherError::WriteFailure {
+                source: io::Error::new(ErrorKind::TimedOut, "request write timed out")
+            }),
+        }
+        return Ok((stream, permit))
+    }
+
+    // the SOCKS5 handshake of the blocking transport over the proxy connection, on a blocking thread of the runtime
+    async fn async_socks5(&self, stream: TcpStream, socks: Socks5Connector) -> Result<TcpStream, GopherError> {
+        let (domain, port, config) = (self.domain.clone(), self.port, self.config.clone());
+        let handshake_error = |source| { GopherError::Handshake { domain: self.domain.clone(), port: self.port, source } };
+        let std_stream = stream.into_std().and_then(|std_stream| { std_stream.set_nonblocking(false).map(|_| { std_stream }) })
+            .map_err(handshake_error)?;
+        let handshake = tokio::task::spawn_blocking(move || {
+            let mut std_stream = std_stream;
+            return socks.handshake_over(&mut std_stream, &domain, port, &config).map(|_| { std_stream })
+        });
+        let std_stream = handshake.await.map_err(|error| { handshake_error(io::Error::other(error)) })??;
+        return std_stream.set_read_timeout(None)
+            .and_then(|_| { std_stream.set_nonblocking(true) })
+            .and_then(|_| { TcpStream::from_std(std_stream) })
+            .map_err(handshake_error)
+    }
+
+    // one address of (domain, port) after the other, the error of the first one is kept
+    async fn async_connect(&self, domain: &str, port: u16) -> Result<TcpStream, GopherError> {
+        let addrs = match lookup_host((domain, port)).await {
+            Ok(addrs) => order_addresses(addrs.collect(), self.config.ip),
+            Err(error) => return Err(GopherError::Dns {
+                domain: String::from(domain),
+                port,
+                source: Some(error),
+            }),
+        };
+        if addrs.is_empty() {
+            return Err(GopherError::Dns { domain: String::from(domain), port, source: None })
+        }
+
+        let mut first_error: Option<io::Error> = None;
+        let mut connected: Option<TcpStream> = None;
+        for sock_addr in addrs {
+            let error = match timeout(self.config.conn_timeout, TcpStream::connect(sock_addr)).await {
+                Ok(Ok(stream)) => { connected = Some(stream); break; }
+                Ok(Err(error)) => error,
+                Err(_) => io::Error::new(ErrorKind::TimedOut, "connection timed out"),
+            };
+            first_error.get_or_insert(error);
+        }
+        return connected.ok_or_else(|| {
+            GopherError::from_connect(domain, port, first_error.unwrap_or(io::Error::from(ErrorKind::TimedOut)))
+        })
+    }
+
+    // send request to server and return the response body as a stream,
+    // a gopher+ header is read and checked before the body is handed out
+    pub async fn async_open(&self) -> Result<AsyncResponse, GopherError> {
+        let (stream, permit) = self.async_open_stream().await?;
+        let mut resp = AsyncResponse::new(BufReader::new(stream), self.referer(), None, self.config.resp_timeout, permit);
+
+        if self.plus.is_some() {
+            let mut header_line: Vec<u8> = Vec::new();
+            if resp.read_line(&mut header_line).await? == 0 {
+                return Err(GopherError::parse("Response ended before the gopher+ header!"))
+            }
+            let header = PlusHeader::parse(&String::from_utf8_lossy(&header_line))?;
+            if !header.success {
+                let mut message: Vec<u8> = Vec::new();
+                resp.read_to_end(&mut message).await.unwrap_or_default();
+                return Err(self.plus_error(&message))
+            }
+            resp.remaining = header.size();
+            resp.plus = Some(header);
+        }
+
+        return Ok(resp)
+    }
+
+    // send request to server and parse the menu items
+    pub async fn async_menu(&self) -> Result<Vec<Item>, GopherError> {
+        return self.async_open().await?.menu().await
+    }
+
+    // send request to server and collect response
+    pub async fn async_send(&self) -> Result<ResponseBuilder, GopherError> {
+        let mut resp = self.async_open().await?;
+        let mut resp_buff: Vec<u8> = Vec::new();
+        resp.read_to_end(&mut resp_buff).await?;
+        resp.shutdown().await;
+
+        return Ok(ResponseBuilder {
+            raw: resp_buff,
+            referer: self.referer(),
+            verbose: self.config.verbose,
+            plus: resp.plus,
+        })
+    }
+
+    pub async fn async_download(&self, dest_prefix: &str) -> Result<u64, GopherError> {
+        let (dir_prefix, file_name) = self.local_target(dest_prefix)?;
+        return self.async_download_as(&dir_prefix, &file_name).await
+    }
+
+    pub async fn async_download_as(&self, dir_prefix: &str, file_name: &str) -> Result<u64, GopherError> {
+        let full_path = clean(format!("{}/{}", dir_prefix, file_name));
+        let mut file = create_file(dir_prefix, &full_path).await?;
+
+        let mut resp = self.async_open().await?;
+        self.report_plus(resp.plus.as_ref());
+        let file_size = resp.copy_to_file(&mut file, &full_path, None).await?;
+        resp.shutdown().await;
+
+        if self.config.verbose {
+            println!("\n==File downloaded==\nFolder:{}\nName:{}\n", &dir_prefix, &file_name);
+        }
+
+        return Ok(file_size)
+    }
+
+    // download a text file, the "." terminator line is not saved
+    pub async fn async_download_txt(&self, dest_prefix: &str) -> Result<u64, GopherError> {
+        let (dir_prefix, file_name) = self.local_target(dest_prefix)?;
+        return self.async_download_txt_as(&dir_prefix, &file_name).await
+    }
+
+    // download a text file, the "." terminator line is not saved
+    pub async fn async_download_txt_as(&self, dir_prefix: &str, file_name: &str) -> Result<u64, GopherError> {
+        let full_path = clean(format!("{}/{}", dir_prefix, file_name));
+        let mut file = create_file(dir_prefix, &full_path).await?;
+
+        let mut resp = self.async_open().await?;
+        self.report_plus(resp.plus.as_ref());
+        let file_size = resp.copy_txt_to_file(&mut file, &full_path, None).await?;
+        resp.shutdown().await;
+
+        if self.config.verbose {
+            println!("\n==File saved==\nFolder:{}\nName:{}\n", dir_prefix, file_name);
+        }
+
+        return Ok(file_size)
+    }
+    // download_changed on async sockets
+    pub async fn async_download_changed(&self, dest_prefix: &str, hash: &str, text: bool) -> Result<(u64, bool), GopherError> {
+        let (dir_prefix, file_name) = self.local_target(dest_prefix)?;
+        let mut resp = self.async_open().await?;
+        self.report_plus(resp.plus.as_ref());
+
+        let part_path = part_path(&dir_prefix, &file_name);
+        let mut file = create_file(&dir_prefix, &part_path).await?;
+        let mut hasher = Sha256::new();
+        let copied = match text {
+            true => resp.copy_txt_to_file(&mut file, &part_path, Some(&mut hasher)).await,
+            false => resp.copy_to_file(&mut file, &part_path, Some(&mut hasher)).await,
+        };
+        resp.shutdown().await;
+        drop(file);
+        let copied = copied.map(|file_size| { (file_size, hex(&hasher.finalize())) });
+        return self.replace_changed(copied, &part_path, &dir_prefix, &file_name, hash)
+    }
+}
+
+// create dir_prefix and all parent folders before creating the file at full_path
+async fn create_file(dir_prefix: &str, full_path: &Path) -> Result<fs::File, GopherError> {
+    let invalid_path = |source| {
+        GopherError::InvalidPath { path: full_path.to_string_lossy().to_string(), source: Some(source) }
+    };
+    fs::create_dir_all(dir_prefix).await.map_err(invalid_path)?;
+    return fs::File::create(full_path).await.map_err(invalid_path)
+}
+
+async fn write_chunk(file: &mut fs::File, chunk: &[u8], full_path: &Path, hasher: Option<&mut Sha256>) -> Result<(), GopherError> {
+    if let Some(hasher) = hasher {
+        hasher.update(chunk);
+    }
+    return file.write_all(chunk).await.map_err(|source| {
+        GopherError::LocalIo { path: full_path.to_string_lossy().to_string(), source }
+    })
+}
+
+// tokio files finish writes in the background, flush before reporting the size
+async fn flush_file(file: &mut fs::File, full_path: &Path) -> Result<(), GopherError> {
+    return file.flush().await.map_err(|source| {
+        GopherError::LocalIo { path: full_path.to_string_lossy().to_string(), source }
+    })
+}
@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::Path;
+use path_clean::clean;
+
+use super::error::GopherError;
+use super::io::{local_name, local_path};
+use super::types::{Item, ItemClass, Referer};
+
+// Mirrors
+// ======
+// Browsable offline copy of a server, written next to the files download_all_to saves. The menu at selector S goes to
+//     <root>/S/gophermap   the menu lines, items of the mirrored server point at their local path with the host
+//                          and port left out, for a local gopher server to fill in its own
+//     <root>/S/index.html  the same menu as a static page linking to the mirrored menus and downloaded files
+// The results of searching S for Q go to <root>/S/Q/. Menus of other servers are not mirrored and their items keep
+// pointing at the servers they are on. Downloads and mirrored menus are placed by local_path, which keeps a selector
+// from ever landing on a gophermap or index.html. "URL:" links are only made live for web and gopher schemes.
+
+pub struct Mirror<'a> {
+    root: &'a str,
+    domain: &'a str,
+    port: u16,
+    // queries the scan sends to search items, their results are mirrored
+    search_queries: &'a [String],
+}
+
+// folder a menu is mirrored to, relative to the mirror root
+pub fn menu_dir(referer: &Referer) -> String {
+    let dir = local_path(&referer.path);
+    return match &referer.query {
+        Some(query) => join(&dir, &query_dir(query)),
+        None => dir,
+    }
+}
+
+// a search query as a single folder name
+fn query_dir(query: &str) -> String {
+    let name = query.replace(['/', '\\'], "_");
+    return if name.is_empty() || name == "." || name == ".." { String::from("_") } else { local_name(&name) }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    return if dir.is_empty() { String::from(name) } else { format!("{}/{}", dir, name) }
+}
+
+// link from a page in from_dir to target, both relative to the mirror root
+fn relative_href(from_dir: &str, target: &str) -> String {
+    let from = from_dir.split('/').filter(|part| { !part.is_empty() }).collect::<Vec<&str>>();
+    let to = target.split('/').filter(|part| { !part.is_empty() }).collect::<Vec<&str>>();
+    let common = from.iter().zip(to.iter()).take_while(|(a, b)| { a == b }).count();
+
+    let mut parts = vec![".."; from.len() - common];
+    parts.extend_from_slice(&to[common..]);
+    return parts.iter().map(|part| { href_encode(part) }).collect::<Vec<String>>().join("/")
+}
+
+// percent-encode everything but unreserved chars, so any file name survives as a link
+fn href_encode(part: &str) -> String {
+    let mut encoded = String::new();
+    for byte in part.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    return encoded
+}
+
+// target of a "URL:" item when it is safe to link, scripts and other schemes are shown but not linked
+fn web_link(link: &str) -> Option<&str> {
+    let (scheme, _) = link.split_once(':')?;
+    return match scheme.to_lowercase().as_str() {
+        "http" | "https" | "gopher" | "gophers" => Some(link),
+        _ => None,
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    return text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl<'a> Mirror<'a> {
+    pub fn new(root: &'a str, domain: &'a str, port: u16, search_queries: &'a [String]) -> Self {
+        return Self { root, domain, port, search_queries }
+    }
+
+    fn is_local(&self, domain: &str, port: u16) -> bool {
+        return domain == self.domain && port == self.port
+    }
+
+    // where a local item is mirrored, relative to the mirror root, None for items
+    // that stay on the live server (searches, sessions, links and other servers' items)
+    fn local_target(&self, item: &Item) -> Option<String> {
+        let Item::DATA { location, domain, port, .. } = item else {
+            return None
+        };
+        if !self.is_local(domain, *port) {
+            return None
+        }
+        return match item.class() {
+            Some(ItemClass::Menu | ItemClass::Text | ItemClass::Binary) => Some(local_path(location)),
+            _ => None,
+        }
+    }
+
+    // folders the results of a local search item are mirrored to, with the query they answer
+    fn search_results(&self, item: &Item) -> Vec<(String, String)> {
+        return match item {
+            Item::DATA { location, domain, port, .. }
+                if item.class() == Some(ItemClass::Search) && self.is_local(domain, *port)
+                => self.search_queries.iter()
+                    .map(|query| { (query.clone(), join(&local_path(location), &query_dir(query))) })
+                    .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // write the menu scanned at referer, menus of other servers are skipped
+    pub fn write_menu(&self, referer: &Referer, items: &[Item]) -> Result<(), GopherError> {
+        if !self.is_local(&referer.domain, referer.port) {
+            return Ok(())
+        }
+        let dir = menu_dir(referer);
+        let full_dir = clean(format!("{}/{}", self.root, dir));
+        fs::create_dir_all(&full_dir).map_err(|source| {
+            GopherError::InvalidPath { path: full_dir.to_string_lossy().to_string(), source: Some(source) }
+        })?;
+        write_file(&full_dir.join("gophermap"), &self.gophermap(items))?;
+        return write_file(&full_dir.join("index.html"), &self.html(referer, &dir, items))
+    }
+
+    fn gophermap(&self, items: &[Item]) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for item in items {
+            match (item, self.local_target(item)) {
+                (Item::DATA { kind, caption, .. }, Some(target)) => {
+                    lines.push(format!("{}{}\t/{}", kind.tag(), caption, target));
+                }
+                (Item::DATA { caption, .. }, None) => {
+                    lines.push(item.menu_line());
+                    for (query, results) in self.search_results(item) {
+                        lines.push(format!("1{} ({})\t/{}", caption, query, results));
+                    }
+                }
+                _ => lines.push(item.menu_line()),
+            }
+        }
+        lines.push(String::from("."));
+        return lines.iter().map(|line| { format!("{}\r\n", line) }).collect::<String>()
+    }
+
+    fn html(&self, referer: &Referer, dir: &str, items: &[Item]) -> String {
+        let title = html_escape(&referer.to_string());
+        let mut lines: Vec<String> = Vec::new();
+        for item in items {
+            match item {
+                Item::INFO { message, .. } => lines.push(html_escape(message)),
+                Item::UNKNOWN { unparsed, .. } => lines.push(html_escape(unparsed)),
+                Item::DATA { caption, location, .. } => {
+                    let class = item.class().map(|class| { format!("{:?}", class).to_lowercase() }).unwrap_or_default();
+                    let href = match (self.local_target(item), location.strip_prefix("URL:")) {
+                        (Some(target), _) if item.is_menu() => Some(relative_href(dir, &join(&target, "index.html"))),
+                        (Some(target), _) => Some(relative_href(dir, &target)),
+                        (None, Some(link)) => web_link(link).map(String::from),
+                        (None, None) => item.url().map(|url| { url.to_string() }),
+                    };
+                    match (href, location.strip_prefix("URL:")) {
+                        (Some(href), _) => lines.push(
+                            format!("[{}] <a href=\"{}\">{}</a>", class, html_escape(&href), html_escape(caption))
+                        ),
+                        (None, link) => lines.push(
+                            format!("[{}] {} {}", class, html_escape(caption), html_escape(link.unwrap_or_default()))
+                        ),
+                    }
+                    for (query, results) in self.search_results(item) {
+                        lines.push(format!("[results] <a href=\"{}\">{} ({})</a>",
+                            html_escape(&relative_href(dir, &join(&results, "index.html"))), html_escape(caption), html_escape(&query)
+                        ));
+                    }
+                }
+            }
+        }
+        return format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n<pre>\n{}\n</pre>\n</body>\n</html>\n",
+            title, title, lines.join("\n")
+        )
+    }
+}
+
+fn write_file(path: &Path, content: &str) -> Result<(), GopherError> {
+    return fs::write(path, content).map_err(|source| {
+        GopherError::LocalIo { path: path.to_string_lossy().to_string(), source }
+    })
+}
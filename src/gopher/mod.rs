@@ -1,38 +1,133 @@
-use std::fs::{OpenOptions, self};
-use std::{str, usize};
-use chrono::Utc;
-use std::sync::Mutex;
+use std::fs;
+use std::str;
+use chrono::{DateTime, Utc};
 use path_clean::clean;
-use std::path::PathBuf;
-use std::time::Duration;
-use regex::{Regex, RegexBuilder};
-use std::io::{Write, Bytes, Read, ErrorKind};
-use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::io::Read;
+use std::collections::{HashMap, HashSet};
 
 pub mod types;
 pub mod io;
-
-use io::{Request, ResponseBuilder};
-use types::{Item, Referer};
+pub mod error;
+pub mod config;
+pub mod url;
+pub mod plus;
+pub mod stream;
+pub mod crawl;
+pub mod checkpoint;
+pub mod diff;
+pub mod export;
+pub mod mirror;
+pub mod server;
+pub mod transport;
+pub mod retry;
+pub mod politeness;
+pub mod robots;
+pub mod caps;
+
+use io::{local_path, Request};
+use error::GopherError;
+use config::{ClientConfig, TlsMode};
+use url::GopherUrl;
+use crawl::{item_request, CrawledMenu, Crawler, Frontier};
+use checkpoint::Checkpoint;
+use diff::{file_hash, menu_hash, CrawlDiff, FileRecord};
+use export::ExportFormat;
+use mirror::Mirror;
+use plus::{PlusAttributes, PlusRequest};
+use retry::{Failure, FailureStage};
+use robots::{Robots, ServerRules};
+use caps::Caps;
+use types::{Item, ItemClass, Referer};
 
 use crate::add_log;
-use crate::cli::CLI_ARGS;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Client {
     pub port: u16,
+    pub config: ClientConfig,
     pub domain: String,
+    // selector of the menu the scan starts from
+    pub root: String,
     pub items: Vec<Item>,
     pub referers: Vec<Referer>,
+    // requests that failed during scan or download once the retry policy gave up on them
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub failures: Vec<Failure>,
+    // robots.txt and caps.txt of every server reached, by (domain, port), see gopher::robots
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub server_rules: HashMap<(String, u16), ServerRules>,
+    // menus and files left alone because the robots.txt of their server disallows them
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub disallowed: Vec<Referer>,
+    // crawl restored from a checkpoint, picked up by the next scan instead of starting from root
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub pending_scan: Option<Frontier>,
+    // digest of every menu scanned, by URL
+    pub menu_hashes: HashMap<String, String>,
+    // size and digest of every item downloaded, by URL, items listed here are not downloaded again
+    pub downloads: HashMap<String, FileRecord>,
+    // an earlier run to report changes against, files it downloaded that have not changed are not downloaded again
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub snapshot: Option<Checkpoint>,
 }
 
+// downloads between two checkpoints
+const CHECKPOINT_DOWNLOADS: usize = 16;
+
 impl Client {
     pub fn new(domain: &str) -> Self {
         return Self {
             port: 70,
+            config: ClientConfig::default(),
             domain: String::from(domain),
+            root: "/".to_string(),
             items: Vec::new(),
             referers: Vec::new(),
+            failures: Vec::new(),
+            server_rules: HashMap::new(),
+            disallowed: Vec::new(),
+            pending_scan: None,
+            menu_hashes: HashMap::new(),
+            downloads: HashMap::new(),
+            snapshot: None,
+        }
+    }
+
+    // pick up a run where its last checkpoint left it
+    pub fn resume(checkpoint_path: &str) -> Result<Self, GopherError> {
+        return Ok(Self::from_checkpoint(Checkpoint::load(checkpoint_path)?))
+    }
+
+    pub fn from_checkpoint(checkpoint: Checkpoint) -> Self {
+        let mut client = Self::from_url(&checkpoint.root);
+        client.items = checkpoint.items;
+        client.referers = checkpoint.referers;
+        client.menu_hashes = checkpoint.menu_hashes;
+        client.downloads = checkpoint.downloads;
+        client.pending_scan = Some(checkpoint.frontier);
+        return client
+    }
+
+    // start the scan from the menu the url points at, the server of a gophers:// url is connected to over TLS
+    pub fn from_url(url: &GopherUrl) -> Self {
+        let mut client = Self::new(&url.domain);
+        client.update_port(url.port).update_root(&url.selector);
+        if url.tls {
+            client.config.request.tls.update_host(&format!("{}:{}", url.host(), url.port), TlsMode::On);
         }
+        return client
+    }
+
+    pub fn url(&self) -> GopherUrl {
+        let mut url = GopherUrl::new(&self.domain, self.port);
+        url.update_selector(&self.root)
+            .update_tls(self.config.request.tls.mode_for(&self.domain, self.port) == TlsMode::On);
+        return url
+    }
+
+    pub fn update_root(&mut self, root: &str) -> &mut Self {
+        self.root = if root.is_empty() { "/".to_string() } else { String::from(root) };
+        return self
     }
 
     pub fn update_port(&mut self, port: u16) -> &mut Self {
@@ -40,9 +135,65 @@ impl Client {
         return self
     }
 
+    pub fn update_config(&mut self, config: ClientConfig) -> &mut Self {
+        self.config = config;
+        return self
+    }
+
+    // capabilities the scanned server lists in its caps.txt, None until a scan found one
+    pub fn caps(&self) -> Option<&Caps> {
+        return self.server_rules.get(&(self.domain.clone(), self.port)).and_then(|rules| { rules.caps.as_ref() })
+    }
+
+    // rules of the robots.txt of the scanned server that apply to this client, None until a scan found one
+    pub fn robots(&self) -> Option<&Robots> {
+        return self.server_rules.get(&(self.domain.clone(), self.port)).and_then(|rules| { rules.robots.as_ref() })
+    }
+
+    pub fn update_snapshot(&mut self, snapshot: Option<Checkpoint>) -> &mut Self {
+        self.snapshot = snapshot;
+        return self
+    }
+
+    // compare the next run against the checkpoint a previous run left at snapshot_path
+    pub fn load_snapshot(&mut self, snapshot_path: &str) -> Result<&mut Self, GopherError> {
+        return Ok(self.update_snapshot(Some(Checkpoint::load(snapshot_path)?)))
+    }
+
+    // write every item found, with the menu it is listed on, as JSON Lines or CSV, or the menus as a DOT graph
+    pub fn export<W: std::io::Write>(&self, format: ExportFormat, writer: &mut W) -> std::io::Result<()> {
+        return export::write(writer, self, format)
+    }
+
+    // what changed since the snapshot, None without a snapshot
+    pub fn diff(&self) -> Option<CrawlDiff> {
+        return self.snapshot.as_ref().map(|snapshot| { CrawlDiff::between(snapshot, self) })
+    }
+
+    // a request to this client's server carrying the client's request settings
+    fn request(&self, path: &str) -> Request {
+        let mut req = Request::new(&self.domain, self.port);
+        req.update_config(&self.config.request).update_path(path);
+        return req
+    }
+
+    fn request_item(&self, item: &Item) -> Result<Request, GopherError> {
+        return item_request(&self.config, item)
+    }
+
+    // write a checkpoint to config.checkpoint_path, frontier is None once the scan is complete
+    fn save_checkpoint(&self, frontier: Option<&Frontier>) {
+        if let Some(path) = &self.config.checkpoint_path {
+            if let Err(error) = checkpoint::save(path, self, frontier) {
+                println!("==Checkpoint write failed==\n{}\n", error);
+                add_log!("===Checkpoint write failed===\n{}\n", error);
+            }
+        }
+    }
+
     // send an initial request to ping the server
-    pub fn ping(&mut self) -> Result<&mut Self, String> {
-        match Request::new(&self.domain, self.port).update_path("/").send() {
+    pub fn ping(&mut self) -> Result<&mut Self, GopherError> {
+        match self.request("/").send() {
             Ok(_) => { return Ok(self) }
             Err(e) => { return Err(e) }
         }
@@ -50,26 +201,47 @@ impl Client {
 
     // scan directory and all sub-directories for items
     pub fn scan_all(&mut self) -> &mut Self {
+        let time_start = self.start_scan();
+        let root = self.root.clone();
+        self.scan_re(&root, !self.config.trace_external);
+        self.finish_scan(time_start);
+        return self
+    }
+
+    // scan_all on a tokio runtime, the executor is never blocked while waiting on a server
+    #[cfg(feature = "async")]
+    pub async fn async_scan_directories(&mut self) -> &mut Self {
+        let time_start = self.start_scan();
+        let root = self.root.clone();
+        self.async_scan_re(&root, !self.config.trace_external).await;
+        self.finish_scan(time_start);
+        return self
+    }
+
+    fn start_scan(&self) -> DateTime<Utc> {
         let time_start = Utc::now();
-        println!("===Scanning server directories===\nServer: {}:{}\nTime: {}\n",
-            self.domain, 
-            self.port, 
-            time_start.format("%Y-%m-%d %H:%M:%S %Z").to_string()
+        let proxy = self.config.request.proxy.proxy_for(&self.domain, self.port)
+            .map(|proxy| { format!("Proxy: {}\n", proxy) })
+            .unwrap_or_default();
+        println!("===Scanning server directories===\nServer: {}\n{}Time: {}\n",
+            self.url(), 
+            proxy,
+            time_start.format("%Y-%m-%d %H:%M:%S %Z")
         );
-        self.scan_re("/", !CLI_ARGS.trace_external);
+        return time_start
+    }
+
+    // report the end of a scan and log the items found
+    fn finish_scan(&self, time_start: DateTime<Utc>) {
         let time_end = Utc::now();
-        println!("===Server directories scan complete===\nServer: {}:{}\nTime: {}\nTotal runtime: {} ms\n\nUpdating logs...\n",
-            self.domain, 
-            self.port, 
-            time_end.format("%Y-%m-%d %H:%M:%S %Z").to_string(),
+        println!("===Server directories scan complete===\nServer: {}\nTime: {}\nTotal runtime: {} ms\n\nUpdating logs...\n",
+            self.url(), 
+            time_end.format("%Y-%m-%d %H:%M:%S %Z"),
             time_end.signed_duration_since(time_start).num_milliseconds()
         );
 
         let dir_items = self.items.iter()
-            .filter(|item| {
-                return if let Item::DATA { tag: '1' | '7', size, caption, referer, location, domain, port } = item 
-                { true } else { false }
-            }).cloned()
+            .filter(|item| { item.is_menu() }).cloned()
             .collect::<Vec<Item>>();
 
         add_log!("===All directory items===\nTotal: {}\n\n{}", 
@@ -77,11 +249,15 @@ impl Client {
             dir_items.iter().map(|item| {item.to_string()}).collect::<Vec<String>>().join("\n")
         );
 
+        add_log!("===Menus scanned===\nTotal: {}\n\n{}", 
+            self.referers.len(),
+            self.referers.iter()
+                .map(|referer| { format!("Target: {}\n{}", referer, self.address_entry(referer)) })
+                .collect::<Vec<String>>().join("\n")
+        );
+
         let err_items = self.items.iter()
-            .filter(|item| {
-                return if let Item::DATA { tag: '3', size, caption, referer, location, domain, port } = item 
-                { true } else { false }
-            }).cloned()
+            .filter(|item| { item.class() == Some(ItemClass::Error) }).cloned()
             .collect::<Vec<Item>>();
 
         add_log!("===All error items===\nTotal: {}\n\n{}", 
@@ -91,7 +267,7 @@ impl Client {
 
         let ext_items = self.items.iter()
             .filter(|item| {
-                return if let Item::DATA { tag, size, caption, referer, location, domain: dom, port: p } = item 
+                return if let Item::DATA { domain: dom, port: p, .. } = item 
                     { (dom != &self.domain) || (p != &self.port) } 
                     else 
                     { false }
@@ -103,54 +279,173 @@ impl Client {
             ext_items.iter().map(|item| {item.to_string()}).collect::<Vec<String>>().join("\n")
         );
 
+        add_log!("===Server rules===\nTotal: {}\n\n{}", 
+            self.server_rules.len(),
+            self.server_rules.iter()
+                .map(|((domain, port), rules)| { 
+                    return format!("Server: {}:{}\nrobots.txt:\n{}caps.txt:\n{}",
+                        GopherUrl::new(domain, *port).host(),
+                        port,
+                        rules.robots.as_ref().map_or(String::from("None\n"), |robots| { robots.to_string() }),
+                        rules.caps.as_ref().map_or(String::from("None\n"), |caps| { caps.to_string() })
+                    )
+                })
+                .collect::<Vec<String>>().join("\n")
+        );
+
+        add_log!("===Disallowed by robots.txt===\nTotal: {}\n\n{}", 
+            self.disallowed.len(),
+            self.disallowed.iter().map(|referer| { referer.to_string() }).collect::<Vec<String>>().join("\n")
+        );
+
+        add_log!("===Failed requests===\nTotal: {}\n\n{}", 
+            self.failures.len(),
+            self.failures.iter()
+                .map(|failure| { failure.to_string() })
+                .collect::<Vec<String>>().join("\n")
+        );
+
+        if let Some(diff) = self.diff() {
+            add_log!("===Changes since snapshot===\n{}", diff);
+        }
+
         println!("Logs update complete.\n");
-        return self
     }
 
+    // scan the menu at loc_re and every menu reachable from it, breadth first. Menus are fetched by a pool
+    // of config.workers threads, one level at a time, and added in menu order so the results do not depend on scheduling.
+    // A checkpoint is written after every level. A crawl restored from a checkpoint is carried on instead.
+    // Menus the robots.txt of their server disallows are left out, see gopher::robots
     pub fn scan_re(&mut self, loc_re: &str, internal_only: bool) {
-        if let Ok(resp) = Request::new(&self.domain, self.port).update_path(loc_re).send() {
-            self.referers.push(resp.referer.clone());
-
-            if let Ok(items) = resp.as_items() {
-                let items_filtered = items.iter()
-                    .filter(|item| { return !self.items.contains(item) }).cloned()
-                    .collect::<Vec<Item>>();
-                // println!("==Items filtered==\n{:?}\n", &items_filtered);
-                self.items.extend_from_slice(&items_filtered);
-
-                for item in &items_filtered {
-                    // Directory item at tag = '1' | '7'
-                    if let Item::DATA { tag: '1' | '7', size: _, caption: _, referer: _, location: l, port: p, domain: dom } = item {
-                        if internal_only {
-                            // only scan ahead if reference is internal
-                            if (&self.domain == dom) && (&self.port == p) {
-                                self.scan_re(l, internal_only);
-                            }
-                        } else {
-                            self.scan_re(l, internal_only);
+        let mut frontier = self.pending_scan.take().unwrap_or_else(|| { Frontier::new(self.request(loc_re)) });
+        let mut crawler = Crawler::new(&self.config, internal_only.then_some((self.domain.as_str(), self.port)));
+        let mut found: HashSet<Item> = self.items.iter().cloned().collect();
+
+        while crawler.step(&mut frontier) {
+            self.add_menus(&mut frontier, &mut found);
+            self.save_checkpoint(Some(&frontier));
+        }
+        self.server_rules.extend(crawler.rules().clone());
+        self.finish_crawl(&mut frontier);
+    }
+
+    // scan_re with the menus fetched by tokio tasks
+    #[cfg(feature = "async")]
+    pub async fn async_scan_re(&mut self, loc_re: &str, internal_only: bool) {
+        let mut frontier = self.pending_scan.take().unwrap_or_else(|| { Frontier::new(self.request(loc_re)) });
+        let mut crawler = Crawler::new(&self.config, internal_only.then_some((self.domain.as_str(), self.port)));
+        let mut found: HashSet<Item> = self.items.iter().cloned().collect();
+
+        while crawler.async_step(&mut frontier).await {
+            self.add_menus(&mut frontier, &mut found);
+            self.save_checkpoint(Some(&frontier));
+        }
+        self.server_rules.extend(crawler.rules().clone());
+        self.finish_crawl(&mut frontier);
+    }
+
+    // add the items of the menus fetched by the last step, items in found are skipped
+    fn add_menus(&mut self, frontier: &mut Frontier, found: &mut HashSet<Item>) {
+        self.disallowed.append(&mut frontier.disallowed);
+        for CrawledMenu { request, depth, fetch } in frontier.menus.drain(..) {
+            let mut referer = request.referer();
+            referer.update_depth(Some(depth));
+            if fetch.answered {
+                self.referers.push(referer.clone());
+            }
+
+            match fetch.items {
+                Ok(items) => {
+                    self.menu_hashes.insert(referer.to_string(), menu_hash(&items));
+                    self.mirror_menu(&referer, &items);
+                    for item in items {
+                        if found.insert(item.clone()) {
+                            self.items.push(item);
                         }
                     }
+                    if let Some(attributes) = fetch.attributes {
+                        self.scan_attributes(&request, attributes);
+                    }
                 }
+                Err(GopherError::ServerError { item }) => {
+                    // keep the error item so it shows up among the logged error items
+                    if found.insert(*item.clone()) {
+                        self.items.push(*item.clone());
+                    }
+                    self.push_failure(referer, FailureStage::Menu, fetch.attempts, GopherError::ServerError { item });
+                }
+                Err(error) => {
+                    self.push_failure(referer, FailureStage::Menu, fetch.attempts, error);
+                }
+            }
+        }
+    }
+
+    // write a scanned menu to the mirror under the download root when config.mirror is set
+    fn mirror_menu(&self, referer: &Referer, items: &[Item]) {
+        if let (true, Some(root)) = (self.config.mirror, &self.config.download_root) {
+            let mirror = Mirror::new(root, &self.domain, self.port, &self.config.search_queries);
+            if let Err(error) = mirror.write_menu(referer, items) {
+                println!("==Mirror write failed==\n{}\n", error);
+                add_log!("===Mirror write failed===\n{}\n", error);
             }
         }
     }
 
-    // Future expansion
-    pub async fn async_scan_directories(&mut self) -> Result<&mut Self, String> {
-        todo!()
+    fn finish_crawl(&mut self, frontier: &mut Frontier) {
+        // the last level may have been disallowed whole, leaving no menus to add
+        self.disallowed.append(&mut frontier.disallowed);
+        if let Some(limit) = frontier.limit_reached {
+            println!("==Scan cut short: {} reached==\n", limit);
+            add_log!("===Scan cut short===\nLimit reached: {}\nMenus scanned: {}\n", limit, self.referers.len());
+        }
+        self.save_checkpoint(None);
+    }
+
+    // attach the attribute blocks fetched for the menu behind req to its gopher+ items
+    fn scan_attributes(&mut self, req: &Request, attributes: Result<Vec<PlusAttributes>, GopherError>) {
+        match attributes {
+            Ok(all_attributes) => {
+                let referer = req.referer();
+                for attributes in all_attributes {
+                    let info_item = attributes.info.as_ref()
+                        .and_then(|info| { Item::from_str(&referer, info).ok() });
+                    if let Some(item) = info_item.and_then(|info_item| { 
+                        self.items.iter_mut().find(|item| { **item == info_item }) 
+                    }) {
+                        item.update_attributes(attributes);
+                    }
+                }
+            }
+            Err(error) => { self.push_failure(req.referer(), FailureStage::Attributes, 1, error); }
+        }
+    }
+
+    // fetch the gopher+ attributes of a single item
+    pub fn attributes(&self, item: &Item) -> Result<PlusAttributes, GopherError> {
+        let mut req = self.request_item(item)?;
+        return PlusAttributes::parse(&req.with_plus(PlusRequest::Attributes).send()?.as_utf8_str()?)
+    }
+
+    // send terms to a search item (type 7) and return the result menu
+    pub fn search(&self, item: &Item, terms: &str) -> Result<Vec<Item>, GopherError> {
+        if item.class() != Some(ItemClass::Search) {
+            return Err(GopherError::InvalidItem { item: Box::new(item.clone()) })
+        }
+        return self.request_item(item)?.with_query(terms).send()?.as_items()
     }
 
     // return a combined info text by combining text contents from all info items at a directory level specified by referer
     pub fn get_info_at(&self, referer: Referer) -> String {
         let items_found = self.items.iter()
             .filter(|item| {
-                return if let Item::INFO { tag, port, from, domain, message } = item 
+                return if let Item::INFO { from, .. } = item 
                     { from == &referer } 
                     else 
                     { false }
             }).cloned()
             .map(|item| { 
-                return if let Item::INFO { tag, port, from, domain, message } = item 
+                return if let Item::INFO { message, .. } = item 
                     { message.clone() } 
                     else 
                     { String::new() }
@@ -159,140 +454,386 @@ impl Client {
         return items_found.join("\n")
     }
 
+    // download all items (updated by scan_all) to the download root of the client's config
+    pub fn download_all(&mut self) -> Result<&mut Self, GopherError> {
+        if let Some(path_prefix) = self.config.download_root.clone() {
+            return self.download_all_to(&path_prefix)
+        } else {
+            return Err(GopherError::InvalidPath { path: String::new(), source: None })
+        }
+    }
+
     // download all items (updated by scan_all) to folder specified by path_prefix
-    pub fn download_all_to(&mut self, path_prefix: &str) -> Result<&mut Self, String> {
-        let time_start = Utc::now();
-        println!("===Starting file downloads===\nTime: {}\n",
-            time_start.format("%Y-%m-%d %H:%M:%S %Z").to_string()
-        );
-        
-        let mut text_items = self.items.iter()
-            .filter(|item| {
-                return if let Item::DATA { tag: '0', size, caption, referer, location, domain, port } = item 
-                { true } else { false }
-            }).cloned()
-            .collect::<Vec<Item>>();
+    // a download is retried as config.retry allows, an item that still fails is recorded in self.failures
+    // and does not stop the remaining downloads. Items the robots.txt of their server disallows are skipped
+    pub fn download_all_to(&mut self, path_prefix: &str) -> Result<&mut Self, GopherError> {
+        let mut run = self.start_downloads(path_prefix)?;
 
-        let mut non_text_items = self.items.iter()
-        .filter(|item| {
-            return if let Item::DATA { tag, size, caption, referer, location, domain, port } = item 
-                { (tag != &'0') && (tag != &'1') && (tag != &'7') } 
-                else 
-                { false }
-        }).cloned()
-        .collect::<Vec<Item>>();
-
-        let mut corrupted_downloads: Vec<Item> = Vec::new();
-        let corrupted_dest: String = String::from(clean(format!("{}/corrupted", path_prefix)).to_str().unwrap());
-        
+        let mut text_items = std::mem::take(&mut run.text_items);
         for item in &mut text_items {
-            if let Ok(mut resp) = Request::from_item(item).unwrap().send() {
-                match resp.save_to_txt(path_prefix) {
-                    Ok(file_size) => {
-                        item.update_size(file_size);
-                    }
-                    Err(_) => {
-                        match resp.save_as_txt(&corrupted_dest, &format!("{}.corrupted", corrupted_downloads.len())) {
-                            Ok(file_size) => {
-                                item.update_size(file_size);
-                                add_log!("===Abnormal download: file name or path invalid===\n{}Relocated to: {}\n", 
-                                    item, 
-                                    &format!("{}.corrupted", corrupted_downloads.len())
-                                );
-                                corrupted_downloads.push(item.clone());
-                            }
-                            Err(_) => { todo!() }
-                        }
-                    }
+            if self.downloaded(item) || !self.allowed(item) {
+                continue;
+            }
+            let kept = self.kept_hash(item, path_prefix);
+            let corrupted_name = format!("{}.corrupted", run.corrupted_downloads.len());
+            let download = retry::run(&self.config.retry, || {
+                let req = self.request_item(item)?;
+                if let Some(hash) = &kept {
+                    return req.download_changed(path_prefix, hash, true).map(refreshed)
+                }
+                return match req.download_txt(path_prefix) {
+                    Err(GopherError::InvalidPath { .. }) 
+                        => req.download_txt_as(&run.corrupted_dest, &corrupted_name).map(Download::Relocated),
+                    download => download.map(Download::Saved),
                 }
+            });
+            self.record_download(item, download, &mut run);
+        }
+        run.text_items = text_items;
+
+        let mut non_text_items = std::mem::take(&mut run.non_text_items);
+        for item in &mut non_text_items {
+            if self.downloaded(item) || !self.allowed(item) {
+                continue;
             }
+            let kept = self.kept_hash(item, path_prefix);
+            let corrupted_name = format!("{}.corrupted", run.corrupted_downloads.len());
+            let download = retry::run(&self.config.retry, || {
+                let req = self.request_item(item)?;
+                if let Some(hash) = &kept {
+                    return req.download_changed(path_prefix, hash, false).map(refreshed)
+                }
+                return match req.download(path_prefix) {
+                    Err(GopherError::InvalidPath { .. }) 
+                        => req.download_as(&run.corrupted_dest, &corrupted_name).map(Download::Relocated),
+                    download => download.map(Download::Saved),
+                }
+            });
+            self.record_download(item, download, &mut run);
+        }
+        run.non_text_items = non_text_items;
+
+        self.finish_downloads(&run);
+        return Ok(self)
+    }
+
+    // download_all on a tokio runtime
+    #[cfg(feature = "async")]
+    pub async fn async_download_all(&mut self) -> Result<&mut Self, GopherError> {
+        if let Some(path_prefix) = self.config.download_root.clone() {
+            return self.async_download_all_to(&path_prefix).await
+        } else {
+            return Err(GopherError::InvalidPath { path: String::new(), source: None })
         }
+    }
+
+    // download_all_to on a tokio runtime, items are downloaded one after another
+    #[cfg(feature = "async")]
+    pub async fn async_download_all_to(&mut self, path_prefix: &str) -> Result<&mut Self, GopherError> {
+        let mut run = self.start_downloads(path_prefix)?;
 
+        let mut text_items = std::mem::take(&mut run.text_items);
+        for item in &mut text_items {
+            if self.downloaded(item) || !self.async_allowed(item).await {
+                continue;
+            }
+            let kept = self.kept_hash(item, path_prefix);
+            let corrupted_name = format!("{}.corrupted", run.corrupted_downloads.len());
+            let (client, target, corrupted_dest) = (&*self, &*item, run.corrupted_dest.as_str());
+            let download = retry::async_run(&self.config.retry, || async {
+                let req = client.request_item(target)?;
+                if let Some(hash) = &kept {
+                    return req.async_download_changed(path_prefix, hash, true).await.map(refreshed)
+                }
+                return match req.async_download_txt(path_prefix).await {
+                    Err(GopherError::InvalidPath { .. }) => req.async_download_txt_as(corrupted_dest, &corrupted_name).await
+                        .map(Download::Relocated),
+                    download => download.map(Download::Saved),
+                }
+            }).await;
+            self.record_download(item, download, &mut run);
+        }
+        run.text_items = text_items;
+
+        let mut non_text_items = std::mem::take(&mut run.non_text_items);
         for item in &mut non_text_items {
-            match Request::from_item(item).unwrap().download(path_prefix) {
-                Ok(file_size) => {
-                    item.update_size(file_size);
+            if self.downloaded(item) || !self.async_allowed(item).await {
+                continue;
+            }
+            let kept = self.kept_hash(item, path_prefix);
+            let corrupted_name = format!("{}.corrupted", run.corrupted_downloads.len());
+            let (client, target, corrupted_dest) = (&*self, &*item, run.corrupted_dest.as_str());
+            let download = retry::async_run(&self.config.retry, || async {
+                let req = client.request_item(target)?;
+                if let Some(hash) = &kept {
+                    return req.async_download_changed(path_prefix, hash, false).await.map(refreshed)
                 }
-                Err((0, _)) => {
-                    match Request::from_item(item).unwrap()
-                        .download_as(
-                            &corrupted_dest, 
-                            &format!("{}.corrupted", corrupted_downloads.len())
-                        ) {
-                        Ok(file_size) => {
-                            item.update_size(file_size);
-                            corrupted_downloads.push(item.clone());
-                        }
-                        Err(_) => { todo!() }
-                    }
+                return match req.async_download(path_prefix).await {
+                    Err(GopherError::InvalidPath { .. }) => req.async_download_as(corrupted_dest, &corrupted_name).await
+                        .map(Download::Relocated),
+                    download => download.map(Download::Saved),
                 }
-                Err(_) => { todo!() }
+            }).await;
+            self.record_download(item, download, &mut run);
+        }
+        run.non_text_items = non_text_items;
+
+        self.finish_downloads(&run);
+        return Ok(self)
+    }
+
+    // create the download folder and pick the items to download
+    fn start_downloads(&self, path_prefix: &str) -> Result<DownloadRun, GopherError> {
+        let time_start = Utc::now();
+        println!("===Starting file downloads===\nTime: {}\n",
+            time_start.format("%Y-%m-%d %H:%M:%S %Z")
+        );
+
+        fs::create_dir_all(path_prefix).map_err(|source| { 
+            GopherError::InvalidPath { path: String::from(path_prefix), source: Some(source) } 
+        })?;
+
+        return Ok(DownloadRun {
+            time_start,
+            path_prefix: String::from(path_prefix),
+            corrupted_dest: String::from(clean(format!("{}/corrupted", path_prefix)).to_str().unwrap()),
+            text_items: self.items.iter()
+                .filter(|item| { item.class() == Some(ItemClass::Text) }).cloned()
+                .collect::<Vec<Item>>(),
+            non_text_items: self.items.iter()
+                .filter(|item| { item.class() == Some(ItemClass::Binary) }).cloned()
+                .collect::<Vec<Item>>(),
+            corrupted_downloads: Vec::new(),
+            failed_before: self.failures.len(),
+            disallowed_before: self.disallowed.len(),
+            since_checkpoint: 0,
+        })
+    }
+
+    // false when the robots.txt of the item's server disallows it, the item is then recorded in self.disallowed.
+    // Servers the scan did not reach have their rules fetched first
+    fn allowed(&mut self, item: &Item) -> bool {
+        let Item::DATA { domain, port, .. } = item else { return true };
+        let host = (domain.clone(), *port);
+        if !self.server_rules.contains_key(&host) {
+            let rules = ServerRules::fetch(&self.config, domain, *port);
+            self.server_rules.insert(host.clone(), rules);
+        }
+        return self.keep_allowed(item)
+    }
+
+    // allowed on async sockets
+    #[cfg(feature = "async")]
+    async fn async_allowed(&mut self, item: &Item) -> bool {
+        let Item::DATA { domain, port, .. } = item else { return true };
+        let host = (domain.clone(), *port);
+        if !self.server_rules.contains_key(&host) {
+            let rules = ServerRules::async_fetch(&self.config, domain, *port).await;
+            self.server_rules.insert(host.clone(), rules);
+        }
+        return self.keep_allowed(item)
+    }
+
+    fn keep_allowed(&mut self, item: &Item) -> bool {
+        let Item::DATA { domain, port, location, .. } = item else { return true };
+        let rules = self.server_rules.get(&(domain.clone(), *port));
+        if rules.is_none_or(|rules| { rules.allows(location) }) {
+            return true
+        }
+        self.disallowed.push(Referer::new(domain, *port, location));
+        return false
+    }
+
+    // true when a previous run already downloaded the item, its size is taken from that run
+    fn downloaded(&self, item: &mut Item) -> bool {
+        let file_size = item.url().and_then(|url| { self.downloads.get(&url.to_string()) }).map(|record| { record.size });
+        if let Some(file_size) = file_size {
+            item.update_size(file_size);
+        }
+        return file_size.is_some()
+    }
+
+    // sha256 of the snapshot's download of the item when the file saved then is still in place with it.
+    // Such an item is fetched again all the same, but its file is only written when the content changed
+    fn kept_hash(&self, item: &Item, path_prefix: &str) -> Option<String> {
+        let (Some(snapshot), Some(url), Item::DATA { location, .. }) = (&self.snapshot, item.url(), item) else {
+            return None
+        };
+        let hash = snapshot.downloads.get(&url.to_string())?.hash.clone()?;
+        let saved = file_hash(clean(format!("{}/{}", path_prefix, local_path(location))));
+        return (saved.as_ref() == Some(&hash)).then_some(hash)
+    }
+
+    // record a download along with the attempts it took
+    fn record_download(&mut self, item: &mut Item, download: (Result<Download, GopherError>, u32), run: &mut DownloadRun) {
+        let (download, attempts) = download;
+        let (file_size, local_path) = match (download, &*item) {
+            (Ok(Download::Saved(file_size) | Download::Unchanged(file_size)), Item::DATA { location, .. }) 
+                => (file_size, clean(format!("{}/{}", run.path_prefix, local_path(location)))),
+            (Ok(Download::Saved(file_size) | Download::Unchanged(file_size)), _) => (file_size, clean(&run.path_prefix)),
+            (Ok(Download::Relocated(file_size)), _) => {
+                let corrupted_name = format!("{}.corrupted", run.corrupted_downloads.len());
+                add_log!("===Abnormal download: file name or path invalid===\n{}Relocated to: {}\n", 
+                    item, 
+                    &corrupted_name
+                );
+                run.corrupted_downloads.push(item.clone());
+                (file_size, clean(format!("{}/{}", run.corrupted_dest, corrupted_name)))
             }
+            (Err(error), _) => { self.record_failure(item, error, attempts); return }
+        };
+
+        item.update_size(file_size);
+        if let Some(url) = item.url() {
+            self.downloads.insert(url.to_string(), FileRecord::new(file_size, file_hash(local_path)));
         }
+        run.since_checkpoint += 1;
+        if run.since_checkpoint >= CHECKPOINT_DOWNLOADS {
+            run.since_checkpoint = 0;
+            self.save_checkpoint(None);
+        }
+    }
 
+    // report the end of the downloads and log the items downloaded
+    fn finish_downloads(&self, run: &DownloadRun) {
+        self.save_checkpoint(None);
         let time_end = Utc::now();
         println!("===File downloads complete===\nTime: {}\nTotal runtime: {} ms\n\nUpdating logs...\n",
-            time_end.format("%Y-%m-%d %H:%M:%S %Z").to_string(),
-            time_end.signed_duration_since(time_start).num_milliseconds()
+            time_end.format("%Y-%m-%d %H:%M:%S %Z"),
+            time_end.signed_duration_since(run.time_start).num_milliseconds()
         );
 
         add_log!("===All text items===\nTotal: {}\n\n{}", 
-            text_items.len(),
-            text_items.iter().map(|item| {item.to_string()}).collect::<Vec<String>>().join("\n")
+            run.text_items.len(),
+            run.text_items.iter().map(|item| { self.download_entry(item) }).collect::<Vec<String>>().join("\n")
         );
 
         add_log!("===All binary items===\nTotal: {}\n\n{}", 
-            non_text_items.len(),
-            non_text_items.iter().map(|item| {item.to_string()}).collect::<Vec<String>>().join("\n")
+            run.non_text_items.len(),
+            run.non_text_items.iter().map(|item| { self.download_entry(item) }).collect::<Vec<String>>().join("\n")
+        );
+
+        add_log!("===Failed downloads===\nTotal: {}\n\n{}", 
+            self.failures.len() - run.failed_before,
+            self.failures[run.failed_before..].iter()
+                .map(|failure| { failure.to_string() })
+                .collect::<Vec<String>>().join("\n")
+        );
+
+        add_log!("===Downloads disallowed by robots.txt===\nTotal: {}\n\n{}", 
+            self.disallowed.len() - run.disallowed_before,
+            self.disallowed[run.disallowed_before..].iter()
+                .map(|referer| { referer.to_string() })
+                .collect::<Vec<String>>().join("\n")
         );
 
-        let (min_text_item, max_text_item) 
-            = (text_items.iter().min().unwrap(), text_items.iter().max().unwrap());
+        if let Some(diff) = self.diff() {
+            add_log!("===Changed files since snapshot===\nTotal: {}\n\n{}", 
+                diff.changed_files.len(),
+                diff.changed_files.iter().map(|file| { file.to_string() }).collect::<Vec<String>>().join("\n")
+            );
+        }
 
-        let mut min_text_read_buff: Vec<u8> = Vec::new();
-        let mut max_text_read_buff: Vec<u8> = Vec::new();
+        if let (Some(min_text_item), Some(max_text_item)) 
+            = (run.text_items.iter().min(), run.text_items.iter().max())
+        {
+            let mut min_text_read_buff: Vec<u8> = Vec::new();
+            let mut max_text_read_buff: Vec<u8> = Vec::new();
 
-        if let Some(i) = corrupted_downloads.iter().position(|item| {item == min_text_item}) {
-            if let Ok(mut file) = fs::File::open(clean(format!("{}/{}.corrupted", corrupted_dest, i))) {
-                file.read_to_end(&mut min_text_read_buff).unwrap();
+            if let Some(i) = run.corrupted_downloads.iter().position(|item| {item == min_text_item}) {
+                if let Ok(mut file) = fs::File::open(clean(format!("{}/{}.corrupted", run.corrupted_dest, i))) {
+                    file.read_to_end(&mut min_text_read_buff).unwrap_or_default();
+                }
+            } else if let Item::DATA { location, .. } = min_text_item {
+                if let Ok(mut file) = fs::File::open(clean(format!("{}/{}", run.path_prefix, local_path(location)))) {
+                    file.read_to_end(&mut min_text_read_buff).unwrap_or_default();
+                }
             }
-        } else {
-            if let Item::DATA { tag, size, caption, referer, location, domain, port } = min_text_item {
-                if let Ok(mut file) = fs::File::open(clean(format!("{}/{}", path_prefix, location))) {
-                    file.read_to_end(&mut min_text_read_buff).unwrap();
+
+            if let Some(i) = run.corrupted_downloads.iter().position(|item| {item == max_text_item}) {
+                if let Ok(mut file) = fs::File::open(clean(format!("{}/{}.corrupted", run.corrupted_dest, i))) {
+                    file.read_to_end(&mut max_text_read_buff).unwrap_or_default();
+                }
+            } else if let Item::DATA { location, .. } = max_text_item {
+                if let Ok(mut file) = fs::File::open(clean(format!("{}/{}", run.path_prefix, local_path(location)))) {
+                    file.read_to_end(&mut max_text_read_buff).unwrap_or_default();
                 }
             }
-        }
 
-        if let Some(i) = corrupted_downloads.iter().position(|item| {item == max_text_item}) {
-            if let Ok(mut file) = fs::File::open(clean(format!("{}/{}.corrupted", corrupted_dest, i))) {
-                file.read_to_end(&mut max_text_read_buff).unwrap();
+            add_log!("===Smallest text file===\n{}", min_text_item);
+            add_log!("===Largest text file===\n{}", max_text_item);
+
+            if let Ok(content) = str::from_utf8(&min_text_read_buff) {
+                add_log!("===Smallest text file content===\n{}\n", content);
             }
-        } else {
-            if let Item::DATA { tag, size, caption, referer, location, domain, port } = max_text_item {
-                if let Ok(mut file) = fs::File::open(clean(format!("{}/{}", path_prefix, location))) {
-                    file.read_to_end(&mut max_text_read_buff).unwrap();
-                }
+            if let Ok(content) = str::from_utf8(&max_text_read_buff) {
+                add_log!("===Largest text file content===\n{}\n", content);
             }
         }
 
-        add_log!("===Smallest text file===\n{}", min_text_item);
-        add_log!("===Largest text file===\n{}", max_text_item);
-
-        if let Ok(content) = str::from_utf8(&min_text_read_buff) {
-            add_log!("===Smallest text file content===\n{}\n", content);
+        if let (Some(min_bin_item), Some(max_bin_item)) 
+            = (run.non_text_items.iter().min(), run.non_text_items.iter().max())
+        {
+            add_log!("===Size of smallest and largest binary file===\nSmallest: {}\nLargest: {}\n", 
+                min_bin_item.get_size().unwrap_or_default(),
+                max_bin_item.get_size().unwrap_or_default()
+            );
         }
-        if let Ok(content) = str::from_utf8(&max_text_read_buff) {
-            add_log!("===Largest text file content===\n{}\n", content);
+
+        println!("Logs update complete.\n");
+    }
+
+    fn record_failure(&mut self, item: &Item, error: GopherError, attempts: u32) {
+        if let Item::DATA { location, domain, port, .. } = item {
+            self.push_failure(Referer::new(domain, *port, location), FailureStage::Download, attempts, error);
         }
+    }
 
-        add_log!("===Size of smallest and largest binary file===\nSmallest: {}\nLargest: {}\n", 
-            non_text_items.iter().min().unwrap().get_size().unwrap(),
-            non_text_items.iter().max().unwrap().get_size().unwrap()
-        );
+    // "Address: " line of the address the last request for referer connected to, empty when it connected nowhere
+    fn address_entry(&self, referer: &Referer) -> String {
+        return self.config.request.addresses.get(referer)
+            .map(|address| { format!("Address: {}\n", address) })
+            .unwrap_or_default()
+    }
 
-        println!("Logs update complete.\n");
+    // log entry of an item to download, with the address its download connected to
+    fn download_entry(&self, item: &Item) -> String {
+        let Item::DATA { domain, port, location, .. } = item else { return item.to_string() };
+        return format!("{}{}", item, self.address_entry(&Referer::new(domain, *port, location)))
+    }
 
-        return Ok(self)
+    // record a request given up on, with the address its last attempt connected to
+    fn push_failure(&mut self, referer: Referer, stage: FailureStage, attempts: u32, error: GopherError) {
+        let mut failure = Failure::new(referer, stage, attempts, error);
+        failure.update_address(self.config.request.addresses.get(&failure.referer));
+        self.failures.push(failure);
     }
-}
\ No newline at end of file
+}
+
+// where a downloaded item ended up
+enum Download {
+    Saved(u64),
+    // the selector is no valid local path, saved under "corrupted" instead
+    Relocated(u64),
+    // the same content as the file the snapshot saved, left as it is
+    Unchanged(u64),
+}
+
+// an item the snapshot downloaded, saved again only when its content changed
+fn refreshed((file_size, replaced): (u64, bool)) -> Download {
+    return if replaced { Download::Saved(file_size) } else { Download::Unchanged(file_size) }
+}
+
+// items and progress of a download_all_to run
+struct DownloadRun {
+    time_start: DateTime<Utc>,
+    path_prefix: String,
+    corrupted_dest: String,
+    text_items: Vec<Item>,
+    non_text_items: Vec<Item>,
+    corrupted_downloads: Vec<Item>,
+    failed_before: usize,
+    disallowed_before: usize,
+    // downloads since the last checkpoint
+    since_checkpoint: usize,
+}
@@ -0,0 +1,246 @@
+use std::fmt::{self, Display};
+
+use super::error::GopherError;
+
+// Gopher+ extensions
+// ======
+// Menu lines of gopher+ items carry a fifth field "+" (or "?" for items with an ASK form).
+// Requests append one more tab separated field to the selector:
+//     selector\t+[view]  => fetch the item, optionally as one of its views
+//     selector\t!        => attribute block of a single item
+//     selector\t$        => attribute blocks of every item on a menu
+// Responses start with a header line:
+//     +-1  => data terminated by a line with a single "."
+//     +-2  => data ends when the server closes the connection
+//     +n   => exactly n bytes of data follow
+//     -... => error, the data is an error message
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlusRequest {
+    Fetch(Option<String>),
+    Attributes,
+    DirectoryAttributes,
+}
+
+impl PlusRequest {
+    // field appended to the request line
+    pub fn field(&self) -> String {
+        match self {
+            Self::Fetch(Some(view)) => format!("+{}", view),
+            Self::Fetch(None) => "+".to_string(),
+            Self::Attributes => "!".to_string(),
+            Self::DirectoryAttributes => "$".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlusLength {
+    DotTerminated,
+    UntilClose,
+    Bytes(u64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlusHeader {
+    pub length: PlusLength,
+    // false when the header starts with "-", the body then holds the error message
+    pub success: bool,
+}
+
+impl PlusHeader {
+    pub fn parse(line: &str) -> Result<Self, GopherError> {
+        let line = line.trim_end();
+        let success = match line.chars().next() {
+            Some('+') => true,
+            Some('-') => false,
+            _ => return Err(GopherError::Parse { message: format!("Invalid gopher+ header: {:?}", line) }),
+        };
+        let length = match &line[1..] {
+            "-1" => PlusLength::DotTerminated,
+            "-2" => PlusLength::UntilClose,
+            size => PlusLength::Bytes(size.parse::<u64>().map_err(|_| {
+                GopherError::Parse { message: format!("Invalid gopher+ header: {:?}", line) }
+            })?),
+        };
+        return Ok(Self { length, success })
+    }
+
+    // number of bytes announced by the server, if it announced any
+    pub fn size(&self) -> Option<u64> {
+        if let PlusLength::Bytes(size) = self.length { Some(size) } else { None }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlusAdmin {
+    // " Admin: Name <email>"
+    pub admin: Option<String>,
+    // " Mod-Date: <YYYYMMDDhhmmss>"
+    pub mod_date: Option<String>,
+    // every other "Key: value" line of the block, i.e. Score, Score-range
+    pub fields: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlusView {
+    pub mime: String,
+    pub language: Option<String>,
+    // as written by the server, i.e. "10k"
+    pub size: Option<String>,
+}
+
+impl PlusView {
+    // parse " text/plain En_US: <10k>"
+    pub fn parse(line: &str) -> Option<Self> {
+        let (desc, size) = match line.trim().split_once(':') {
+            Some((desc, size)) => (desc.trim(), size.trim()),
+            None => (line.trim(), ""),
+        };
+        let mut desc_fields = desc.split_whitespace();
+        let mime = desc_fields.next()?;
+        let size = size.trim_start_matches('<').trim_end_matches('>').trim();
+        return Some(Self {
+            mime: String::from(mime),
+            language: desc_fields.next().map(String::from),
+            size: if size.is_empty() { None } else { Some(String::from(size)) },
+        })
+    }
+
+    // approximate size in bytes from "512", "10k", "1.5M" or "2G"
+    pub fn bytes(&self) -> Option<u64> {
+        let size = self.size.as_ref()?.trim();
+        let (num, unit) = match size.char_indices().find(|(_, c)| { c.is_ascii_alphabetic() }) {
+            Some((i, _)) => (&size[..i], size[i..].to_ascii_lowercase()),
+            None => (size, String::new()),
+        };
+        let multiplier = match unit.trim_end_matches('b') {
+            "" => 1.0,
+            "k" => 1024.0,
+            "m" => 1024.0 * 1024.0,
+            "g" => 1024.0 * 1024.0 * 1024.0,
+            _ => return None,
+        };
+        return num.trim().parse::<f64>().ok().map(|num| { (num * multiplier) as u64 })
+    }
+}
+
+impl Display for PlusView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mime)?;
+        if let Some(language) = &self.language {
+            write!(f, " {}", language)?;
+        }
+        if let Some(size) = &self.size {
+            write!(f, ": <{}>", size)?;
+        }
+        return Ok(())
+    }
+}
+
+// Attributes of a single gopher+ item
+#[derive(Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlusAttributes {
+    // the menu line following "+INFO: "
+    pub info: Option<String>,
+    pub admin: Option<PlusAdmin>,
+    pub views: Vec<PlusView>,
+    pub abstract_text: Option<String>,
+    // blocks not covered above, by name without the leading "+"
+    pub other: Vec<(String, String)>,
+}
+
+impl PlusAttributes {
+    // parse the attribute blocks of one or more items, every "+INFO" block starts a new item
+    pub fn parse_blocks(content: &str) -> Result<Vec<Self>, GopherError> {
+        let mut all_attributes: Vec<Self> = Vec::new();
+        let mut block: Option<(String, Vec<String>)> = None;
+
+        let lines = content.split('\n')
+            .map(|line| { line.trim_end_matches('\r') })
+            .take_while(|line| { *line != "." });
+
+        for line in lines {
+            if let Some(block_start) = line.strip_prefix('+') {
+                if let Some((name, lines)) = block.take() {
+                    Self::push_block(&mut all_attributes, &name, &lines);
+                }
+                let (name, first) = block_start.split_once(':').unwrap_or((block_start, ""));
+                let first = first.trim_start();
+                block = Some((
+                    String::from(name.trim()),
+                    if first.is_empty() { Vec::new() } else { vec![String::from(first)] }
+                ));
+            } else if let Some((_, lines)) = block.as_mut() {
+                lines.push(String::from(line.strip_prefix(' ').unwrap_or(line)));
+            } else if !line.trim().is_empty() {
+                return Err(GopherError::Parse { message: format!("Attribute line outside of a block: {:?}", line) })
+            }
+        }
+        if let Some((name, lines)) = block.take() {
+            Self::push_block(&mut all_attributes, &name, &lines);
+        }
+        return Ok(all_attributes)
+    }
+
+    // parse the attributes of a single item
+    pub fn parse(content: &str) -> Result<Self, GopherError> {
+        return Ok(Self::parse_blocks(content)?.into_iter().next().unwrap_or_default())
+    }
+
+    fn push_block(all_attributes: &mut Vec<Self>, name: &str, lines: &[String]) {
+        if name.eq_ignore_ascii_case("INFO") || all_attributes.is_empty() {
+            all_attributes.push(Self::default());
+        }
+        let attributes = all_attributes.last_mut().unwrap();
+        let lines = lines.iter().filter(|line| { !line.trim().is_empty() });
+
+        match name.to_ascii_uppercase().as_str() {
+            "INFO" => {
+                attributes.info = lines.map(String::from).next();
+            }
+            "ADMIN" => {
+                let mut admin = PlusAdmin::default();
+                for line in lines {
+                    let (key, value) = line.split_once(':').unwrap_or((line, ""));
+                    let value = String::from(value.trim());
+                    match key.trim() {
+                        "Admin" => { admin.admin = Some(value) }
+                        "Mod-Date" => {
+                            // "Fri Jul 16 07:11:59 1993 <19930716071159>", keep the bracketed timestamp
+                            let stamp = value.rsplit_once('<')
+                                .map(|(_, stamp)| { String::from(stamp.trim_end_matches('>')) })
+                                .unwrap_or(value);
+                            admin.mod_date = Some(stamp)
+                        }
+                        key => { admin.fields.push((String::from(key), value)) }
+                    }
+                }
+                attributes.admin = Some(admin);
+            }
+            "VIEWS" => {
+                attributes.views = lines.filter_map(|line| { PlusView::parse(line) }).collect();
+            }
+            "ABSTRACT" => {
+                attributes.abstract_text = Some(lines.map(|line| { line.as_str() }).collect::<Vec<&str>>().join("\n"));
+            }
+            _ => {
+                attributes.other.push((
+                    String::from(name),
+                    lines.map(|line| { line.as_str() }).collect::<Vec<&str>>().join("\n")
+                ));
+            }
+        }
+    }
+
+    // MIME type of the first view, the default representation of the item
+    pub fn mime(&self) -> Option<&str> {
+        return self.views.first().map(|view| { view.mime.as_str() })
+    }
+}
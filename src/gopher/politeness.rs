@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::config::{PolitenessConfig, MAX_INTERVAL};
+use super::transport::Connection;
+
+// Politeness
+// ======
+// Every connection a Request opens, blocking or async, for a menu, a search, attributes or a download, first takes
+// a Permit from the Politeness of its RequestConfig. A Permit is given once the server has a connection to spare
+// and its turn has come, requests to a server starting at least the interval of its settings apart, and is held
+// until the connection is dropped. Bytes read on the connection are paid for out of one bucket shared by every
+// server, reads wait once the bandwidth of the PolitenessConfig is spent.
+// The crawl delay a server asks for in its robots.txt stretches its interval for the rest of the run.
+// Clones of a Politeness share their state, so the clones of a ClientConfig handed to every Request of a crawl,
+// its worker threads and its downloads keep to the same pace.
+
+// how long an async request waits before asking again for a connection to a busy server
+#[cfg(feature = "async")]
+const BUSY_POLL: Duration = Duration::from_millis(10);
+
+#[derive(Default)]
+struct HostState {
+    // connections open to the server
+    active: usize,
+    // earliest start of the next request to the server
+    next_start: Option<Instant>,
+}
+
+impl HostState {
+    // start of a request taking its turn now, the one after it starts interval later
+    fn reserve(&mut self, interval: Duration) -> Instant {
+        let now = Instant::now();
+        let start = self.next_start.map_or(now, |next_start| { next_start.max(now) });
+        self.next_start = Some(start + interval);
+        self.active += 1;
+        return start
+    }
+}
+
+// Bytes that may be read right away, refilled at the bandwidth up to a second's worth
+struct Bucket {
+    available: f64,
+    updated: Instant,
+}
+
+struct Shared {
+    config: PolitenessConfig,
+    hosts: Mutex<HashMap<(String, u16), HostState>>,
+    released: Condvar,
+    bucket: Mutex<Bucket>,
+    // crawl delays servers asked for, see gopher::robots
+    crawl_delays: Mutex<HashMap<(String, u16), Duration>>,
+}
+
+// Pace of the requests of a crawl, cloning it shares the pace
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "PolitenessConfig", into = "PolitenessConfig")
+)]
+pub struct Politeness(Arc<Shared>);
+
+impl Politeness {
+    pub fn new(config: PolitenessConfig) -> Self {
+        let bucket = Bucket { available: config.bandwidth.unwrap_or_default() as f64, updated: Instant::now() };
+        return Self(Arc::new(Shared {
+            config,
+            hosts: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+            bucket: Mutex::new(bucket),
+            crawl_delays: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    pub fn config(&self) -> &PolitenessConfig {
+        return &self.0.config
+    }
+
+    // space the requests to a server at least crawl_delay apart from now on, whatever its settings
+    pub fn update_crawl_delay(&self, domain: &str, port: u16, crawl_delay: Duration) {
+        self.0.crawl_delays.lock().unwrap().insert((String::from(domain), port), crawl_delay);
+    }
+
+    // least time between the starts of two requests to the server, a crawl delay counting up to MAX_INTERVAL
+    pub fn interval(&self, domain: &str, port: u16) -> Duration {
+        let crawl_delay = self.0.crawl_delays.lock().unwrap().get(&(String::from(domain), port)).copied();
+        return self.0.config.for_host(domain, port).interval().max(crawl_delay.unwrap_or_default().min(MAX_INTERVAL))
+    }
+
+    // block until a connection to the server is allowed and its turn has come
+    pub fn acquire(&self, domain: &str, port: u16) -> Permit {
+        let host = (String::from(domain), port);
+        let (settings, interval) = (self.0.config.for_host(domain, port), self.interval(domain, port));
+        let mut hosts = self.0.hosts.lock().unwrap();
+        while hosts.get(&host).is_some_and(|state| { state.active >= settings.max_conns }) {
+            hosts = self.0.released.wait(hosts).unwrap();
+        }
+        let start = hosts.entry(host.clone()).or_default().reserve(interval);
+        drop(hosts);
+
+        thread::sleep(start.saturating_duration_since(Instant::now()));
+        return Permit { politeness: self.clone(), host }
+    }
+
+    // the permit and the time its request may start, None while the server has no connection to spare
+    pub fn try_acquire(&self, domain: &str, port: u16) -> Option<(Permit, Instant)> {
+        let host = (String::from(domain), port);
+        let (settings, interval) = (self.0.config.for_host(domain, port), self.interval(domain, port));
+        let mut hosts = self.0.hosts.lock().unwrap();
+        let state = hosts.entry(host.clone()).or_default();
+        if state.active >= settings.max_conns {
+            return None
+        }
+        let start = state.reserve(interval);
+        return Some((Permit { politeness: self.clone(), host }, start))
+    }
+
+    // acquire on tokio timers
+    #[cfg(feature = "async")]
+    pub async fn async_acquire(&self, domain: &str, port: u16) -> Permit {
+        loop {
+            if let Some((permit, start)) = self.try_acquire(domain, port) {
+                tokio::time::sleep_until(start.into()).await;
+                return permit
+            }
+            tokio::time::sleep(BUSY_POLL).await;
+        }
+    }
+
+    // take size bytes out of the bucket, the wait before more may be read
+    pub fn spend(&self, size: usize) -> Duration {
+        let Some(bandwidth) = self.0.config.bandwidth else {
+            return Duration::ZERO
+        };
+        let mut bucket = self.0.bucket.lock().unwrap();
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.updated).as_secs_f64() * bandwidth as f64;
+        bucket.available = (bucket.available + refill).min(bandwidth as f64) - size as f64;
+        bucket.updated = now;
+        if bucket.available >= 0.0 {
+            return Duration::ZERO
+        }
+        return Duration::from_secs_f64(-bucket.available / bandwidth as f64)
+    }
+}
+
+impl Default for Politeness {
+    fn default() -> Self {
+        return Self::new(PolitenessConfig::default())
+    }
+}
+
+impl From<PolitenessConfig> for Politeness {
+    fn from(config: PolitenessConfig) -> Self {
+        return Self::new(config)
+    }
+}
+
+impl From<Politeness> for PolitenessConfig {
+    fn from(politeness: Politeness) -> Self {
+        return politeness.0.config.clone()
+    }
+}
+
+// politeness is equal when the settings are, whatever the state
+impl PartialEq for Politeness {
+    fn eq(&self, other: &Self) -> bool {
+        return self.0.config == other.0.config
+    }
+}
+
+impl fmt::Debug for Politeness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.debug_tuple("Politeness").field(&self.0.config).finish()
+    }
+}
+
+// A connection to a server counted against its limit, given back when dropped
+pub struct Permit {
+    politeness: Politeness,
+    host: (String, u16),
+}
+
+impl Permit {
+    // take size bytes read on the connection out of the bucket, the wait before more may be read
+    pub fn spend(&self, size: usize) -> Duration {
+        return self.politeness.spend(size)
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut hosts = self.politeness.0.hosts.lock().unwrap();
+        if let Some(state) = hosts.get_mut(&self.host) {
+            state.active -= 1;
+        }
+        self.politeness.0.released.notify_all();
+    }
+}
+
+// A connection holding its permit, reads keep to the bandwidth
+pub struct PoliteConnection {
+    inner: Box<dyn Connection>,
+    permit: Permit,
+}
+
+impl PoliteConnection {
+    pub fn new(inner: Box<dyn Connection>, permit: Permit) -> Self {
+        return Self { inner, permit }
+    }
+}
+
+impl Read for PoliteConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        thread::sleep(self.permit.spend(size));
+        return Ok(size)
+    }
+}
+
+impl Write for PoliteConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        return self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return self.inner.flush()
+    }
+}
+
+impl Connection for PoliteConnection {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        return self.inner.set_read_timeout(timeout)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        return self.inner.shutdown()
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        return self.inner.peer_addr()
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::{self, Display};
+use std::hash::{BuildHasher, Hasher};
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+
+use super::config::RetryPolicy;
+use super::error::{FailureKind, GopherError};
+use super::types::Referer;
+
+// Retries
+// ======
+// Menu fetches and downloads of a Client go through run (or async_run), which tries again as long as the error met
+// is of a kind the RetryPolicy of the ClientConfig retries and attempts are left, waiting the backoff of the retry
+// in between. Whatever still fails ends up in Client.failures as a Failure, with the number of attempts made.
+
+// what a Client was doing when a request failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureStage {
+    // fetching a menu or search results while scanning
+    Menu,
+    // fetching the gopher+ attributes of a menu while scanning
+    Attributes,
+    Download,
+}
+
+impl Display for FailureStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Menu => write!(f, "menu"),
+            Self::Attributes => write!(f, "attributes"),
+            Self::Download => write!(f, "download"),
+        }
+    }
+}
+
+// A request given up on
+pub struct Failure {
+    pub referer: Referer,
+    pub stage: FailureStage,
+    // attempts made, the first one included
+    pub attempts: u32,
+    // error of the last attempt
+    pub error: GopherError,
+    // when the last attempt failed
+    pub time: DateTime<Utc>,
+    // address the last attempt connected to, None when it connected nowhere
+    pub address: Option<SocketAddr>,
+}
+
+impl Failure {
+    pub fn new(referer: Referer, stage: FailureStage, attempts: u32, error: GopherError) -> Self {
+        return Self { referer, stage, attempts, error, time: Utc::now(), address: None }
+    }
+
+    pub fn update_address(&mut self, address: Option<SocketAddr>) -> &mut Self {
+        self.address = address;
+        return self
+    }
+
+    pub fn kind(&self) -> FailureKind {
+        return self.error.kind()
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let address = self.address.map(|address| { format!("Address: {}\n", address) }).unwrap_or_default();
+        return write!(f, "Target: {}\n{}Stage: {}\nKind: {}\nAttempts: {}\nTime: {}\nError: {}\n",
+            self.referer,
+            address,
+            self.stage,
+            self.kind(),
+            self.attempts,
+            self.time.format("%Y-%m-%d %H:%M:%S %Z"),
+            self.error
+        )
+    }
+}
+
+// wait before the given retry counting from 1, the backoff of policy less a random share of up to its jitter
+pub fn delay(policy: &RetryPolicy, retry: u32) -> Duration {
+    // hashers of a fresh RandomState are seeded at random, which is all the randomness jitter needs
+    let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+    // jitter is a public field, one set outside 0..=1 without update_jitter takes nothing off
+    let jitter = if (0.0..=1.0).contains(&policy.jitter) { policy.jitter } else { 0.0 };
+    return policy.backoff(retry).mul_f64(1.0 - jitter * random)
+}
+
+// call attempt until it succeeds or fails in a way policy does not retry, with the number of attempts made
+pub fn run<T, F>(policy: &RetryPolicy, mut attempt: F) -> (Result<T, GopherError>, u32)
+where
+    F: FnMut() -> Result<T, GopherError>,
+{
+    let mut attempts = 1;
+    loop {
+        match attempt() {
+            Err(error) if policy.retries(&error, attempts) => {
+                thread::sleep(delay(policy, attempts));
+                attempts += 1;
+            }
+            result => return (result, attempts),
+        }
+    }
+}
+
+// run with the waits on tokio timers
+#[cfg(feature = "async")]
+pub async fn async_run<T, F, Fut>(policy: &RetryPolicy, mut attempt: F) -> (Result<T, GopherError>, u32)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, GopherError>>,
+{
+    let mut attempts = 1;
+    loop {
+        match attempt().await {
+            Err(error) if policy.retries(&error, attempts) => {
+                tokio::time::sleep(delay(policy, attempts)).await;
+                attempts += 1;
+            }
+            result => return (result, attempts),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt::{self, Display};
+use std::io::Read;
+use std::time::Duration;
+
+use super::caps::Caps;
+use super::config::ClientConfig;
+use super::io::{Request, ResponseBuilder};
+use super::stream::TextReader;
+
+// Server rules
+// ======
+// Before a crawl reaches a server it asks for
+//     /robots.txt  which selectors crawlers should keep away from, and how long to wait between requests
+//     caps.txt     the capabilities of the server, how its selectors are put together and what software it runs
+// A server answering with an error item, or not at all, has no such file. Disallowed menus are left out of the
+// crawl and disallowed files out of the downloads, the crawl delay is added to the politeness of the server and
+// the selectors of menus on a server with a caps.txt are normalised by its path rules.
+
+// product token the groups of a robots.txt are matched against, "*" groups apply when none names it
+pub const AGENT: &str = "simple_gopher_in_rust";
+
+// An Allow or Disallow line
+#[derive(Clone, Debug, PartialEq)]
+pub struct RobotsRule {
+    pub allow: bool,
+    // selector prefix, "*" matches any run of characters and a trailing "$" the end of the selector
+    pub pattern: String,
+}
+
+impl RobotsRule {
+    fn matches(&self, selector: &str) -> bool {
+        let (pattern, anchored) = match self.pattern.strip_suffix('$') {
+            Some(pattern) => (pattern, true),
+            None => (self.pattern.as_str(), false),
+        };
+        let mut parts = pattern.split('*');
+        let first = parts.next().unwrap_or_default();
+        let Some(mut rest) = selector.strip_prefix(first) else {
+            return false
+        };
+        let parts = parts.collect::<Vec<&str>>();
+        for (i, part) in parts.iter().enumerate() {
+            // the last part of an anchored pattern has to end the selector
+            let found = if anchored && i == parts.len() - 1 { rest.rfind(part) } else { rest.find(part) };
+            match found {
+                Some(at) => rest = &rest[at + part.len()..],
+                None => return false,
+            }
+        }
+        return !anchored || rest.is_empty()
+    }
+}
+
+// The rules of a robots.txt that apply to AGENT
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Robots {
+    pub rules: Vec<RobotsRule>,
+    // wait the server asks for between two requests
+    pub crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    // the groups naming AGENT, or the "*" groups when none does. Unknown lines and comments are skipped
+    pub fn parse(content: &str) -> Self {
+        let (mut named, mut any) = (Robots::default(), Robots::default());
+        let mut agents: Vec<String> = Vec::new();
+        // a user-agent line after rules starts another group
+        let mut in_rules = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((field, value)) = line.split_once(':') else { continue };
+            let (field, value) = (field.trim().to_lowercase(), value.trim());
+
+            if field == "user-agent" {
+                if in_rules {
+                    agents.clear();
+                    in_rules = false;
+                }
+                agents.push(value.to_lowercase());
+                continue;
+            }
+            in_rules = true;
+            let mut groups: Vec<&mut Robots> = Vec::new();
+            if agents.iter().any(|agent| { agent == &AGENT.to_lowercase() }) {
+                groups.push(&mut named);
+            }
+            if agents.iter().any(|agent| { agent == "*" }) {
+                groups.push(&mut any);
+            }
+            for group in groups {
+                match field.as_str() {
+                    // an empty disallow allows everything
+                    "allow" | "disallow" if !value.is_empty() => {
+                        group.rules.push(RobotsRule { allow: field == "allow", pattern: String::from(value) });
+                    }
+                    // delays that are negative or too long for a Duration are left out
+                    "crawl-delay" => {
+                        group.crawl_delay = value.parse::<f64>().ok()
+                            .and_then(|seconds| { Duration::try_from_secs_f64(seconds).ok() });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let named_agent = content.lines().any(|line| {
+            let line = line.split('#').next().unwrap_or_default();
+            return line.split_once(':').is_some_and(|(field, value)| {
+                return field.trim().eq_ignore_ascii_case("user-agent") && value.trim().eq_ignore_ascii_case(AGENT)
+            })
+        });
+        return if named_agent { named } else { any }
+    }
+
+    // true unless the longest pattern matching selector disallows it, Allow wins a tie.
+    // Selectors without a leading "/" are matched as if they had one
+    pub fn allows(&self, selector: &str) -> bool {
+        let selector = if selector.starts_with('/') { String::from(selector) } else { format!("/{}", selector) };
+        let rule = self.rules.iter()
+            .filter(|rule| { rule.matches(&selector) })
+            .max_by_key(|rule| { (rule.pattern.len(), rule.allow) });
+        return rule.is_none_or(|rule| { rule.allow })
+    }
+}
+
+impl Display for Robots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rule in &self.rules {
+            writeln!(f, "{}: {}", if rule.allow { "Allow" } else { "Disallow" }, rule.pattern)?;
+        }
+        if let Some(crawl_delay) = self.crawl_delay {
+            writeln!(f, "Crawl-delay: {}", crawl_delay.as_secs_f64())?;
+        }
+        return Ok(())
+    }
+}
+
+// The robots.txt and caps.txt of a server, None for those it does not have or that were not asked for
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServerRules {
+    pub robots: Option<Robots>,
+    pub caps: Option<Caps>,
+}
+
+impl ServerRules {
+    // ask the server for the files config.robots and config.caps want, a single attempt each.
+    // The crawl delay of the robots.txt is added to the politeness of config
+    pub fn fetch(config: &ClientConfig, domain: &str, port: u16) -> Self {
+        let robots = config.robots.then(|| { rules_request(config, domain, port, "/robots.txt").send() });
+        let caps = config.caps.then(|| { rules_request(config, domain, port, "caps.txt").send() });
+        let rules = Self {
+            robots: robots.and_then(|resp| { text(resp.ok()?) }).map(|content| { Robots::parse(&content) }),
+            caps: caps.and_then(|resp| { text(resp.ok()?) }).map(|content| { Caps::parse(&content) }),
+        };
+        return rules.keep_pace(config, domain, port)
+    }
+
+    // fetch on async sockets
+    #[cfg(feature = "async")]
+    pub async fn async_fetch(config: &ClientConfig, domain: &str, port: u16) -> Self {
+        let robots = match config.robots {
+            true => text_of(rules_request(config, domain, port, "/robots.txt").async_send().await),
+            false => None,
+        };
+        let caps = match config.caps {
+            true => text_of(rules_request(config, domain, port, "caps.txt").async_send().await),
+            false => None,
+        };
+        let rules = Self {
+            robots: robots.map(|content| { Robots::parse(&content) }),
+            caps: caps.map(|content| { Caps::parse(&content) }),
+        };
+        return rules.keep_pace(config, domain, port)
+    }
+
+    fn keep_pace(self, config: &ClientConfig, domain: &str, port: u16) -> Self {
+        if let Some(crawl_delay) = self.crawl_delay() {
+            config.request.politeness.update_crawl_delay(domain, port, crawl_delay);
+        }
+        return self
+    }
+
+    pub fn allows(&self, selector: &str) -> bool {
+        return self.robots.as_ref().is_none_or(|robots| { robots.allows(selector) })
+    }
+
+    pub fn crawl_delay(&self) -> Option<Duration> {
+        return self.robots.as_ref().and_then(|robots| { robots.crawl_delay })
+    }
+
+    // selector as the caps.txt of the server puts it together, None without one
+    pub fn normalize(&self, selector: &str) -> Option<String> {
+        return self.caps.as_ref().map(|caps| { caps.normalize(selector) })
+    }
+}
+
+fn rules_request(config: &ClientConfig, domain: &str, port: u16, selector: &str) -> Request {
+    let mut req = Request::new(domain, port);
+    req.update_config(&config.request).update_path(selector);
+    return req
+}
+
+#[cfg(feature = "async")]
+fn text_of(resp: Result<ResponseBuilder, super::error::GopherError>) -> Option<String> {
+    return text(resp.ok()?)
+}
+
+// the body of a text response, None for an error item
+fn text(resp: ResponseBuilder) -> Option<String> {
+    let first_line = resp.raw.split(|byte| { *byte == b'\n' }).next().unwrap_or_default();
+    if first_line.starts_with(b"3") && first_line.contains(&b'\t') {
+        return None
+    }
+    let mut content = String::new();
+    TextReader::new(&resp.raw[..]).read_to_string(&mut content).ok()?;
+    return Some(content)
+}
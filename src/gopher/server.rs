@@ -0,0 +1,346 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::error::GopherError;
+use super::io::selector_path;
+use super::types::{Item, ItemClass, ItemKind, Referer};
+use super::url::GopherUrl;
+
+// Server
+// ======
+// Serves a folder, max_connections worker threads answering one connection each while further connections wait
+// to be accepted. A request "selector" maps to the same path under the root, so ".." never leaves it and symlinks
+// resolving outside the root are neither listed nor served. It is answered with
+//     a folder   its gophermap when it has one, otherwise a menu of its entries sorted by name with a search item first
+//     a folder + search terms   a menu of every file and folder below it whose name holds all the terms
+//     a file     its content, dot-stuffed and ended with a "." line for text kinds, raw for everything else
+// Gophermap lines are completed the usual way: a line without a tab is an info line, a missing host and port are
+// the server's own and a selector not starting with "/" is relative to the folder. Every menu line goes out through
+// Item::menu_line, the same formatter the client parses with, so what a client scans is what the server lists.
+
+// longest request line read before the request is dropped
+const MAX_REQUEST: u64 = 4096;
+
+#[derive(Clone)]
+pub struct Server {
+    // folder served at selector "/"
+    pub root: String,
+    // address listened on
+    pub bind: String,
+    pub port: u16,
+    // host name written into menus for clients to connect back to
+    pub host: String,
+    // time a client has to send its request
+    pub req_timeout: Duration,
+    // connections answered at once, one worker thread each
+    pub max_connections: usize,
+}
+
+impl Server {
+    pub fn new(root: &str) -> Self {
+        return Self {
+            root: String::from(root),
+            bind: String::from("127.0.0.1"),
+            port: 70,
+            host: String::from("localhost"),
+            req_timeout: Duration::from_secs(10),
+            max_connections: 16,
+        }
+    }
+
+    pub fn update_bind(&mut self, bind: &str) -> &mut Self {
+        self.bind = String::from(bind);
+        return self
+    }
+
+    pub fn update_port(&mut self, port: u16) -> &mut Self {
+        self.port = port;
+        return self
+    }
+
+    pub fn update_host(&mut self, host: &str) -> &mut Self {
+        self.host = String::from(host);
+        return self
+    }
+
+    pub fn update_req_timeout(&mut self, req_timeout: Duration) -> &mut Self {
+        self.req_timeout = req_timeout;
+        return self
+    }
+
+    // at least one connection is answered at a time
+    pub fn update_max_connections(&mut self, max_connections: usize) -> &mut Self {
+        self.max_connections = max_connections.max(1);
+        return self
+    }
+
+    pub fn listen(&self) -> Result<TcpListener, GopherError> {
+        let address = if self.bind.contains(':') { format!("[{}]:{}", self.bind, self.port) } else { format!("{}:{}", self.bind, self.port) };
+        return TcpListener::bind(&address).map_err(|source| { GopherError::Bind { address, source } })
+    }
+
+    // listen on bind and port and answer requests until the process ends
+    pub fn serve(&self) -> Result<(), GopherError> {
+        let listener = self.listen()?;
+        self.serve_listener(listener);
+        return Ok(())
+    }
+
+    // answer the connections accepted by listener, port 0 listeners keep the port they were given in menus
+    pub fn serve_listener(&self, listener: TcpListener) {
+        let mut server = self.clone();
+        if let Ok(address) = listener.local_addr() {
+            server.port = address.port();
+        }
+        // a connection is only accepted once a worker is free to take it
+        let (sender, receiver) = mpsc::sync_channel::<TcpStream>(0);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..server.max_connections.max(1) {
+            let server = server.clone();
+            let receiver = receiver.clone();
+            thread::spawn(move || {
+                loop {
+                    let Ok(stream) = receiver.lock().unwrap().recv() else {
+                        return
+                    };
+                    if let Err(error) = server.handle(stream) {
+                        println!("==Request failed==\n{}\n", error);
+                    }
+                }
+            });
+        }
+        for stream in listener.incoming().flatten() {
+            if sender.send(stream).is_err() {
+                break;
+            }
+        }
+    }
+
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(self.req_timeout))?;
+        let mut request: Vec<u8> = Vec::new();
+        BufReader::new((&stream).take(MAX_REQUEST)).read_until(b'\n', &mut request)?;
+        let request = String::from_utf8_lossy(&request);
+
+        let mut writer = io::BufWriter::new(&stream);
+        self.respond(request.trim_end_matches(['\r', '\n']), &mut writer)?;
+        writer.flush()?;
+        return stream.shutdown(std::net::Shutdown::Both)
+    }
+
+    // write the answer to one request line
+    pub fn respond<W: Write>(&self, request: &str, writer: &mut W) -> io::Result<()> {
+        let mut fields = request.split('\t');
+        let selector = fields.next().unwrap_or_default();
+        // a gopher+ field is not a search, gopher+ is not served
+        let search = fields.next().filter(|field| { !(field.starts_with('+') || *field == "!" || *field == "$") });
+
+        let path = self.local(selector);
+        let gophermap = path.join("gophermap");
+        if path.is_dir() && self.within_root(&path) {
+            return match search {
+                Some(terms) => self.write_search(selector, terms, writer),
+                None if gophermap.is_file() && self.within_root(&gophermap) => self.write_gophermap(selector, &gophermap, writer),
+                None => self.write_listing(selector, &path, writer),
+            }
+        }
+        if path.is_file() && self.within_root(&path) {
+            return match file_kind(&path).class() {
+                ItemClass::Text => write_text(&path, writer),
+                _ => io::copy(&mut fs::File::open(&path)?, writer).map(|_| {}),
+            }
+        }
+        return self.write_menu(&[self.error_item(selector, &format!("'{}' not found", selector))], writer)
+    }
+
+    fn local(&self, selector: &str) -> PathBuf {
+        return Path::new(&self.root).join(selector_path(selector))
+    }
+
+    // whether path, with its symlinks resolved, is still under the root
+    fn within_root(&self, path: &Path) -> bool {
+        return match (fs::canonicalize(&self.root), fs::canonicalize(path)) {
+            (Ok(root), Ok(path)) => path.starts_with(root),
+            _ => false,
+        }
+    }
+
+    // visible entries of a folder sorted by name, gophermaps, dot files and symlinks out of the root are left out
+    fn entries(&self, path: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(path).map(|dir| {
+            return dir.flatten()
+                .map(|entry| { entry.file_name().to_string_lossy().to_string() })
+                .filter(|name| { !name.starts_with('.') && name != "gophermap" && self.within_root(&path.join(name)) })
+                .collect::<Vec<String>>()
+        }).unwrap_or_default();
+        names.sort();
+        return names
+    }
+
+    fn referer(&self, selector: &str) -> Referer {
+        return Referer::new(&self.host, self.port, selector)
+    }
+
+    fn item(&self, from: &str, kind: ItemKind, selector: &str, caption: &str) -> Item {
+        let mut url = GopherUrl::new(&self.host, self.port);
+        url.update_kind(kind).update_selector(selector);
+        return Item::from_url(&self.referer(from), &url, caption)
+    }
+
+    fn error_item(&self, from: &str, message: &str) -> Item {
+        let mut url = GopherUrl::new("error.host", 1);
+        url.update_kind(ItemKind::Error);
+        return Item::from_url(&self.referer(from), &url, message)
+    }
+
+    fn write_menu<W: Write>(&self, items: &[Item], writer: &mut W) -> io::Result<()> {
+        for item in items {
+            write!(writer, "{}\r\n", item.menu_line())?;
+        }
+        return write!(writer, ".\r\n")
+    }
+
+    // the item for a file or folder found at path, listed under selector
+    fn entry_item(&self, from: &str, path: &Path, selector: &str, caption: &str) -> Item {
+        let kind = if path.is_dir() { ItemKind::Menu } else { file_kind(path) };
+        return self.item(from, kind, selector, caption)
+    }
+
+    fn write_listing<W: Write>(&self, selector: &str, path: &Path, writer: &mut W) -> io::Result<()> {
+        let dir = selector_path(selector);
+        let mut items = vec![self.item(selector, ItemKind::Search, &format!("/{}", dir), "Search file names")];
+        for name in self.entries(path) {
+            let entry_selector = format!("/{}", join(&dir, &name));
+            items.push(self.entry_item(selector, &path.join(&name), &entry_selector, &name));
+        }
+        return self.write_menu(&items, writer)
+    }
+
+    // every file and folder below the folder at selector whose name holds all of the terms, ignoring case
+    fn write_search<W: Write>(&self, selector: &str, terms: &str, writer: &mut W) -> io::Result<()> {
+        let terms = terms.split_whitespace().map(|term| { term.to_lowercase() }).collect::<Vec<String>>();
+        let mut items: Vec<Item> = Vec::new();
+        let mut pending = vec![selector_path(selector)];
+        while let Some(dir) = pending.pop() {
+            for name in self.entries(&self.local(&dir)) {
+                let found = join(&dir, &name);
+                let found_path = self.local(&found);
+                let lower = name.to_lowercase();
+                if terms.iter().all(|term| { lower.contains(term) }) {
+                    items.push(self.entry_item(selector, &found_path, &format!("/{}", found), &found));
+                }
+                if found_path.is_dir() {
+                    pending.push(found);
+                }
+            }
+        }
+        items.sort_by(|a, b| { a.url().map(|url| { url.selector }).cmp(&b.url().map(|url| { url.selector })) });
+        if items.is_empty() {
+            items.push(self.item(selector, ItemKind::Info, "", "No file names match"));
+        }
+        return self.write_menu(&items, writer)
+    }
+
+    fn write_gophermap<W: Write>(&self, selector: &str, gophermap: &Path, writer: &mut W) -> io::Result<()> {
+        let dir = selector_path(selector);
+        let content = fs::read(gophermap)?;
+        let mut items: Vec<Item> = Vec::new();
+        for line in String::from_utf8_lossy(&content).lines() {
+            if line == "." {
+                break;
+            }
+            items.push(self.gophermap_item(selector, &dir, line));
+        }
+        return self.write_menu(&items, writer)
+    }
+
+    // complete a gophermap line into a menu item
+    fn gophermap_item(&self, selector: &str, dir: &str, line: &str) -> Item {
+        let mut fields = line.split('\t');
+        let display = fields.next().unwrap_or_default();
+        if !line.contains('\t') || display.is_empty() {
+            return self.item(selector, ItemKind::Info, "", line)
+        }
+
+        let location = fields.next().unwrap_or_default();
+        let location = if location.starts_with('/') || location.starts_with("URL:") || location.is_empty() {
+            String::from(location)
+        } else {
+            format!("/{}", join(dir, location))
+        };
+        let (host, port) = match (fields.next().filter(|host| { !host.is_empty() }), fields.next()) {
+            (Some(host), Some(port)) if !port.is_empty() => (String::from(host), String::from(port)),
+            (Some(host), _) => (String::from(host), String::from("70")),
+            (None, _) => (self.host.clone(), self.port.to_string()),
+        };
+        let rest = fields.map(|field| { format!("\t{}", field) }).collect::<String>();
+        let complete = format!("{}\t{}\t{}\t{}{}", display, location, host, port, rest);
+        return Item::from_str(&self.referer(selector), &complete)
+            .unwrap_or_else(|_| { self.item(selector, ItemKind::Info, "", line) })
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    return if dir.is_empty() { String::from(name) } else { format!("{}/{}", dir, name) }
+}
+
+// item kind of a file from its extension, files without a known one are text when their start is utf-8
+pub fn file_kind(path: &Path) -> ItemKind {
+    let extension = path.extension().map(|extension| { extension.to_string_lossy().to_lowercase() }).unwrap_or_default();
+    return match extension.as_str() {
+        "txt" | "md" | "text" | "csv" | "log" | "conf" | "json" => ItemKind::Text,
+        "html" | "htm" => ItemKind::Html,
+        "xml" => ItemKind::Xml,
+        "rtf" => ItemKind::Rtf,
+        "ics" => ItemKind::Calendar,
+        "eml" | "mbox" => ItemKind::Mime,
+        "gif" => ItemKind::Gif,
+        "png" => ItemKind::Png,
+        "jpg" | "jpeg" | "bmp" | "webp" | "tif" | "tiff" => ItemKind::Image,
+        "pdf" => ItemKind::Pdf,
+        "doc" | "docx" | "odt" => ItemKind::Document,
+        "wav" | "mp3" | "ogg" | "flac" => ItemKind::Sound,
+        "hqx" => ItemKind::BinHex,
+        "uue" => ItemKind::Uuencoded,
+        _ if is_text(path) => ItemKind::Text,
+        _ => ItemKind::Binary,
+    }
+}
+
+fn is_text(path: &Path) -> bool {
+    let mut start: Vec<u8> = Vec::new();
+    if fs::File::open(path).and_then(|file| { file.take(1024).read_to_end(&mut start) }).is_err() {
+        return false
+    }
+    // a multi-byte char may be cut at the end of the sample
+    return !start.contains(&0) && match std::str::from_utf8(&start) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none(),
+    }
+}
+
+// a text file line by line, lines starting with "." get another one and a "." line ends it,
+// a last line without a line ending gets one
+fn write_text<W: Write>(path: &Path, writer: &mut W) -> io::Result<()> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let mut line: Vec<u8> = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if line.starts_with(b".") {
+            writer.write_all(b".")?;
+        }
+        writer.write_all(&line)?;
+        if !line.ends_with(b"\n") {
+            writer.write_all(b"\r\n")?;
+        }
+    }
+    return writer.write_all(b".\r\n")
+}
@@ -0,0 +1,233 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::str;
+
+use super::error::GopherError;
+use super::plus::{PlusHeader, PlusLength};
+use super::transport::Connection;
+use super::types::{Item, ItemClass, Referer};
+
+// Body of a response read straight off the connection, the gopher+ header
+// (if any) is already consumed and a "+size" body is cut off at its size
+pub struct Response {
+    reader: BufReader<Box<dyn Connection>>,
+    referer: Referer,
+    pub plus: Option<PlusHeader>,
+    // bytes left of a "+size" body
+    remaining: Option<u64>,
+}
+
+impl Response {
+    pub fn new(reader: BufReader<Box<dyn Connection>>, referer: Referer, plus: Option<PlusHeader>) -> Self {
+        let remaining = plus.as_ref().and_then(|header| { header.size() });
+        return Self { reader, referer, plus, remaining }
+    }
+
+    pub fn referer(&self) -> &Referer {
+        return &self.referer
+    }
+
+    // parse the body as a menu, one item per line as they arrive
+    pub fn menu(self) -> MenuReader<BufReader<Response>> {
+        let referer = self.referer.clone();
+        return MenuReader::new(BufReader::new(self), referer)
+    }
+
+    // read the body as a text file, without the "." terminator line
+    pub fn text(self) -> TextReader<BufReader<Response>> {
+        return TextReader::new(BufReader::new(self))
+    }
+
+    // read the body as a binary file, without the "." terminator line of a "+-1" gopher+ body
+    pub fn data(&mut self) -> DataReader<BufReader<&mut Response>> {
+        let plus = self.plus.clone();
+        return DataReader::new(BufReader::new(self), plus.as_ref())
+    }
+
+    pub fn shutdown(&self) {
+        if self.reader.get_ref().shutdown().is_err() {
+            println!("==Stream shutdown failed!==");
+        }
+    }
+}
+
+impl Read for Response {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let limit = match self.remaining {
+            Some(0) => return Ok(0),
+            Some(remaining) => (remaining.min(buf.len() as u64)) as usize,
+            None => buf.len(),
+        };
+
+        let read_size = self.reader.read(&mut buf[..limit])?;
+        if let Some(remaining) = self.remaining.as_mut() {
+            if read_size == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("Response truncated: {} bytes missing", remaining)
+                ))
+            }
+            *remaining -= read_size as u64;
+        }
+        return Ok(read_size)
+    }
+}
+
+// read one line including its line ending, None at the end of the stream
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<Vec<u8>>, GopherError> {
+    let mut line: Vec<u8> = Vec::new();
+    match reader.read_until(b'\n', &mut line) {
+        Ok(0) => Ok(None),
+        Ok(_) => Ok(Some(line)),
+        Err(error) => Err(GopherError::from_read(error)),
+    }
+}
+
+pub fn is_terminator(line: &[u8]) -> bool {
+    return matches!(line, b".\r\n" | b".\n" | b".")
+}
+
+// Iterator over the items of a menu, ending at the "." line
+pub struct MenuReader<R: BufRead> {
+    reader: R,
+    referer: Referer,
+    first: bool,
+    done: bool,
+}
+
+impl<R: BufRead> MenuReader<R> {
+    pub fn new(reader: R, referer: Referer) -> Self {
+        return Self { reader, referer, first: true, done: false }
+    }
+}
+
+impl<R: BufRead> Iterator for MenuReader<R> {
+    type Item = Result<Item, GopherError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+
+        let line = match read_line(&mut self.reader) {
+            Ok(Some(line)) if is_terminator(&line) => { self.done = true; return None }
+            Ok(Some(line)) => line,
+            Ok(None) => {
+                self.done = true;
+                return Some(Err(GopherError::parse("Response message incomplete or mal-formatted!")))
+            }
+            Err(error) => { self.done = true; return Some(Err(error)) }
+        };
+
+        let line = match str::from_utf8(&line) {
+            Ok(line) => line.trim_end_matches('\n').trim_end_matches('\r'),
+            Err(error) => { self.done = true; return Some(Err(GopherError::from(error))) }
+        };
+
+        let item = Item::from_str(&self.referer, line).map_err(|message| { GopherError::Parse { message } });
+        let first = self.first;
+        self.first = false;
+
+        // a menu made up of an error item is the server refusing the selector
+        return match item {
+            Ok(item) if first && item.class() == Some(ItemClass::Error) => {
+                self.done = true;
+                Some(Err(GopherError::ServerError { item: Box::new(item) }))
+            }
+            item => Some(item),
+        }
+    }
+}
+
+// Text file body with the "." terminator line dropped and dot-stuffed lines ("..") restored,
+// content without a terminator is passed through as it is
+pub struct TextReader<R: BufRead> {
+    reader: R,
+    pending: Vec<u8>,
+    offset: usize,
+    done: bool,
+}
+
+impl<R: BufRead> TextReader<R> {
+    pub fn new(reader: R) -> Self {
+        return Self { reader, pending: Vec::new(), offset: 0, done: false }
+    }
+}
+
+impl<R: BufRead> Read for TextReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset >= self.pending.len() {
+            if self.done {
+                return Ok(0)
+            }
+            self.pending.clear();
+            self.offset = 0;
+
+            if self.reader.read_until(b'\n', &mut self.pending)? == 0 || is_terminator(&self.pending) {
+                self.pending.clear();
+                self.done = true;
+            } else if self.pending.starts_with(b"..") {
+                self.offset = 1;
+            }
+        }
+
+        let size = buf.len().min(self.pending.len() - self.offset);
+        buf[..size].copy_from_slice(&self.pending[self.offset..self.offset + size]);
+        self.offset += size;
+        return Ok(size)
+    }
+}
+
+// Body of a binary download passed through as it is, up to the "." line for "+-1" gopher+ bodies.
+// Lines are not held whole, a line starting with "." is only looked at long enough to tell the "." line apart
+pub struct DataReader<R: BufRead> {
+    reader: R,
+    dot_terminated: bool,
+    line_start: bool,
+    // first bytes of a line read ahead, not handed out yet
+    head: Vec<u8>,
+    done: bool,
+}
+
+impl<R: BufRead> DataReader<R> {
+    pub fn new(reader: R, plus: Option<&PlusHeader>) -> Self {
+        let dot_terminated = plus.is_some_and(|header| { header.length == PlusLength::DotTerminated });
+        return Self { reader, dot_terminated, line_start: true, head: Vec::new(), done: false }
+    }
+}
+
+impl<R: BufRead> Read for DataReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.dot_terminated {
+            return self.reader.read(buf)
+        }
+        if self.line_start && self.head.is_empty() && !self.done {
+            while self.head.len() < 3 && self.head.last() != Some(&b'\n') && self.head.first().is_none_or(|byte| { *byte == b'.' }) {
+                let Some(byte) = self.reader.fill_buf()?.first().copied() else { break };
+                self.head.push(byte);
+                self.reader.consume(1);
+            }
+            self.done = is_terminator(&self.head) || self.head == b".\r";
+        }
+        if self.done || buf.is_empty() {
+            return Ok(0)
+        }
+
+        let size = if self.head.is_empty() {
+            let available = self.reader.fill_buf()?;
+            let line_end = available.iter().position(|byte| { *byte == b'\n' }).map_or(available.len(), |i| { i + 1 });
+            let size = line_end.min(buf.len());
+            buf[..size].copy_from_slice(&available[..size]);
+            self.reader.consume(size);
+            size
+        } else {
+            let size = self.head.len().min(buf.len());
+            buf[..size].copy_from_slice(&self.head[..size]);
+            self.head.drain(..size);
+            size
+        };
+        if size > 0 {
+            self.line_start = buf[size - 1] == b'\n';
+        }
+        return Ok(size)
+    }
+}
@@ -0,0 +1,528 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Cursor, ErrorKind, Read, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::config::{IpPreference, Proxy, RequestConfig, TlsMode};
+use super::error::GopherError;
+use super::politeness::PoliteConnection;
+use super::types::Referer;
+
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "tls")]
+mod pins;
+#[cfg(feature = "tls")]
+pub use tls::{secure, speaks_tls, TlsConnection, TlsConnector};
+
+// Transports
+// ======
+// A Request writes its request line to, and reads the response from, whatever Connection the Connector of its
+// RequestConfig opens to (domain, port), with the timeouts and IP preference of that config. Connectors given here
+//     TcpConnector     plain TCP to every address of the domain, Happy Eyeballs style, the default
+//     Socks5Connector  TCP through a SOCKS5 proxy, the proxy resolves the domain unless told otherwise
+//     TlsConnector     TLS over another connector, behind the "tls" feature
+//     MemoryConnector  recorded sessions replayed from memory, nothing goes over the network
+// Connectors stack, a TlsConnector over a Socks5Connector talks TLS to the server through the proxy.
+// The proxy RequestConfig.proxy has for a server puts a Socks5Connector in front of the transport for its requests,
+// so per-host proxy rules work with any transport. Onion names are never resolved locally, they fail with Dns
+// unless the proxy resolves them.
+// Whatever the transport, the TLS mode RequestConfig.tls has for a server can put TLS over the connection, with
+// certificates checked against the webpki roots, pinned to the fingerprints of a pin file, both, or neither.
+// Servers in Auto mode that answer the TLS handshake with anything but TLS are connected to again in the clear.
+// Async requests open their own tokio TCP streams and do not go through a transport, they try the addresses one
+// after the other, talk SOCKS5 to the proxy of the server when it has one and fail rather than connect directly
+// to a server that has TLS on.
+// Either way a connection waits for a permit of the politeness of the config first, see gopher::politeness.
+
+// an open connection to a server
+pub trait Connection: Read + Write + Send {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+    // close both directions, reads and writes fail afterwards
+    fn shutdown(&self) -> io::Result<()>;
+    // address at the other end, the proxy for proxied connections, None when not on a socket
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        return None
+    }
+}
+
+pub trait Connector: Send + Sync {
+    fn connect(&self, domain: &str, port: u16, config: &RequestConfig) -> Result<Box<dyn Connection>, GopherError>;
+}
+
+impl Connection for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        return TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        return TcpStream::shutdown(self, Shutdown::Both)
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        return TcpStream::peer_addr(self).ok()
+    }
+}
+
+// Connector shared by every request of a crawl, cloning it shares the connector
+#[derive(Clone)]
+pub struct Transport(Arc<dyn Connector>);
+
+impl Transport {
+    pub fn new<C: Connector + 'static>(connector: C) -> Self {
+        return Self(Arc::new(connector))
+    }
+
+    pub fn tcp() -> Self {
+        return Self::new(TcpConnector)
+    }
+
+    pub fn connect(&self, domain: &str, port: u16, config: &RequestConfig) -> Result<Box<dyn Connection>, GopherError> {
+        return self.0.connect(domain, port, config)
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        return Self::tcp()
+    }
+}
+
+// transports are equal when they share a connector
+impl PartialEq for Transport {
+    fn eq(&self, other: &Self) -> bool {
+        return std::ptr::addr_eq(Arc::as_ptr(&self.0), Arc::as_ptr(&other.0))
+    }
+}
+
+impl fmt::Debug for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.debug_struct("Transport").finish_non_exhaustive()
+    }
+}
+
+// Address that answered the last request for each referer of a crawl, the proxy for proxied requests.
+// Clones share the record, so the clones of a RequestConfig handed to every Request of a crawl fill the same one
+#[derive(Clone, Default)]
+pub struct AddressLog(Arc<Mutex<HashMap<String, SocketAddr>>>);
+
+impl AddressLog {
+    pub fn record(&self, referer: &Referer, address: Option<SocketAddr>) {
+        let mut addresses = self.0.lock().unwrap();
+        match address {
+            Some(address) => addresses.insert(referer.to_string(), address),
+            None => addresses.remove(&referer.to_string()),
+        };
+    }
+
+    // None when the last request for referer connected to no address, or there was none
+    pub fn get(&self, referer: &Referer) -> Option<SocketAddr> {
+        return self.0.lock().unwrap().get(&referer.to_string()).copied()
+    }
+}
+
+// address logs are equal whatever they hold
+impl PartialEq for AddressLog {
+    fn eq(&self, _other: &Self) -> bool {
+        return true
+    }
+}
+
+impl fmt::Debug for AddressLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.debug_struct("AddressLog").finish_non_exhaustive()
+    }
+}
+
+// transport of config, behind the proxy of the server when it has one
+pub fn route(config: &RequestConfig, domain: &str, port: u16) -> Transport {
+    return match config.proxy.proxy_for(domain, port) {
+        Some(proxy) => {
+            let mut socks = Socks5Connector::from_proxy(proxy);
+            socks.update_inner(config.transport.clone());
+            Transport::new(socks)
+        }
+        None => config.transport.clone(),
+    }
+}
+
+// connect to (domain, port) through the transport and proxy of config, over TLS when the TLS mode of the server says so,
+// once the politeness of config lets the request start
+pub fn open(config: &RequestConfig, domain: &str, port: u16) -> Result<Box<dyn Connection>, GopherError> {
+    let permit = config.politeness.acquire(domain, port);
+    let transport = route(config, domain, port);
+    let connect = || { transport.connect(domain, port, config) };
+    let connection = match config.tls.mode_for(domain, port) {
+        TlsMode::Off => connect(),
+        TlsMode::On => secure(connect()?, &config.tls, domain, port, config.conn_timeout),
+        TlsMode::Auto => match secure(connect()?, &config.tls, domain, port, config.conn_timeout) {
+            Err(GopherError::Handshake { source, .. }) if !speaks_tls(&source) => connect(),
+            secured => secured,
+        },
+    }?;
+    return Ok(Box::new(PoliteConnection::new(connection, permit)))
+}
+
+#[cfg(not(feature = "tls"))]
+pub fn secure(
+    _sock: Box<dyn Connection>, _tls: &super::config::TlsConfig, domain: &str, port: u16, _conn_timeout: Duration,
+) -> Result<Box<dyn Connection>, GopherError> {
+    return Err(GopherError::Handshake {
+        domain: String::from(domain),
+        port,
+        source: io::Error::new(ErrorKind::Unsupported, "TLS needs the tls feature"),
+    })
+}
+
+// without the tls feature every server is taken as one that does not speak TLS
+#[cfg(not(feature = "tls"))]
+pub fn speaks_tls(_error: &io::Error) -> bool {
+    return false
+}
+
+// onion names (RFC 7686) are left to proxies, resolving them locally would give them away
+pub fn refuse_onion(domain: &str, port: u16) -> Result<(), GopherError> {
+    if domain.to_lowercase().trim_end_matches('.').ends_with(".onion") {
+        return Err(GopherError::Dns {
+            domain: String::from(domain),
+            port,
+            source: Some(io::Error::new(ErrorKind::InvalidInput, "onion names are only reachable through a socks5h:// proxy")),
+        })
+    }
+    return Ok(())
+}
+
+// every address domain resolves to locally, in the order they are to be tried
+pub fn resolve(domain: &str, port: u16, ip: IpPreference) -> Result<Vec<SocketAddr>, GopherError> {
+    refuse_onion(domain, port)?;
+    let dns_error = |source| { GopherError::Dns { domain: String::from(domain), port, source } };
+    let resolved = (domain, port).to_socket_addrs().map_err(|error| { dns_error(Some(error)) })?.collect::<Vec<SocketAddr>>();
+    if resolved.is_empty() {
+        return Err(dns_error(None))
+    }
+    let addrs = order_addresses(resolved, ip);
+    if addrs.is_empty() {
+        return Err(dns_error(Some(io::Error::new(ErrorKind::NotFound, format!("no address left by the IP preference {:?}", ip)))))
+    }
+    return Ok(addrs)
+}
+
+// addresses of the two families in turn (RFC 8305 section 4), starting with the preferred family or, for Auto,
+// the family of the first address, with the family left out by Ipv4 and Ipv6 dropped
+pub fn order_addresses(addrs: Vec<SocketAddr>, ip: IpPreference) -> Vec<SocketAddr> {
+    let ipv6_first = match ip {
+        IpPreference::Auto => addrs.first().is_some_and(SocketAddr::is_ipv6),
+        IpPreference::PreferIpv6 | IpPreference::Ipv6 => true,
+        IpPreference::PreferIpv4 | IpPreference::Ipv4 => false,
+    };
+    let (mut ipv6, mut ipv4): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs.into_iter().partition(SocketAddr::is_ipv6);
+    match ip {
+        IpPreference::Ipv4 => ipv6.clear(),
+        IpPreference::Ipv6 => ipv4.clear(),
+        _ => {}
+    }
+    let (mut first, mut second) = if ipv6_first { (ipv6.into_iter(), ipv4.into_iter()) } else { (ipv4.into_iter(), ipv6.into_iter()) };
+    let mut ordered: Vec<SocketAddr> = Vec::new();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => break,
+            (one, other) => { ordered.extend(one); ordered.extend(other); }
+        }
+    }
+    return ordered
+}
+
+// head start an address gets before the next one is tried alongside it (RFC 8305 Connection Attempt Delay)
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+// Connect to the addresses in order, each attempt running on its own thread for at most conn_timeout. The next
+// attempt starts once the last one failed or had ATTEMPT_DELAY to itself, the first connection made is kept and
+// the later ones are dropped. Fails with the error of the first address when none connects.
+pub fn connect_any(addrs: &[SocketAddr], conn_timeout: Duration) -> io::Result<TcpStream> {
+    if let [addr] = addrs {
+        return TcpStream::connect_timeout(addr, conn_timeout)
+    }
+    let (sender, receiver) = mpsc::channel::<(usize, io::Result<TcpStream>)>();
+    let start = |index: usize| {
+        let (sender, addr) = (sender.clone(), addrs[index]);
+        thread::spawn(move || { let _ = sender.send((index, TcpStream::connect_timeout(&addr, conn_timeout))); });
+    };
+
+    let mut errors: Vec<Option<io::Error>> = addrs.iter().map(|_| { None }).collect();
+    start(0);
+    let (mut started, mut failed) = (1, 0);
+    while failed < addrs.len() {
+        // every attempt ends within conn_timeout of its start
+        let wait = if started < addrs.len() { ATTEMPT_DELAY } else { conn_timeout + ATTEMPT_DELAY };
+        match receiver.recv_timeout(wait) {
+            Ok((_, Ok(stream))) => return Ok(stream),
+            Ok((index, Err(error))) => {
+                errors[index] = Some(error);
+                failed += 1;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) if started < addrs.len() => {}
+            Err(_) => break,
+        }
+        if started < addrs.len() {
+            start(started);
+            started += 1;
+        }
+    }
+    return Err(errors.into_iter().flatten().next().unwrap_or(io::Error::new(ErrorKind::TimedOut, "connection timed out")))
+}
+
+// Plain TCP to the addresses domain resolves to, tried as connect_any does in the order of the IP preference
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TcpConnector;
+
+impl Connector for TcpConnector {
+    fn connect(&self, domain: &str, port: u16, config: &RequestConfig) -> Result<Box<dyn Connection>, GopherError> {
+        let addrs = resolve(domain, port, config.ip)?;
+        let stream = connect_any(&addrs, config.conn_timeout)
+            .map_err(|error| { GopherError::from_connect(domain, port, error) })?;
+        return Ok(Box::new(stream))
+    }
+}
+
+// TCP through a SOCKS5 proxy (RFC 1928), the domain is sent to the proxy unresolved unless remote DNS is
+// turned off, with a username and password (RFC 1929) when credentials are given
+#[derive(Clone)]
+pub struct Socks5Connector {
+    proxy_domain: String,
+    proxy_port: u16,
+    credentials: Option<(String, String)>,
+    // the proxy resolves domains, otherwise they are resolved locally and the proxy is given the address
+    remote_dns: bool,
+    // connects to the proxy
+    inner: Transport,
+}
+
+impl Socks5Connector {
+    pub fn new(proxy_domain: &str, proxy_port: u16) -> Self {
+        return Self {
+            proxy_domain: String::from(proxy_domain),
+            proxy_port,
+            credentials: None,
+            remote_dns: true,
+            inner: Transport::tcp(),
+        }
+    }
+
+    pub fn from_proxy(proxy: &Proxy) -> Self {
+        let mut connector = Self::new(&proxy.domain, proxy.port);
+        connector.update_remote_dns(proxy.remote_dns);
+        if let Some((username, password)) = &proxy.credentials {
+            connector.update_credentials(username, password);
+        }
+        return connector
+    }
+
+    pub fn update_credentials(&mut self, username: &str, password: &str) -> &mut Self {
+        self.credentials = Some((String::from(username), String::from(password)));
+        return self
+    }
+
+    pub fn update_remote_dns(&mut self, remote_dns: bool) -> &mut Self {
+        self.remote_dns = remote_dns;
+        return self
+    }
+
+    pub fn update_inner(&mut self, inner: Transport) -> &mut Self {
+        self.inner = inner;
+        return self
+    }
+
+    // ask the proxy behind an open connection to connect on to (domain, port), for connections opened elsewhere,
+    // like the tokio streams of async requests
+    pub fn handshake_over(&self, proxy: &mut dyn Connection, domain: &str, port: u16, config: &RequestConfig) -> Result<(), GopherError> {
+        // the proxy is handed one address, the first in the order of the IP preference
+        let target = match self.remote_dns {
+            true => String::from(domain),
+            false => resolve(domain, port, config.ip)?[0].ip().to_string(),
+        };
+        return proxy.set_read_timeout(Some(config.conn_timeout))
+            .and_then(|_| { self.handshake(proxy, &target, port) })
+            .map_err(|source| { GopherError::Handshake { domain: String::from(domain), port, source } })
+    }
+
+    fn handshake(&self, proxy: &mut dyn Connection, domain: &str, port: u16) -> io::Result<()> {
+        let method: u8 = if self.credentials.is_some() { 0x02 } else { 0x00 };
+        proxy.write_all(&[0x05, 0x01, method])?;
+        let mut reply = [0u8; 2];
+        proxy.read_exact(&mut reply)?;
+        if reply[0] != 0x05 || reply[1] != method {
+            return Err(socks_error("proxy refused the authentication method"))
+        }
+
+        if let Some((username, password)) = &self.credentials {
+            if username.len() > 255 || password.len() > 255 {
+                return Err(io::Error::new(ErrorKind::InvalidInput, "SOCKS5 credentials longer than 255 bytes"))
+            }
+            let mut auth = vec![0x01, username.len() as u8];
+            auth.extend_from_slice(username.as_bytes());
+            auth.push(password.len() as u8);
+            auth.extend_from_slice(password.as_bytes());
+            proxy.write_all(&auth)?;
+            proxy.read_exact(&mut reply)?;
+            if reply[1] != 0x00 {
+                return Err(socks_error("proxy rejected the credentials"))
+            }
+        }
+
+        let mut connect = vec![0x05, 0x01, 0x00];
+        match domain.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => { connect.push(0x01); connect.extend_from_slice(&ip.octets()); }
+            Ok(IpAddr::V6(ip)) => { connect.push(0x04); connect.extend_from_slice(&ip.octets()); }
+            Err(_) if domain.len() <= 255 => {
+                connect.extend_from_slice(&[0x03, domain.len() as u8]);
+                connect.extend_from_slice(domain.as_bytes());
+            }
+            Err(_) => return Err(io::Error::new(ErrorKind::InvalidInput, "domain longer than 255 bytes")),
+        }
+        connect.extend_from_slice(&port.to_be_bytes());
+        proxy.write_all(&connect)?;
+
+        let mut head = [0u8; 4];
+        proxy.read_exact(&mut head)?;
+        if head[1] != 0x00 {
+            return Err(socks_reply_error(head[1]))
+        }
+        // the address the proxy bound is not needed
+        let bound_size = match head[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => {
+                let mut size = [0u8; 1];
+                proxy.read_exact(&mut size)?;
+                size[0] as usize
+            }
+            _ => return Err(socks_error("proxy replied with an unknown address type")),
+        };
+        let mut bound = vec![0u8; bound_size + 2];
+        return proxy.read_exact(&mut bound)
+    }
+}
+
+fn socks_error(message: &str) -> io::Error {
+    return io::Error::new(ErrorKind::ConnectionRefused, format!("SOCKS5: {}", message))
+}
+
+fn socks_reply_error(reply: u8) -> io::Error {
+    let message = match reply {
+        0x01 => "general failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    };
+    return socks_error(message)
+}
+
+impl Connector for Socks5Connector {
+    fn connect(&self, domain: &str, port: u16, config: &RequestConfig) -> Result<Box<dyn Connection>, GopherError> {
+        let mut proxy = self.inner.connect(&self.proxy_domain, self.proxy_port, config)?;
+        self.handshake_over(proxy.as_mut(), domain, port, config)?;
+        return Ok(proxy)
+    }
+}
+
+// (domain, port, request line without its line ending)
+type SessionKey = (String, u16, String);
+
+// Recorded sessions served from memory. A connection answers the request line written to it with the response
+// recorded for (domain, port, request line), a domain and port without sessions refuse the connection.
+// Every request line written is kept, in order.
+#[derive(Clone, Default)]
+pub struct MemoryConnector {
+    sessions: Arc<Mutex<HashMap<SessionKey, Vec<u8>>>>,
+    requests: Arc<Mutex<Vec<SessionKey>>>,
+}
+
+impl MemoryConnector {
+    pub fn new() -> Self {
+        return Self::default()
+    }
+
+    // response is sent for request_line, which is given without its line ending
+    pub fn session(&self, domain: &str, port: u16, request_line: &str, response: &[u8]) -> &Self {
+        self.sessions.lock().unwrap()
+            .insert((String::from(domain), port, String::from(request_line)), response.to_vec());
+        return self
+    }
+
+    // every request received
+    pub fn requests(&self) -> Vec<SessionKey> {
+        return self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Connector for MemoryConnector {
+    fn connect(&self, domain: &str, port: u16, _config: &RequestConfig) -> Result<Box<dyn Connection>, GopherError> {
+        let known = self.sessions.lock().unwrap().keys().any(|(d, p, _)| { d == domain && *p == port });
+        if !known {
+            return Err(GopherError::from_connect(domain, port, io::Error::from(ErrorKind::ConnectionRefused)))
+        }
+        return Ok(Box::new(MemoryConnection {
+            connector: self.clone(),
+            domain: String::from(domain),
+            port,
+            written: Vec::new(),
+            response: None,
+        }))
+    }
+}
+
+pub struct MemoryConnection {
+    connector: MemoryConnector,
+    domain: String,
+    port: u16,
+    written: Vec<u8>,
+    // picked once the request line is complete
+    response: Option<Cursor<Vec<u8>>>,
+}
+
+impl Read for MemoryConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.response.is_none() {
+            let Some(end) = self.written.iter().position(|byte| { *byte == b'\n' }) else {
+                return Err(io::Error::new(ErrorKind::WouldBlock, "no request line written"))
+            };
+            let line = String::from_utf8_lossy(&self.written[..end]).trim_end_matches('\r').to_string();
+            let key = (self.domain.clone(), self.port, line);
+            self.connector.requests.lock().unwrap().push(key.clone());
+            let response = self.connector.sessions.lock().unwrap().get(&key).cloned().ok_or_else(|| {
+                io::Error::new(ErrorKind::ConnectionReset, format!("no session recorded for {:?}", key.2))
+            })?;
+            self.response = Some(Cursor::new(response));
+        }
+        return self.response.as_mut().map_or(Ok(0), |response| { response.read(buf) })
+    }
+}
+
+impl Write for MemoryConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        return Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(())
+    }
+}
+
+impl Connection for MemoryConnection {
+    fn set_read_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        return Ok(())
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        return Ok(())
+    }
+}
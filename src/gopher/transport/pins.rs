@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+// Pin files
+// ======
+// Trust on first use. A pin file holds one line per server
+//     <host>:<port> <sha256 of its certificate>
+// lines starting with "#" are comments. A server missing from the file is pinned to the certificate it presents
+// the first time and the line appended, a server in the file has to present the same certificate from then on.
+// Pin files are read once per process and shared by every connection.
+
+fn loaded() -> &'static Mutex<HashMap<String, HashMap<String, String>>> {
+    static PIN_FILES: OnceLock<Mutex<HashMap<String, HashMap<String, String>>>> = OnceLock::new();
+    return PIN_FILES.get_or_init(|| { Mutex::new(HashMap::new()) })
+}
+
+fn read_pins(path: &str) -> Result<HashMap<String, String>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(format!("pin file {} unreadable: {}", path, error)),
+    };
+    return Ok(content.lines()
+        .map(|line| { line.trim() })
+        .filter(|line| { !line.is_empty() && !line.starts_with('#') })
+        .filter_map(|line| { line.split_once(char::is_whitespace) })
+        .map(|(host, fingerprint)| { (host.to_lowercase(), fingerprint.trim().to_lowercase()) })
+        .collect())
+}
+
+fn append_pin(path: &str, host: &str, fingerprint: &str) -> Result<(), String> {
+    if let Some(dir) = Path::new(path).parent().filter(|dir| { !dir.as_os_str().is_empty() }) {
+        fs::create_dir_all(dir).map_err(|error| { format!("pin file {} not writable: {}", path, error) })?;
+    }
+    return fs::OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut file| { writeln!(file, "{} {}", host, fingerprint) })
+        .map_err(|error| { format!("pin file {} not writable: {}", path, error) })
+}
+
+// Ok when host presented the certificate it is pinned to, or was not pinned and now is
+pub fn check(path: &str, host: &str, fingerprint: &str) -> Result<(), String> {
+    let mut pin_files = loaded().lock().unwrap();
+    if !pin_files.contains_key(path) {
+        pin_files.insert(String::from(path), read_pins(path)?);
+    }
+    let pins = pin_files.get_mut(path).unwrap();
+    let host = host.to_lowercase();
+
+    return match pins.get(&host) {
+        Some(pinned) if pinned == fingerprint => Ok(()),
+        Some(pinned) => Err(format!(
+            "certificate of {} changed, pinned sha256 {} but presented {}", host, pinned, fingerprint
+        )),
+        None => {
+            append_pin(path, &host, fingerprint)?;
+            pins.insert(host, String::from(fingerprint));
+            Ok(())
+        }
+    }
+}
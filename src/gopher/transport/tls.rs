@@ -0,0 +1,212 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::WebPkiSupportedAlgorithms;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, OtherError, RootCertStore, SignatureScheme, StreamOwned};
+use sha2::{Digest, Sha256};
+
+use super::{pins, Connection, Connector, Transport};
+use crate::gopher::config::{RequestConfig, TlsConfig};
+use crate::gopher::diff::hex;
+use crate::gopher::error::GopherError;
+
+fn webpki_roots() -> Arc<RootCertStore> {
+    static ROOTS: OnceLock<Arc<RootCertStore>> = OnceLock::new();
+    return ROOTS.get_or_init(|| {
+        return Arc::new(RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() })
+    }).clone()
+}
+
+// TLS over the connection of another connector, certificates are checked against
+// the webpki roots unless a rustls ClientConfig of its own is given
+#[derive(Clone)]
+pub struct TlsConnector {
+    config: Arc<ClientConfig>,
+    inner: Transport,
+}
+
+impl TlsConnector {
+    pub fn new() -> Self {
+        return Self::with_config(ClientConfig::builder().with_root_certificates(webpki_roots()).with_no_client_auth())
+    }
+
+    pub fn with_config(config: ClientConfig) -> Self {
+        return Self { config: Arc::new(config), inner: Transport::tcp() }
+    }
+
+    pub fn update_inner(&mut self, inner: Transport) -> &mut Self {
+        self.inner = inner;
+        return self
+    }
+}
+
+impl Default for TlsConnector {
+    fn default() -> Self {
+        return Self::new()
+    }
+}
+
+impl Connector for TlsConnector {
+    fn connect(&self, domain: &str, port: u16, config: &RequestConfig) -> Result<Box<dyn Connection>, GopherError> {
+        let sock = self.inner.connect(domain, port, config)?;
+        return handshake(self.config.clone(), sock, domain, port, config.conn_timeout)
+    }
+}
+
+// TLS over sock as the TLS settings of a request say, see gopher::transport
+pub fn secure(
+    sock: Box<dyn Connection>, tls: &TlsConfig, domain: &str, port: u16, conn_timeout: Duration,
+) -> Result<Box<dyn Connection>, GopherError> {
+    let host = if domain.contains(':') { format!("[{}]:{}", domain, port) } else { format!("{}:{}", domain, port) };
+    let verifier = PolicyVerifier {
+        webpki: if tls.verify { Some(webpki_verifier()?) } else { None },
+        pin_file: tls.pin_file.clone(),
+        host,
+        algorithms: rustls::crypto::ring::default_provider().signature_verification_algorithms,
+    };
+    let config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    return handshake(Arc::new(config), sock, domain, port, conn_timeout)
+}
+
+// true when the handshake failed on a server that does speak TLS, one that should not be retried in the clear
+pub fn speaks_tls(error: &io::Error) -> bool {
+    return matches!(
+        error.get_ref().and_then(|inner| { inner.downcast_ref::<rustls::Error>() }),
+        Some(rustls::Error::InvalidCertificate(_)
+            | rustls::Error::NoCertificatesPresented
+            | rustls::Error::AlertReceived(_)
+            | rustls::Error::PeerIncompatible(_)
+            | rustls::Error::PeerMisbehaved(_))
+    )
+}
+
+fn webpki_verifier() -> Result<Arc<WebPkiServerVerifier>, GopherError> {
+    static VERIFIER: OnceLock<Arc<WebPkiServerVerifier>> = OnceLock::new();
+    if let Some(verifier) = VERIFIER.get() {
+        return Ok(verifier.clone())
+    }
+    let verifier = WebPkiServerVerifier::builder(webpki_roots()).build()
+        .map_err(|error| { GopherError::parse(&format!("TLS roots unusable: {}", error)) })?;
+    return Ok(VERIFIER.get_or_init(|| { verifier }).clone())
+}
+
+fn handshake(
+    config: Arc<ClientConfig>, sock: Box<dyn Connection>, domain: &str, port: u16, conn_timeout: Duration,
+) -> Result<Box<dyn Connection>, GopherError> {
+    let handshake_error = |source| { GopherError::Handshake { domain: String::from(domain), port, source } };
+    let server_name = ServerName::try_from(domain.trim_start_matches('[').trim_end_matches(']').to_string())
+        .map_err(|error| { handshake_error(io::Error::new(ErrorKind::InvalidInput, error)) })?;
+    let session = ClientConnection::new(config, server_name)
+        .map_err(|error| { handshake_error(io::Error::other(error)) })?;
+
+    sock.set_read_timeout(Some(conn_timeout)).map_err(handshake_error)?;
+    let mut stream = StreamOwned::new(session, sock);
+    // finish the handshake here so certificate errors are told apart from read errors
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock).map_err(handshake_error)?;
+    }
+    return Ok(Box::new(TlsConnection { stream }))
+}
+
+// Checks the certificate chain against the webpki roots when set, then the certificate against its pin when
+// a pin file is set, and takes any certificate when neither is. Handshake signatures are always checked.
+#[derive(Debug)]
+struct PolicyVerifier {
+    webpki: Option<Arc<WebPkiServerVerifier>>,
+    pin_file: Option<String>,
+    // "host:port" the certificate is pinned for
+    host: String,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for PolicyVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(webpki) = &self.webpki {
+            webpki.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        }
+        if let Some(pin_file) = &self.pin_file {
+            pins::check(pin_file, &self.host, &hex(&Sha256::digest(end_entity))).map_err(|message| {
+                return rustls::Error::InvalidCertificate(CertificateError::Other(OtherError(Arc::new(io::Error::other(message)))))
+            })?;
+        }
+        return Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        return rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        return rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        return self.algorithms.supported_schemes()
+    }
+}
+
+pub struct TlsConnection {
+    stream: StreamOwned<ClientConnection, Box<dyn Connection>>,
+}
+
+impl TlsConnection {
+    // certificates the server presented, its own first
+    pub fn peer_certificates(&self) -> Vec<Vec<u8>> {
+        return self.stream.conn.peer_certificates()
+            .map(|certs| { certs.iter().map(|cert| { cert.to_vec() }).collect() })
+            .unwrap_or_default()
+    }
+}
+
+impl Read for TlsConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        return match self.stream.read(buf) {
+            // plenty of gopher servers close without a close_notify, the body ends with the connection either way
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => Ok(0),
+            read => read,
+        }
+    }
+}
+
+impl Write for TlsConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        return self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return self.stream.flush()
+    }
+}
+
+impl Connection for TlsConnection {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        return self.stream.sock.set_read_timeout(timeout)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        return self.stream.sock.shutdown()
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        return self.stream.sock.peer_addr()
+    }
+}
@@ -1,65 +1,287 @@
 use regex::Regex;
-use std::{fmt::{Display, Debug}, path::PathBuf, cmp::Ordering};
+use std::{fmt::{Display, Debug}, cmp::Ordering, hash::{Hash, Hasher}, sync::OnceLock};
+
+use super::plus::PlusAttributes;
+use super::url::GopherUrl;
 
 // Item types
 // ======
 // Info
 //     i => information text
-// Binaries
+// Text
 //     0 => plain text
+//     c => calendar (iCalendar)
+//     h => HTML document (or a web link when selector starts with "URL:")
+//     M => MIME encoded message
+//     r => rich text (RTF)
+//     X => XML document
+// Binaries
 //     4 => BinHexed Macintosh file
 //     5 => DOS binary
 //     6 => UNIX uuencoded file
 //     9 => generic binary
+//     d => document (doc, pdf, ...)
 //     g => .gif image
 //     I => generic image
+//     p => .png image
+//     P => .pdf document
 //     s => .wav sound file
+//     : => bitmap image (gopher+)
+//     ; => video (gopher+)
+//     < => sound (gopher+)
 // Directories
 //     1 => directory/menu
 //     7 => search result
+// Sessions
+//     2 => CSO phone-book server
+//     8 => telnet session
+//     T => tn3270 session
+// Links
+//     + => redundant server (mirror of the previous item)
 // Error
 //     3 => error
+// With the serde feature an item kind is written as its tag above, a one char string ("0", "1", "i", ...),
+// so these tags are the serialised schema and stay the same whatever the variants are named.
+
+// Broad category of an item kind, used by the crawler and downloader to decide
+// what to do with an item instead of matching on tag chars
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ItemClass {
+    Info,
+    Error,
+    Menu,
+    Search,
+    Text,
+    Binary,
+    Session,
+    Link,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ItemKind {
+    Text,
+    Menu,
+    Cso,
+    Error,
+    BinHex,
+    Dos,
+    Uuencoded,
+    Search,
+    Telnet,
+    Binary,
+    Redundant,
+    Tn3270,
+    Gif,
+    Image,
+    Html,
+    Info,
+    Sound,
+    Document,
+    Png,
+    Pdf,
+    Rtf,
+    Xml,
+    Mime,
+    Calendar,
+    Bitmap,
+    Video,
+    PlusSound,
+}
+
+impl ItemKind {
+    pub const ALL: [ItemKind; 27] = [
+        Self::Text, Self::Menu, Self::Cso, Self::Error, Self::BinHex, Self::Dos,
+        Self::Uuencoded, Self::Search, Self::Telnet, Self::Binary, Self::Redundant,
+        Self::Tn3270, Self::Gif, Self::Image, Self::Html, Self::Info, Self::Sound,
+        Self::Document, Self::Png, Self::Pdf, Self::Rtf, Self::Xml, Self::Mime,
+        Self::Calendar, Self::Bitmap, Self::Video, Self::PlusSound,
+    ];
+
+    pub fn from_tag(tag: char) -> Option<Self> {
+        return Self::ALL.iter().find(|kind| { kind.tag() == tag }).copied()
+    }
+
+    // the first char of a menu line for this kind
+    pub fn tag(&self) -> char {
+        match self {
+            Self::Text => '0',
+            Self::Menu => '1',
+            Self::Cso => '2',
+            Self::Error => '3',
+            Self::BinHex => '4',
+            Self::Dos => '5',
+            Self::Uuencoded => '6',
+            Self::Search => '7',
+            Self::Telnet => '8',
+            Self::Binary => '9',
+            Self::Redundant => '+',
+            Self::Tn3270 => 'T',
+            Self::Gif => 'g',
+            Self::Image => 'I',
+            Self::Html => 'h',
+            Self::Info => 'i',
+            Self::Sound => 's',
+            Self::Document => 'd',
+            Self::Png => 'p',
+            Self::Pdf => 'P',
+            Self::Rtf => 'r',
+            Self::Xml => 'X',
+            Self::Mime => 'M',
+            Self::Calendar => 'c',
+            Self::Bitmap => ':',
+            Self::Video => ';',
+            Self::PlusSound => '<',
+        }
+    }
+
+    pub fn class(&self) -> ItemClass {
+        match self {
+            Self::Info => ItemClass::Info,
+            Self::Error => ItemClass::Error,
+            Self::Menu => ItemClass::Menu,
+            Self::Search => ItemClass::Search,
+            Self::Text | Self::Html | Self::Rtf | Self::Xml | Self::Mime | Self::Calendar
+                => ItemClass::Text,
+            Self::Cso | Self::Telnet | Self::Tn3270 => ItemClass::Session,
+            Self::Redundant => ItemClass::Link,
+            Self::BinHex | Self::Dos | Self::Uuencoded | Self::Binary | Self::Gif | Self::Image
+                | Self::Sound | Self::Document | Self::Png | Self::Pdf | Self::Bitmap
+                | Self::Video | Self::PlusSound
+                => ItemClass::Binary,
+        }
+    }
+
+    // info and error lines carry a message rather than a fetchable selector
+    pub fn is_info(&self) -> bool { self.class() == ItemClass::Info || self.class() == ItemClass::Error }
+    pub fn is_menu(&self) -> bool { self.class() == ItemClass::Menu || self.class() == ItemClass::Search }
+    pub fn is_text(&self) -> bool { self.class() == ItemClass::Text }
+    pub fn is_binary(&self) -> bool { self.class() == ItemClass::Binary }
+    pub fn is_session(&self) -> bool { self.class() == ItemClass::Session }
+    pub fn is_link(&self) -> bool { self.class() == ItemClass::Link }
+
+    // whether the content behind the item can be saved to a local file
+    pub fn is_downloadable(&self) -> bool { self.is_text() || self.is_binary() }
+}
+
+impl Display for ItemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.tag())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ItemKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_char(self.tag())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ItemKind {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let tag = char::deserialize(deserializer)?;
+        return Self::from_tag(tag).ok_or_else(|| { serde::de::Error::custom(format!("unknown item type: {:?}", tag)) })
+    }
+}
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Referer {
     pub port: u16,
     pub path: String,
     pub domain: String,
+    // search terms when the menu is the result of a search item
+    pub query: Option<String>,
+    // menus between the scan root and this menu, set on the referers of a scan,
+    // not taken into account when comparing referers
+    pub depth: Option<usize>,
 }
 
 impl Referer {
     pub fn new(domain: &str, port: u16, path: &str) -> Self {
         return Self {
-            port: port,
+            port,
             path: String::from(path),
             domain: String::from(domain),
+            query: None,
+            depth: None,
         }
     }
+
+    pub fn from_url(url: &GopherUrl) -> Self {
+        let mut referer = Self::new(&url.domain, url.port, &url.selector);
+        referer.update_query(url.search.as_deref());
+        return referer
+    }
+
+    pub fn update_query(&mut self, query: Option<&str>) -> &mut Self {
+        self.query = query.map(String::from);
+        return self
+    }
+
+    pub fn update_depth(&mut self, depth: Option<usize>) -> &mut Self {
+        self.depth = depth;
+        return self
+    }
+
+    // referers are always menus, or search results when queried
+    pub fn url(&self) -> GopherUrl {
+        let mut url = GopherUrl::new(&self.domain, self.port);
+        url.update_selector(&self.path).update_search(self.query.as_deref());
+        if self.query.is_some() {
+            url.update_kind(ItemKind::Search);
+        }
+        return url
+    }
+}
+
+impl PartialEq for Referer {
+    fn eq(&self, other: &Self) -> bool {
+        return (self.port == other.port) && (self.domain == other.domain) 
+            && (self.path == other.path) && (self.query == other.query)
+    }
+}
+
+impl Eq for Referer {}
+
+impl Hash for Referer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.port.hash(state);
+        self.domain.hash(state);
+        self.path.hash(state);
+        self.query.hash(state);
+    }
 }
 
 impl Display for Referer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}{}", self.domain, self.port, self.path)
+        write!(f, "{}", self.url())
     }
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "item", rename_all = "lowercase"))]
 pub enum Item {
     INFO {
-        tag: char,
+        kind: ItemKind,
         from: Referer,
         message: String,
         domain: String, 
         port: u16,
     },
     DATA {
-        tag: char,
+        kind: ItemKind,
         size: u64,
         caption: String,
         referer: Referer,
         location: String,
         domain: String,
         port: u16,
+        // Some for gopher+ items, filled in once their attributes are fetched
+        plus: Option<Box<PlusAttributes>>,
     },
     UNKNOWN {
         from: Referer,
@@ -69,67 +291,160 @@ pub enum Item {
 
 impl Item {
     pub fn from_str(from: &Referer, unparsed: &str) -> Result<Self, String> {
-        if unparsed.len() == 0 {
-            // return Err("Parse error: Must not be an empty string!\n".to_string())
-            return Ok(Item::UNKNOWN {
-                from: from.clone(), 
-                unparsed: String::from(unparsed)
-            })
-        }
+        let unknown = Item::UNKNOWN {
+            from: from.clone(), 
+            unparsed: String::from(unparsed)
+        };
 
-        let fst_char = unparsed.chars().collect::<Vec<char>>()[0];
+        let fst_char = if let Some(fst_char) = unparsed.chars().next() { fst_char } else {
+            // return Err("Parse error: Must not be an empty string!\n".to_string())
+            return Ok(unknown)
+        };
 
-        let matcher = Regex::new("^(.*?)\t([^\t]*)\t([^\t/]*)\t([0-9]*)$").unwrap();
+        // a fifth field of "+" or "?" marks a gopher+ item
+        // compiled once, every line of every menu goes through it
+        static MATCHER: OnceLock<Regex> = OnceLock::new();
+        let matcher = MATCHER.get_or_init(|| { Regex::new("^(.*?)\t([^\t]*)\t([^\t/]*)\t([0-9]*)(?:\t([^\t]*))?$").unwrap() });
 
-        if let Some(captured) 
-            = matcher.captures(&unparsed[1..unparsed.len()])
+        if let (Some(kind), Some(captured)) 
+            = (ItemKind::from_tag(fst_char), matcher.captures(&unparsed[fst_char.len_utf8()..]))
         {
-            match fst_char {
-                'i' | '3' => {
-                    if let Ok(port) = String::from(captured.get(4).unwrap().as_str()).parse::<u16>() {
-                        return Ok(Self::INFO {
-                            tag: fst_char,
-                            from: from.clone(),
-                            message: String::from(captured.get(1).unwrap().as_str()),
-                            port: port,
-                            domain: String::from(captured.get(3).unwrap().as_str()),
-                        })
-                    } else {
-                        return Err("Unknown regex error on INFO!".to_string())
-                    }
-                }
-                '0' | '1' | '4' | '5' | '7' | '9' | 'g' | 'I' | 's' => {
-                    if let Ok(port) = String::from(captured.get(4).unwrap().as_str()).parse::<u16>() {
-                        return Ok(Self::DATA {
-                            tag: fst_char,
-                            size: 0,
-                            caption: String::from(captured.get(1).unwrap().as_str()),
-                            referer: from.clone(),
-                            location: String::from(captured.get(2).unwrap().as_str()),
-                            domain: String::from(captured.get(3).unwrap().as_str()),
-                            port: port,
-                        })
-                    } else {
-                        return Err("Unknown regex error on DATA!".to_string())
-                    }   
-                }
-                _ => {
-                    return Ok(Item::UNKNOWN {
-                        from: from.clone(), 
-                        unparsed: String::from(unparsed)
-                    })
-                }
+            // a line with an empty or out of range port is kept as it is, like any other line that is no item
+            let Ok(port) = captured.get(4).unwrap().as_str().parse::<u16>() else {
+                return Ok(unknown)
+            };
+
+            if kind.is_info() {
+                return Ok(Self::INFO {
+                    kind,
+                    from: from.clone(),
+                    message: String::from(captured.get(1).unwrap().as_str()),
+                    port,
+                    domain: String::from(captured.get(3).unwrap().as_str()),
+                })
+            } else {
+                return Ok(Self::DATA {
+                    kind,
+                    size: 0,
+                    caption: String::from(captured.get(1).unwrap().as_str()),
+                    referer: from.clone(),
+                    location: String::from(captured.get(2).unwrap().as_str()),
+                    domain: String::from(captured.get(3).unwrap().as_str()),
+                    port,
+                    plus: match captured.get(5).map(|plus| { plus.as_str().trim() }) {
+                        Some("+" | "?") => Some(Box::default()),
+                        _ => None,
+                    },
+                })
+            }
+        } else {
+            return Ok(unknown)
+        }
+    }
+
+    // the menu line the item is parsed from, without the line ending
+    pub fn menu_line(&self) -> String {
+        match self {
+            Self::INFO { kind, message, domain, port, .. } => {
+                return format!("{}{}\t\t{}\t{}", kind.tag(), message, domain, port)
             }
+            Self::DATA { kind, caption, location, domain, port, plus, .. } => {
+                return format!("{}{}\t{}\t{}\t{}{}", 
+                    kind.tag(), caption, location, domain, port,
+                    if plus.is_some() { "\t+" } else { "" }
+                )
+            }
+            Self::UNKNOWN { unparsed, .. } => {
+                return unparsed.clone()
+            }
+        }
+    }
+
+    // item pointing at url, listed on the menu at from
+    pub fn from_url(from: &Referer, url: &GopherUrl, caption: &str) -> Self {
+        if url.kind.is_info() {
+            return Self::INFO {
+                kind: url.kind,
+                from: from.clone(),
+                message: String::from(caption),
+                domain: url.domain.clone(),
+                port: url.port,
+            }
+        } else {
+            return Self::DATA {
+                kind: url.kind,
+                size: 0,
+                caption: String::from(caption),
+                referer: from.clone(),
+                location: url.selector.clone(),
+                domain: url.domain.clone(),
+                port: url.port,
+                plus: None,
+            }
+        }
+    }
+
+    // destination of the item, info and unknown items lead nowhere
+    pub fn url(&self) -> Option<GopherUrl> {
+        if let Self::DATA { kind, location, domain, port, .. } = self {
+            let mut url = GopherUrl::new(domain, *port);
+            url.update_kind(*kind).update_selector(location);
+            return Some(url)
+        } else {
+            return None
+        }
+    }
+
+    pub fn kind(&self) -> Option<ItemKind> {
+        match self {
+            Self::INFO { kind, .. } | Self::DATA { kind, .. } => Some(*kind),
+            Self::UNKNOWN { .. } => None,
+        }
+    }
+
+    // class of the item, "URL:" selectors are links whatever their kind says
+    pub fn class(&self) -> Option<ItemClass> {
+        match self {
+            Self::DATA { location, .. } if location.starts_with("URL:") => Some(ItemClass::Link),
+            _ => self.kind().map(|kind| { kind.class() }),
+        }
+    }
+
+    pub fn is_menu(&self) -> bool {
+        return matches!(self.class(), Some(ItemClass::Menu | ItemClass::Search))
+    }
+
+    pub fn is_downloadable(&self) -> bool {
+        return matches!(self.class(), Some(ItemClass::Text | ItemClass::Binary))
+    }
+
+    pub fn is_plus(&self) -> bool {
+        return matches!(self, Self::DATA { plus: Some(_), .. })
+    }
+
+    pub fn attributes(&self) -> Option<&PlusAttributes> {
+        if let Self::DATA { plus: Some(attributes), .. } = self {
+            return Some(attributes)
         } else {
-            return Ok(Item::UNKNOWN {
-                from: from.clone(), 
-                unparsed: String::from(unparsed)
-            })
+            return None
         }
     }
 
+    // attach gopher+ attributes, marking the item as a gopher+ item
+    pub fn update_attributes(&mut self, attributes: PlusAttributes) -> &mut Self {
+        if let Self::DATA { ref mut plus, .. } = self {
+            *plus = Some(Box::new(attributes));
+        }
+        return self
+    }
+
+    // MIME type of the default gopher+ view
+    pub fn mime(&self) -> Option<&str> {
+        return self.attributes().and_then(|attributes| { attributes.mime() })
+    }
+
     pub fn get_size(&self) -> Option<u64> {
-        if let Self::DATA { tag, size, caption, referer, location, domain, port } = self {
+        if let Self::DATA { size, .. } = self {
             return Some(*size)
         } else {
             return None
@@ -137,7 +452,7 @@ impl Item {
     }
 
     pub fn update_size(&mut self, new_size: u64) -> &mut Self {
-        if let Self::DATA { tag, ref mut size, caption, referer, location, domain, port } = self {
+        if let Self::DATA { ref mut size, .. } = self {
             *size = new_size;
         }
         return self
@@ -148,15 +463,15 @@ impl PartialEq for Item {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (
-                Self::INFO { tag: t1, from: f1, message: m1, domain: d1, port: p1 }, 
-                Self::INFO { tag: t2, from: f2, message: m2, domain: d2, port: p2 }
+                Self::INFO { kind: k1, from: f1, message: m1, domain: d1, port: p1 }, 
+                Self::INFO { kind: k2, from: f2, message: m2, domain: d2, port: p2 }
             ) => {
-                return (t1 == t2)  && (p1 == p2) && (f1 == f2) && (d1 == d2) && (m1 == m2)
+                return (k1 == k2)  && (p1 == p2) && (f1 == f2) && (d1 == d2) && (m1 == m2)
             }
 
             (
-                Self::DATA { tag: t1, size: _, caption: c1, referer: r1, location: l1, domain: d1, port: p1 },
-                Self::DATA { tag: t2, size: _, caption: c2, referer: r2, location: l2, domain: d2, port: p2 },
+                Self::DATA { location: l1, domain: d1, port: p1, .. },
+                Self::DATA { location: l2, domain: d2, port: p2, .. },
             ) => {
                 return (p1 == p2) && (d1 == d2) && (l1 == l2) 
             }
@@ -174,17 +489,21 @@ impl PartialEq for Item {
 impl Display for Item {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::INFO { tag, from, message, domain, port } => {
+            Self::INFO { kind, from, message, domain, port } => {
                 write!(f, 
                     "==INFO: type {}==\nFrom: {}\nMessage: {}\nDomain: {}\nPort: {}\n", 
-                    tag, from, message, domain, port
+                    kind, from, message, domain, port
                 )
             }
-            Self::DATA { tag, size, caption, referer, location, domain, port } => {
+            Self::DATA { kind, size, caption, location, domain, port, .. } => {
                 write!(f, 
-                    "==DATA: type {}==\nCaption: {}\nLocation: {}\nSize: {}\nDomain: {}\nPort: {}\n", 
-                    tag, caption, location, size, domain, port
-                )
+                    "==DATA: type {}==\nCaption: {}\nLocation: {}\nSize: {}\nDomain: {}\nPort: {}\nURL: {}\n", 
+                    kind, caption, location, size, domain, port, self.url().unwrap()
+                )?;
+                if let Some(mime) = self.mime() {
+                    writeln!(f, "MIME: {}", mime)?;
+                }
+                return Ok(())
             }
             Self::UNKNOWN { from, unparsed } => {
                 write!(f, "==Unknown==\nFrom: {}\nUnparsed content:\n{}\n", from, unparsed)
@@ -196,17 +515,21 @@ impl Display for Item {
 impl Debug for Item {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::INFO { tag, port, from, domain, message } => {
+            Self::INFO { kind, port, from, domain, message } => {
                 write!(f, 
                     "==INFO: type {}==\nFrom: {}\nMessage: {}\nDomain: {}\nPort: {}\n", 
-                    tag, from, message, domain, port
+                    kind, from, message, domain, port
                 )
             }
-            Self::DATA { tag, size, caption, referer, location, domain, port } => {
+            Self::DATA { kind, size, caption, location, domain, port, .. } => {
                 write!(f, 
-                    "==DATA: type {}==\nCaption: {}\nLocation: {}\nSize: {}\nDomain: {}\nPort: {}\n", 
-                    tag, caption, location, size, domain, port
-                )
+                    "==DATA: type {}==\nCaption: {}\nLocation: {}\nSize: {}\nDomain: {}\nPort: {}\nURL: {}\n", 
+                    kind, caption, location, size, domain, port, self.url().unwrap()
+                )?;
+                if let Some(mime) = self.mime() {
+                    writeln!(f, "MIME: {}", mime)?;
+                }
+                return Ok(())
             }
             Self::UNKNOWN { from, unparsed } => {
                 write!(f, "==Unknown==\nFrom: {}\nUnparsed content:\n{}\n", from, unparsed)
@@ -217,26 +540,49 @@ impl Debug for Item {
 
 impl PartialOrd for Item {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        if let (Some(self_size), Some(other_size)) = (self.get_size(), other.get_size()) {
-            return self_size.partial_cmp(&other_size)
-        } else {
-            return None
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Eq for Item {}
 
+// hashes the same fields PartialEq compares, so items can be deduplicated in a HashSet
+impl Hash for Item {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Self::INFO { kind, from, message, domain, port } => {
+                0u8.hash(state);
+                kind.hash(state);
+                from.hash(state);
+                message.hash(state);
+                domain.hash(state);
+                port.hash(state);
+            }
+            Self::DATA { location, domain, port, .. } => {
+                1u8.hash(state);
+                location.hash(state);
+                domain.hash(state);
+                port.hash(state);
+            }
+            Self::UNKNOWN { from, unparsed } => {
+                2u8.hash(state);
+                from.hash(state);
+                unparsed.hash(state);
+            }
+        }
+    }
+}
+
 impl Ord for Item {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         if let (Some(self_size), Some(other_size)) = (self.get_size(), other.get_size()) {
             return self_size.cmp(&other_size)
         } else {
             match (self, other) {
-                (Item::DATA { tag, size, caption, referer, location, domain, port }, _) => {
+                (Item::DATA { .. }, _) => {
                     return Ordering::Greater;
                 }
-                (_, Self::DATA { tag, size, caption, referer, location, domain, port }) => {
+                (_, Self::DATA { .. }) => {
                     return Ordering::Less;
                 }
                 _ => { return Ordering::Equal; }
@@ -245,6 +591,7 @@ impl Ord for Item {
     }
 }
 
+
 // #[derive(Clone)]
 // pub struct Error {
 //     id: u16,
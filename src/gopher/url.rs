@@ -0,0 +1,203 @@
+use std::{fmt::{self, Display}, str::FromStr};
+
+use super::error::GopherError;
+use super::types::ItemKind;
+
+pub const DEFAULT_PORT: u16 = 70;
+
+// A gopher URL as described in RFC 4266
+//     gopher://<host>:<port>/<gophertype><selector>%09<search>%09<gopher+_string>
+// or gophers:// for a server connected to over TLS.
+// The domain is kept without brackets, IPv6 literals are bracketed again when formatted
+#[derive(Clone, Debug, PartialEq)]
+pub struct GopherUrl {
+    pub domain: String,
+    pub port: u16,
+    pub kind: ItemKind,
+    pub selector: String,
+    pub search: Option<String>,
+    // gophers://
+    pub tls: bool,
+}
+
+impl GopherUrl {
+    // url to the root menu of a server
+    pub fn new(domain: &str, port: u16) -> Self {
+        return Self {
+            domain: String::from(domain),
+            port,
+            kind: ItemKind::Menu,
+            selector: String::new(),
+            search: None,
+            tls: false,
+        }
+    }
+
+    // parse "gopher://host:port/Tselector%09search", its gophers:// form or a bare "host[:port][/Tselector]",
+    // a missing port defaults to 70 and a missing path to the root menu
+    pub fn parse(url: &str) -> Result<Self, GopherError> {
+        let url = url.trim();
+        let tls = url.get(..10).is_some_and(|scheme| { scheme.eq_ignore_ascii_case("gophers://") });
+        let rest = if url.get(..9).is_some_and(|scheme| { scheme.eq_ignore_ascii_case("gopher://") }) {
+            &url[9..]
+        } else if tls {
+            &url[10..]
+        } else if url.contains("://") {
+            return Err(GopherError::Parse { message: format!("Unsupported URL scheme: {}", url) })
+        } else {
+            url
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => (rest, ""),
+        };
+        let (domain, port) = split_authority(authority)?;
+
+        let mut url = Self::new(&domain, port);
+        url.tls = tls;
+        let mut path_chars = path.chars();
+        if let Some(tag) = path_chars.next() {
+            url.kind = ItemKind::from_tag(tag).ok_or(GopherError::Parse {
+                message: format!("Unknown item type in URL: {}", tag)
+            })?;
+            let decoded = percent_decode(path_chars.as_str())?;
+            let mut fields = decoded.split('\t');
+            url.selector = String::from(fields.next().unwrap_or_default());
+            url.search = fields.next().map(String::from);
+        }
+        return Ok(url)
+    }
+
+    pub fn update_kind(&mut self, kind: ItemKind) -> &mut Self {
+        self.kind = kind;
+        return self
+    }
+
+    pub fn update_selector(&mut self, selector: &str) -> &mut Self {
+        self.selector = String::from(selector);
+        return self
+    }
+
+    pub fn update_search(&mut self, search: Option<&str>) -> &mut Self {
+        self.search = search.map(String::from);
+        return self
+    }
+
+    pub fn update_tls(&mut self, tls: bool) -> &mut Self {
+        self.tls = tls;
+        return self
+    }
+
+    // host part of the url, IPv6 literals in brackets
+    pub fn host(&self) -> String {
+        if self.domain.contains(':') {
+            return format!("[{}]", self.domain)
+        } else {
+            return self.domain.clone()
+        }
+    }
+}
+
+impl Display for GopherUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", if self.tls { "gophers" } else { "gopher" }, self.host())?;
+        if self.port != DEFAULT_PORT {
+            write!(f, ":{}", self.port)?;
+        }
+        write!(f, "/{}{}", self.kind.tag(), percent_encode(&self.selector))?;
+        if let Some(search) = &self.search {
+            write!(f, "%09{}", percent_encode(search))?;
+        }
+        return Ok(())
+    }
+}
+
+impl FromStr for GopherUrl {
+    type Err = GopherError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return Self::parse(s)
+    }
+}
+
+// with the serde feature a URL is written in its canonical string form
+#[cfg(feature = "serde")]
+impl serde::Serialize for GopherUrl {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for GopherUrl {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let url = String::deserialize(deserializer)?;
+        return Self::parse(&url).map_err(serde::de::Error::custom)
+    }
+}
+
+// split "host", "host:port", "[v6]" or "[v6]:port", a bare IPv6 literal is taken as the host
+fn split_authority(authority: &str) -> Result<(String, u16), GopherError> {
+    let parse_error = || { GopherError::Parse { message: format!("Invalid host: {}", authority) } };
+
+    let (domain, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+        let end = bracketed.find(']').ok_or_else(parse_error)?;
+        let port = match &bracketed[end + 1..] {
+            "" => None,
+            rest => Some(rest.strip_prefix(':').ok_or_else(parse_error)?),
+        };
+        (&bracketed[..end], port)
+    } else if authority.matches(':').count() > 1 {
+        (authority, None)
+    } else {
+        match authority.split_once(':') {
+            Some((domain, port)) => (domain, Some(port)),
+            None => (authority, None),
+        }
+    };
+
+    if domain.is_empty() {
+        return Err(parse_error())
+    }
+    let port = match port {
+        None | Some("") => DEFAULT_PORT,
+        Some(port) => port.parse::<u16>().map_err(|_| { parse_error() })?,
+    };
+    return Ok((String::from(domain), port))
+}
+
+pub fn percent_decode(encoded: &str) -> Result<String, GopherError> {
+    let bytes = encoded.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = encoded.get(i + 1..i + 3)
+                .and_then(|hex| { u8::from_str_radix(hex, 16).ok() })
+                .ok_or(GopherError::Parse { message: format!("Invalid percent encoding: {}", encoded) })?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    return String::from_utf8(decoded)
+        .map_err(|error| { GopherError::NonUtf8 { source: error.utf8_error() } })
+}
+
+// encode everything but unreserved chars and the path delimiters of RFC 3986
+pub fn percent_encode(decoded: &str) -> String {
+    let mut encoded = String::with_capacity(decoded.len());
+    for byte in decoded.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9'
+                | b'-' | b'.' | b'_' | b'~' | b'/' | b':' | b'@' | b'!' | b'$'
+                | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'='
+                => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    return encoded
+}
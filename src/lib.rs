@@ -0,0 +1,5 @@
+// explicit returns and upper-case item variants are house style
+#![allow(clippy::needless_return, clippy::upper_case_acronyms)]
+
+pub mod log;
+pub mod gopher;
@@ -3,18 +3,16 @@ use lazy_static::lazy_static;
 use path_clean::clean;
 use std::{sync::Mutex, fs, io::Write};
 
-use crate::cli::CLI_ARGS;
-
 lazy_static! {
     pub static ref BUFF: Mutex<String> = Mutex::new(format!("Boot time: {}\n", 
-        Utc::now().format("%Y-%m-%d %H:%M:%S %Z").to_string()
+        Utc::now().format("%Y-%m-%d %H:%M:%S %Z")
     ));
 }
 
 #[macro_export]
 macro_rules! add_log {
     ($($arg:tt)*) => {{
-        use crate::log::BUFF;
+        use $crate::log::BUFF;
         *BUFF.lock().unwrap() = format!("{}\n{}", 
             *BUFF.lock().unwrap(),
             format!($($arg)*)
@@ -27,15 +25,15 @@ pub fn wipe_log() {
 }
 
 // write BUFF to a local file 
-// (File path prefix specified in CLI_ARGS.log_path_prefix)
-pub fn produce() {
+// (File path prefix specified by log_path_prefix, nothing is written when None)
+pub fn produce(log_path_prefix: Option<&str>) {
     add_log!("End time: {}\n", 
-        Utc::now().format("%Y-%m-%d %H:%M:%S %Z").to_string()
+        Utc::now().format("%Y-%m-%d %H:%M:%S %Z")
     );
-    if let Some(ref path_prefix) = CLI_ARGS.log_path_prefix {
+    if let Some(path_prefix) = log_path_prefix {
         let full_path = clean(format!("{}/log.txt", path_prefix));
         if let (Ok(()), Ok(mut file)) = 
-            (fs::create_dir_all(&path_prefix), fs::File::create(&full_path)) 
+            (fs::create_dir_all(path_prefix), fs::File::create(&full_path)) 
         {
             println!("===Log file saved===\nLocation: {}", &full_path.to_str().unwrap());
             file.write_all(BUFF.lock().unwrap().as_bytes()).unwrap();
@@ -45,6 +43,6 @@ pub fn produce() {
     }
     // wipe_log();
     *BUFF.lock().unwrap() = format!("Boot time: {}\n", 
-        Utc::now().format("%Y-%m-%d %H:%M:%S %Z").to_string()
+        Utc::now().format("%Y-%m-%d %H:%M:%S %Z")
     );
 }
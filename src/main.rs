@@ -1,29 +1,203 @@
-use regex::Regex;
-use path_clean::clean;
-use std::path::PathBuf;
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+use std::time::Duration;
 
 mod cli;
-mod log;
-mod gopher;
 
-use crate::log::produce; 
-use crate::cli::CLI_ARGS;
-use crate::gopher::Client;
+use simple_gopher_in_rust::log::produce; 
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::config::{
+    ClientConfig, HostPoliteness, PolitenessConfig, Proxy, ProxyConfig, RetryPolicy, TlsConfig, TlsMode,
+};
+use simple_gopher_in_rust::gopher::error::GopherError;
+use simple_gopher_in_rust::gopher::export;
+use simple_gopher_in_rust::gopher::server::Server;
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use crate::cli::{CliArgs, Command, ServeArgs, CLI_ARGS};
 
-fn main() {
-    let host_matcher = Regex::new("^(.*?):*([0-9]*)$").unwrap();
+// --TLS_HOST host[:port][=mode]
+fn tls_host(arg: &str) -> Result<(&str, TlsMode), String> {
+    return match arg.rsplit_once('=') {
+        Some((host, mode)) => Ok((host, mode.parse::<TlsMode>()?)),
+        None => Ok((arg, TlsMode::On)),
+    }
+}
+
+// TLS settings of the arguments on top of those of the client, which already has the server of a gophers:// URL on
+fn tls_config(args: &CliArgs, client: &Client) -> Result<TlsConfig, String> {
+    let mut tls = client.config.request.tls.clone();
+    tls.update_mode(args.tls).update_verify(!args.insecure).update_pin_file(args.pin_file.as_deref());
+    for arg in &args.tls_hosts {
+        let (host, mode) = tls_host(arg)?;
+        tls.update_host(host, mode);
+    }
+    return Ok(tls)
+}
+
+// --PROXY_HOST host[:port]=socks5h://...|direct
+fn proxy_host(arg: &str) -> Result<(&str, Option<Proxy>), String> {
+    return match arg.split_once('=') {
+        Some((host, "direct")) => Ok((host, None)),
+        Some((host, proxy)) => Ok((host, Some(proxy.parse::<Proxy>()?))),
+        None => Err(format!("Proxy rule without a proxy: {:?} (host[:port]=socks5h://host:port or host[:port]=direct)", arg)),
+    }
+}
+
+fn proxy_config(args: &CliArgs) -> Result<ProxyConfig, String> {
+    let mut proxy = ProxyConfig::new();
+    proxy.update_proxy(args.proxy.as_deref().map(str::parse::<Proxy>).transpose()?);
+    for arg in &args.proxy_hosts {
+        let (host, rule) = proxy_host(arg)?;
+        proxy.update_host(host, rule);
+    }
+    return Ok(proxy)
+}
+
+// the settings of every server, then the rules of the politeness file on top of them
+fn politeness_config(args: &CliArgs) -> Result<PolitenessConfig, String> {
+    if let Some(rate) = args.max_rate.filter(|rate| { !HostPoliteness::valid_rate(*rate) }) {
+        return Err(format!("Invalid rate: {:?}, at least one request a day is needed", rate))
+    }
+    let mut host = HostPoliteness::new();
+    host
+        .update_min_delay(Duration::from_millis(args.min_delay))
+        .update_max_rate(args.max_rate)
+        .update_max_conns(args.max_conns_per_host);
+    let mut politeness = PolitenessConfig::new();
+    politeness.update_host_default(host).update_bandwidth(args.bandwidth);
+    if let Some(politeness_file) = &args.politeness_file {
+        politeness.load_hosts(politeness_file).map_err(|error| { error.to_string() })?;
+    }
+    return Ok(politeness)
+}
+
+fn retry_policy(args: &CliArgs) -> RetryPolicy {
+    let mut retry = RetryPolicy::new();
+    retry
+        .update_max_attempts(args.retries)
+        .update_base_delay(Duration::from_millis(args.retry_delay))
+        .update_max_delay(Duration::from_millis(args.retry_max_delay))
+        .update_jitter(args.retry_jitter);
+    if !args.retry_on.is_empty() {
+        retry.update_retry_on(&args.retry_on);
+    }
+    return retry
+}
+
+// the only place command line arguments are turned into library settings
+fn client_config(args: &CliArgs, tls: TlsConfig) -> Result<ClientConfig, String> {
+    let mut config = ClientConfig::new();
+    config
+        .update_conn_timeout(Duration::from_secs(args.conn_timeout))
+        .update_resp_timeout(Duration::from_secs(args.resp_timeout))
+        .update_verbose(!args.disable_verbose)
+        .update_ip(args.ip)
+        .update_trace_external(args.trace_external)
+        .update_download_root(args.download_path_prefix.as_deref())
+        .update_search_queries(&args.search_queries)
+        .update_plus(args.plus)
+        .update_workers(args.workers)
+        .update_max_depth(args.max_depth)
+        .update_max_menus(args.max_menus)
+        .update_max_items(args.max_items)
+        .update_max_duration(args.max_duration.map(Duration::from_secs))
+        .update_checkpoint_path(checkpoint_path(args).as_deref())
+        .update_mirror(args.mirror)
+        .update_retry(retry_policy(args))
+        .update_robots(!args.ignore_robots)
+        .update_caps(!args.ignore_caps)
+        .update_tls(tls)
+        .update_proxy(proxy_config(args)?)
+        .update_politeness(politeness_config(args)?);
+    return Ok(config)
+}
+
+// checkpoints go under the log path, then the download path, or back to the file resumed from
+fn checkpoint_path(args: &CliArgs) -> Option<String> {
+    return args.log_path_prefix.as_ref()
+        .or(args.download_path_prefix.as_ref())
+        .map(|path_prefix| { format!("{}/checkpoint.txt", path_prefix) })
+        .or(args.resume.clone())
+}
+
+fn client(args: &CliArgs) -> Result<Client, GopherError> {
+    return match (&args.resume, &args.host) {
+        (Some(checkpoint_path), _) => Client::resume(checkpoint_path),
+        (None, Some(host)) => Ok(Client::from_url(&GopherUrl::parse(host)?)),
+        (None, None) => Err(GopherError::parse("No host given")),
+    }
+}
 
-    if let Some(captured) = host_matcher.captures(&CLI_ARGS.host) {
-        let port = if let Ok(_port) = String::from(captured.get(2).unwrap().as_str()).parse::<u16>() {_port} else {70};
-        let domain = captured.get(1).unwrap().as_str();
+// the changes since the snapshot go to diff.tsv under the log path
+fn save_diff(client: &Client, args: &CliArgs) {
+    if let (Some(diff), Some(path_prefix)) = (client.diff(), &args.log_path_prefix) {
+        if let Err(error) = diff.save(&format!("{}/diff.tsv", path_prefix)) {
+            println!("\n===Diff write unsuccessful!===\n{}\n", error);
+        }
+    }
+}
+
+fn save_exports(client: &Client, args: &CliArgs) {
+    for path in &args.exports {
+        match export::save(path, client) {
+            Ok(()) => { println!("===Export saved===\nLocation: {}\n", path); }
+            Err(error) => { println!("\n===Export unsuccessful!===\n{}\n", error); }
+        }
+    }
+}
+
+// menus name the bind address unless it listens on every address
+fn serve(args: &ServeArgs) {
+    let host = args.name.clone().unwrap_or(match args.bind.as_str() {
+        "0.0.0.0" | "::" => String::from("localhost"),
+        bind => String::from(bind),
+    });
+    let mut server = Server::new(&args.root);
+    server.update_bind(&args.bind).update_port(args.port).update_host(&host).update_max_connections(args.conns);
+
+    match server.listen() {
+        Ok(listener) => {
+            println!("===Serving===\nRoot: {}\nAddress: {}:{}\nHost: {}\n", args.root, args.bind, args.port, host);
+            server.serve_listener(listener);
+        }
+        Err(error) => { println!("\n===Serving failed===\n{}\n", error); }
+    }
+}
+
+fn main() {
+    if let Some(Command::Serve(serve_args)) = &CLI_ARGS.command {
+        return serve(serve_args)
+    }
 
-        let mut client = Client::new(domain);
-        client.update_port(port).scan_all();
+    match client(&CLI_ARGS) {
+        Ok(mut client) => {
+            if let Some(snapshot_path) = &CLI_ARGS.snapshot {
+                if let Err(error) = client.load_snapshot(snapshot_path) {
+                    println!("\n===Invalid snapshot===\n{}\n", error);
+                    return
+                }
+            }
+            let config = match tls_config(&CLI_ARGS, &client).and_then(|tls| { client_config(&CLI_ARGS, tls) }) {
+                Ok(config) => config,
+                Err(message) => {
+                    println!("\n===Invalid connection setting===\n{}\n", message);
+                    return
+                }
+            };
+            client.update_config(config).scan_all();
 
-        if let Some(ref dl_prefix) = CLI_ARGS.download_path_prefix {
-            client.download_all_to(dl_prefix).unwrap();
+            if client.config.download_root.is_some() {
+                if let Err(error) = client.download_all() {
+                    println!("\n===File downloads aborted===\n{}\n", error);
+                }
+            }
+            save_diff(&client, &CLI_ARGS);
+            save_exports(&client, &CLI_ARGS);
         }
+        Err(error) if CLI_ARGS.resume.is_some() => { println!("\n===Invalid checkpoint===\n{}\n", error); }
+        Err(error) => { println!("\n===Invalid host===\n{}\n", error); }
     }
 
-    produce()
+    produce(CLI_ARGS.log_path_prefix.as_deref())
 }
@@ -0,0 +1,135 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::log::BUFF;
+use simple_gopher_in_rust::gopher::config::{ClientConfig, IpPreference, RequestConfig, RetryPolicy};
+use simple_gopher_in_rust::gopher::error::GopherError;
+use simple_gopher_in_rust::gopher::io::Request;
+use simple_gopher_in_rust::gopher::server::Server;
+use simple_gopher_in_rust::gopher::types::Referer;
+use simple_gopher_in_rust::gopher::transport::{connect_any, order_addresses};
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fault, Fixture, MockServer, TempDir};
+
+fn address(address: &str) -> SocketAddr {
+    return address.parse().unwrap()
+}
+
+// an address nothing listens on
+fn closed(bind: &str) -> SocketAddr {
+    return TcpListener::bind(bind).unwrap().local_addr().unwrap()
+}
+
+fn request(domain: &str, port: u16, ip: IpPreference) -> Request {
+    let mut config = RequestConfig::new();
+    config.update_verbose(false).update_conn_timeout(Duration::from_secs(2)).update_ip(ip);
+    let mut req = Request::new(domain, port);
+    req.update_config(&config);
+    return req
+}
+
+// a folder served on ::1, with the server thread left running for the test
+fn served_on_ipv6(dir: &TempDir) -> u16 {
+    std::fs::write(dir.path.join("hello.txt"), "hello over IPv6\n").unwrap();
+    let mut server = Server::new(&dir.path_str());
+    server.update_bind("::1").update_port(0).update_host("::1");
+    let listener = server.listen().unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || { server.serve_listener(listener) });
+    return port
+}
+
+#[test]
+fn families_take_turns() {
+    let resolved = vec![address("[2001:db8::1]:70"), address("[2001:db8::2]:70"), address("192.0.2.1:70"), address("192.0.2.2:70")];
+    let ports = |ip| { order_addresses(resolved.clone(), ip).iter().map(|addr| { addr.to_string() }).collect::<Vec<String>>() };
+
+    assert_eq!(ports(IpPreference::Auto), vec!["[2001:db8::1]:70", "192.0.2.1:70", "[2001:db8::2]:70", "192.0.2.2:70"]);
+    assert_eq!(ports(IpPreference::PreferIpv4), vec!["192.0.2.1:70", "[2001:db8::1]:70", "192.0.2.2:70", "[2001:db8::2]:70"]);
+    assert_eq!(ports(IpPreference::Ipv4), vec!["192.0.2.1:70", "192.0.2.2:70"]);
+    assert_eq!(ports(IpPreference::Ipv6), vec!["[2001:db8::1]:70", "[2001:db8::2]:70"]);
+    assert_eq!("prefer-ipv6".parse::<IpPreference>(), Ok(IpPreference::PreferIpv6));
+    assert!("ipv5".parse::<IpPreference>().is_err());
+}
+
+#[test]
+fn refused_addresses_fall_through_to_the_next() {
+    let server = MockServer::start(Fixture::standard());
+    let live = address(&server.host());
+
+    let stream = connect_any(&[closed("[::1]:0"), closed("127.0.0.1:0"), live], Duration::from_secs(2)).unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), live);
+
+    let error = connect_any(&[closed("127.0.0.1:0"), closed("[::1]:0")], Duration::from_secs(2)).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::ConnectionRefused);
+}
+
+#[test]
+fn silent_addresses_are_overtaken() {
+    let server = MockServer::start(Fixture::standard());
+    let live = address(&server.host());
+    let started = Instant::now();
+    // TEST-NET-1 never answers, the live address gets its turn after the attempt delay
+    let stream = connect_any(&[address("192.0.2.1:70"), live], Duration::from_secs(5)).unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), live);
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+}
+
+#[test]
+fn ipv6_servers_are_reachable() {
+    let dir = TempDir::new("ipv6");
+    let port = served_on_ipv6(&dir);
+
+    let items = request("::1", port, IpPreference::Auto).send().unwrap().as_items().unwrap();
+    assert!(items.iter().any(|item| { item.url().is_some_and(|url| { url.selector == "/hello.txt" }) }));
+    let refused = request("::1", port, IpPreference::Ipv4).send();
+    assert!(matches!(refused, Err(GopherError::Dns { .. })), "{:?}", refused.err());
+
+    // bracketed literals in host input
+    let mut client = Client::from_url(&GopherUrl::parse(&format!("[::1]:{}", port)).unwrap());
+    assert_eq!(client.url().to_string(), format!("gopher://[::1]:{}/1/", port));
+    let mut config = ClientConfig::new();
+    config.update_verbose(false);
+    client.update_config(config).scan_all();
+    assert_eq!(client.referers.len(), 1);
+    assert!(client.failures.is_empty());
+}
+
+#[test]
+fn the_answering_address_is_logged_and_kept_with_failures() {
+    let server = MockServer::start(Fixture::standard().fault("/blob.bin", Fault::Reset));
+    let dir = TempDir::new("answering-address");
+    let mut config = ClientConfig::new();
+    config.update_verbose(false).update_retry(RetryPolicy::none()).update_download_root(Some(&dir.path_str()));
+    let mut client = Client::from_url(&GopherUrl::parse(&server.host()).unwrap());
+    client.update_config(config).scan_all();
+    client.download_all().unwrap();
+
+    let answered = address(&server.host());
+    let root = Referer::new("127.0.0.1", answered.port(), "/");
+    assert_eq!(client.config.request.addresses.get(&root), Some(answered));
+    let log = BUFF.lock().unwrap().clone();
+    assert!(log.contains(&format!("Target: {}\nAddress: {}\n", root, answered)));
+    let about = client.items.iter().find_map(|item| { item.url().filter(|url| { url.selector == "/about.txt" }) }).unwrap();
+    assert!(log.contains(&format!("URL: {}\nAddress: {}\n", about, answered)));
+
+    let failure = &client.failures[0];
+    assert_eq!((failure.referer.path.as_str(), failure.address), ("/blob.bin", Some(answered)));
+    assert!(failure.to_string().contains(&format!("Address: {}\n", answered)));
+
+    // nothing answered a request that could not connect
+    let refused = closed("127.0.0.1:0");
+    let mut client = Client::from_url(&GopherUrl::parse(&refused.to_string()).unwrap());
+    let mut config = ClientConfig::new();
+    config.update_verbose(false).update_retry(RetryPolicy::none());
+    client.update_config(config).scan_all();
+    assert_eq!(client.failures[0].address, None);
+    assert!(!client.failures[0].to_string().contains("Address:"));
+}
@@ -0,0 +1,100 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::checkpoint;
+use simple_gopher_in_rust::gopher::config::ClientConfig;
+use simple_gopher_in_rust::gopher::crawl::{menu_key, Frontier};
+use simple_gopher_in_rust::gopher::diff::FileRecord;
+use simple_gopher_in_rust::gopher::error::GopherError;
+use simple_gopher_in_rust::gopher::io::Request;
+use simple_gopher_in_rust::gopher::types::Referer;
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fixture, MockServer, TempDir};
+
+// more than fits a 32-bit usize
+const LARGE_SIZE: u64 = 5_000_000_000;
+
+fn url(server: &MockServer, selector: &str) -> GopherUrl {
+    return GopherUrl::parse(&server.url(selector)).unwrap()
+}
+
+// a crawl of the standard fixture interrupted after the root menu, with /docs and /search left to fetch
+fn interrupted(server: &MockServer) -> (Client, Frontier) {
+    let mut client = Client::from_url(&url(server, "/"));
+    let mut root = Referer::from_url(&url(server, "/"));
+    root.update_depth(Some(0));
+    client.referers.push(root);
+    client.downloads.insert(format!("gopher://{}/9/blob.bin", server.host()), FileRecord::new(LARGE_SIZE, Some("ab".repeat(32))));
+    client.downloads.insert(format!("gopher://{}/0/about.txt", server.host()), FileRecord::new(19, None));
+
+    let mut frontier = Frontier::new(Request::from_url(&url(server, "/docs")));
+    frontier.level.push(Request::from_url(&url(server, "/search")));
+    frontier.visited.insert(menu_key(&Referer::from_url(&url(server, "/"))));
+    frontier.visited.insert(menu_key(&Referer::from_url(&url(server, "/search"))));
+    frontier.depth = 1;
+    frontier.menu_count = 1;
+    frontier.item_count = 7;
+    return (client, frontier)
+}
+
+#[test]
+fn resumed_runs_get_back_their_frontier_visited_set_and_downloads() {
+    let server = MockServer::start(Fixture::standard());
+    let dir = TempDir::new("checkpoint-resume");
+    let path = dir.path.join("run.checkpoint").to_string_lossy().to_string();
+    let (client, frontier) = interrupted(&server);
+    checkpoint::save(&path, &client, Some(&frontier)).unwrap();
+
+    let resumed = Client::resume(&path).unwrap();
+    let pending = resumed.pending_scan.as_ref().unwrap();
+    let level = pending.level.iter().map(|req| { req.url().to_string() }).collect::<Vec<String>>();
+    assert_eq!(level, vec![server.url("/docs"), server.url("/search")]);
+    assert_eq!(pending.visited, frontier.visited);
+    assert_eq!((pending.depth, pending.menu_count, pending.item_count), (1, 1, 7));
+    assert_eq!(resumed.downloads, client.downloads);
+    assert_eq!(resumed.downloads[&format!("gopher://{}/9/blob.bin", server.host())].size, LARGE_SIZE);
+    assert_eq!(resumed.url().to_string(), client.url().to_string());
+}
+
+#[test]
+fn resumed_scans_carry_on_from_the_frontier() {
+    let server = MockServer::start(Fixture::standard());
+    let dir = TempDir::new("checkpoint-carry-on");
+    let path = dir.path.join("run.checkpoint").to_string_lossy().to_string();
+    let (client, frontier) = interrupted(&server);
+    checkpoint::save(&path, &client, Some(&frontier)).unwrap();
+
+    let mut resumed = Client::resume(&path).unwrap();
+    let mut config = ClientConfig::new();
+    config.update_verbose(false).update_resp_timeout(Duration::from_millis(500)).update_checkpoint_path(Some(&path));
+    resumed.update_config(config);
+    resumed.scan_all();
+
+    // the root was fetched before the interruption and is not asked for again, even through /docs linking back to it
+    assert_eq!((server.hits("/"), server.hits("/docs"), server.hits("/docs/deep")), (0, 1, 1));
+    let scanned = resumed.referers.iter().map(|referer| { referer.path.clone() }).collect::<HashSet<String>>();
+    assert_eq!(scanned, HashSet::from(["/", "/docs", "/search", "/docs/deep"].map(String::from)));
+    assert!(resumed.pending_scan.is_none());
+
+    // the checkpoint of the finished scan has nothing left to do
+    let finished = Client::resume(&path).unwrap();
+    assert!(finished.pending_scan.as_ref().is_none_or(|pending| { pending.level.is_empty() }));
+    assert_eq!(finished.downloads, client.downloads);
+}
+
+#[test]
+fn download_sizes_parse_as_u64() {
+    let root = "gopher://127.0.0.1:7070/1/";
+    let checkpoint = format!("checkpoint\t1\nroot\t{}\nscan\tcomplete\ndownload\t{}\tgopher://127.0.0.1:7070/9/big.iso\n", root, u64::MAX);
+    let loaded = checkpoint::Checkpoint::read(checkpoint.as_bytes()).unwrap();
+    assert_eq!(loaded.downloads["gopher://127.0.0.1:7070/9/big.iso"].size, u64::MAX);
+
+    let negative = checkpoint.replace(&u64::MAX.to_string(), "-1");
+    assert!(matches!(checkpoint::Checkpoint::read(negative.as_bytes()), Err(GopherError::Parse { .. })));
+}
@@ -0,0 +1,164 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::time::Duration;
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::config::{ClientConfig, RetryPolicy};
+use simple_gopher_in_rust::gopher::error::GopherError;
+use simple_gopher_in_rust::gopher::types::{Item, ItemClass};
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fault, Fixture, Line, MockServer};
+
+fn config() -> ClientConfig {
+    let mut config = ClientConfig::new();
+    let mut retry = RetryPolicy::new();
+    retry.update_base_delay(Duration::from_millis(10));
+    config.update_verbose(false).update_resp_timeout(Duration::from_millis(500)).update_retry(retry);
+    return config
+}
+
+fn client(server: &MockServer, config: ClientConfig) -> Client {
+    let mut client = Client::from_url(&GopherUrl::parse(&server.host()).unwrap());
+    client.update_config(config);
+    return client
+}
+
+fn selectors(items: &[Item]) -> Vec<String> {
+    return items.iter().filter_map(|item| { item.url() }).map(|url| { url.selector }).collect()
+}
+
+#[test]
+fn scan_finds_every_menu_once() {
+    let server = MockServer::start(Fixture::standard());
+    let mut client = client(&server, config());
+    client.scan_all();
+
+    let scanned = client.referers.iter().map(|referer| { referer.path.clone() }).collect::<Vec<String>>();
+    assert_eq!(scanned, vec!["/", "/docs", "/docs/deep"]);
+    // /docs links back to /, the cycle is not fetched again
+    assert_eq!(server.hits("/"), 1);
+    assert_eq!(server.hits("/docs"), 1);
+    assert!(client.failures.is_empty());
+    assert_eq!(
+        selectors(&client.items),
+        vec!["/docs", "/about.txt", "/blob.bin", "/search", "/", "/docs/readme.txt", "/docs/deep", "/", "/docs/deep/bottom.txt"]
+    );
+    assert!(client.items.iter().any(|item| { item.class() == Some(ItemClass::Error) }));
+}
+
+#[test]
+fn external_menus_are_left_out_unless_traced() {
+    let server = MockServer::start(Fixture::standard());
+    let mut client = client(&server, config());
+    client.scan_all();
+    assert!(client.referers.iter().all(|referer| { referer.domain == "127.0.0.1" }));
+
+    // the external host does not resolve, tracing it ends in a failure rather than a scan
+    let mut traced = config();
+    traced.update_trace_external(true).update_conn_timeout(Duration::from_millis(500));
+    let mut client = self::client(&server, traced);
+    client.scan_all();
+    assert!(client.failures.iter().any(|failure| { failure.referer.domain == "example.invalid" }));
+}
+
+#[test]
+fn search_items_are_followed_with_queries() {
+    let server = MockServer::start(Fixture::standard());
+    let mut config = config();
+    config.update_search_queries(&[String::from("gopher")]);
+    let mut client = client(&server, config);
+    client.scan_all();
+
+    assert!(server.requests().contains(&String::from("/search\tgopher")));
+    assert!(client.referers.iter().any(|referer| { referer.path == "/search" && referer.query.as_deref() == Some("gopher") }));
+}
+
+#[test]
+fn workers_do_not_change_the_results() {
+    let server = MockServer::start(Fixture::standard());
+    let mut single = client(&server, config());
+    single.scan_all();
+
+    let mut pooled_config = config();
+    pooled_config.update_workers(4);
+    let mut pooled = client(&server, pooled_config);
+    pooled.scan_all();
+
+    assert_eq!(single.items, pooled.items);
+    let urls = |client: &Client| { client.referers.iter().map(|referer| { referer.to_string() }).collect::<Vec<String>>() };
+    assert_eq!(urls(&single), urls(&pooled));
+}
+
+#[test]
+fn max_depth_stops_the_scan() {
+    let server = MockServer::start(Fixture::standard());
+    let mut config = config();
+    config.update_max_depth(Some(1));
+    let mut client = client(&server, config);
+    client.scan_all();
+
+    assert_eq!(server.hits("/docs/deep"), 0);
+    assert_eq!(client.referers.len(), 2);
+}
+
+#[test]
+fn faulty_menus_are_recorded_as_failures() {
+    let fixture = Fixture::standard()
+        .fault("/docs", Fault::Reset)
+        .menu("/", vec![Line::menu("Documents", "/docs"), Line::menu("Missing", "/missing"), Line::menu("Slow", "/slow")])
+        .menu("/slow", vec![Line::info("too late")])
+        .fault("/slow", Fault::Delay(Duration::from_secs(2)));
+    let server = MockServer::start(fixture);
+    let mut client = client(&server, config());
+    client.scan_all();
+
+    let failed = |path: &str| {
+        return client.failures.iter().find(|failure| { failure.referer.path == path }).map(|failure| { (&failure.error, failure.attempts) })
+    };
+    assert!(matches!(failed("/docs"), Some((GopherError::Read { .. }, 3))));
+    // error items are the server's answer, asking again would not change it
+    assert!(matches!(failed("/missing"), Some((GopherError::ServerError { .. }, 1))));
+    assert!(matches!(failed("/slow"), Some((GopherError::ReadTimeout { .. }, 3))));
+    assert_eq!(client.failures.len(), 3);
+    assert_eq!(server.hits("/docs"), 3);
+}
+
+#[test]
+fn urls_with_non_ascii_hosts_parse() {
+    // the scheme is checked without slicing through the multi-byte chars of the host
+    let url = GopherUrl::parse("ééééé").unwrap();
+    assert_eq!((url.domain.as_str(), url.port), ("ééééé", 70));
+}
+
+#[test]
+fn scan_starts_from_the_url_path() {
+    let server = MockServer::start(Fixture::standard());
+    let mut client = Client::from_url(&GopherUrl::parse(&server.url("/docs")).unwrap());
+    client.update_config(config());
+    client.scan_all();
+
+    assert_eq!(client.referers[0].path, "/docs");
+    // the link back to / takes the scan to the rest of the server
+    assert!(client.referers.iter().any(|referer| { referer.path == "/" }));
+}
+
+#[test]
+fn caps_leave_trailing_slashes_and_non_path_selectors_alone() {
+    let fixture = Fixture::new()
+        .menu("/", vec![Line::menu("Folder", "/dir/"), Line::menu("Web", "GET /"), Line::menu("Up", "/dir/sub/..")])
+        .menu("/dir/", vec![Line::text("Inside", "/dir/inside.txt")])
+        .menu("GET /", vec![Line::info("not a path")])
+        .text("caps.txt", "CAPS\nCapsVersion=1\nPathDelimiter=/\n");
+    let server = MockServer::start(fixture);
+    let mut client = client(&server, config());
+    client.scan_all();
+
+    assert!(client.caps().is_some());
+    // "/dir/sub/.." resolves to the folder "/dir/" the scan already has
+    assert_eq!((server.hits("/dir/"), server.hits("GET /")), (1, 1));
+    assert_eq!(server.hits("/dir") + server.hits("/dir/sub/..") + server.hits("GET"), 0);
+    assert!(selectors(&client.items).contains(&String::from("/dir/inside.txt")));
+}
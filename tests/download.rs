@@ -0,0 +1,122 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::fs;
+use std::time::{Duration, UNIX_EPOCH};
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::checkpoint::{self, Checkpoint};
+use simple_gopher_in_rust::gopher::config::{ClientConfig, RetryPolicy};
+use simple_gopher_in_rust::gopher::diff::{file_hash, FileRecord};
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fault, Fixture, MockServer, TempDir};
+
+fn scanned(server: &MockServer, download_root: &TempDir) -> Client {
+    let mut retry = RetryPolicy::new();
+    retry.update_base_delay(Duration::from_millis(10));
+    let mut config = ClientConfig::new();
+    config
+        .update_verbose(false)
+        .update_resp_timeout(Duration::from_millis(500))
+        .update_retry(retry)
+        .update_download_root(Some(&download_root.path_str()));
+    let mut client = Client::from_url(&GopherUrl::parse(&server.host()).unwrap());
+    client.update_config(config).scan_all();
+    return client
+}
+
+#[test]
+fn download_all_saves_every_file() {
+    let server = MockServer::start(Fixture::standard());
+    let dir = TempDir::new("download-all");
+    let mut client = scanned(&server, &dir);
+    client.download_all().unwrap();
+
+    assert_eq!(fs::read_to_string(dir.path.join("about.txt")).unwrap(), "About this server\r\n");
+    assert_eq!(
+        fs::read_to_string(dir.path.join("docs/readme.txt")).unwrap(),
+        "first line\r\n.starts with a dot\r\nlast line\r\n"
+    );
+    assert_eq!(fs::read_to_string(dir.path.join("docs/deep/bottom.txt")).unwrap(), "bottom\r\n");
+    assert_eq!(fs::read(dir.path.join("blob.bin")).unwrap(), vec![0, 1, 2, 255, b'\n', b'.', b'\r', b'\n', 7]);
+    assert_eq!(client.downloads.len(), 4);
+    assert!(client.failures.is_empty());
+}
+
+#[test]
+fn failed_downloads_do_not_stop_the_rest() {
+    let server = MockServer::start(Fixture::standard().fault("/about.txt", Fault::Reset));
+    let dir = TempDir::new("download-failed");
+    let mut client = scanned(&server, &dir);
+    client.download_all().unwrap();
+
+    assert_eq!(client.failures.len(), 1);
+    assert_eq!(client.failures[0].referer.path, "/about.txt");
+    assert_eq!(client.failures[0].attempts, 3);
+    assert_eq!(client.downloads.len(), 3);
+    assert!(dir.path.join("blob.bin").is_file());
+}
+
+#[test]
+fn downloaded_files_are_not_fetched_again() {
+    let server = MockServer::start(Fixture::standard());
+    let dir = TempDir::new("download-twice");
+    let mut client = scanned(&server, &dir);
+    client.download_all().unwrap();
+    client.download_all().unwrap();
+
+    assert_eq!(server.hits("/blob.bin"), 1);
+    assert_eq!(server.hits("/docs/readme.txt"), 1);
+}
+
+// download with a snapshot that saw an older about.txt, readme.txt is the same and is not written again
+fn download_against_a_snapshot(name: &str, download: fn(&mut Client)) {
+    let server = MockServer::start(Fixture::standard());
+    let dir = TempDir::new(name);
+    let mut client = scanned(&server, &dir);
+    client.download_all().unwrap();
+    let mut saved: Vec<u8> = Vec::new();
+    checkpoint::write(&mut saved, &client, None).unwrap();
+    let mut snapshot = Checkpoint::read(&saved[..]).unwrap();
+
+    let (about, readme) = (dir.path.join("about.txt"), dir.path.join("docs/readme.txt"));
+    fs::write(&about, "About this server, before\r\n").unwrap();
+    let about_url = snapshot.downloads.keys().find(|url| { url.ends_with("/about.txt") }).cloned().unwrap();
+    snapshot.downloads.insert(about_url.clone(), FileRecord::new(27, file_hash(&about)));
+    let long_ago = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    for unchanged in [&readme, &dir.path.join("blob.bin")] {
+        fs::File::options().write(true).open(unchanged).unwrap().set_modified(long_ago).unwrap();
+    }
+
+    let mut client = scanned(&server, &dir);
+    client.update_snapshot(Some(snapshot));
+    download(&mut client);
+
+    assert_eq!(server.hits("/about.txt") + server.hits("/docs/readme.txt") + server.hits("/blob.bin"), 6);
+    assert_eq!(fs::read_to_string(&about).unwrap(), "About this server\r\n");
+    assert_eq!(fs::metadata(&readme).unwrap().modified().unwrap(), long_ago);
+    assert_eq!(fs::metadata(dir.path.join("blob.bin")).unwrap().modified().unwrap(), long_ago);
+    // the temporary files are gone either way
+    for folder in [dir.path.clone(), dir.path.join("docs")] {
+        assert!(fs::read_dir(folder).unwrap().all(|entry| { !entry.unwrap().file_name().to_string_lossy().ends_with(".part") }));
+    }
+    let changed = client.diff().unwrap().changed_files;
+    assert_eq!(changed.iter().map(|file| { file.url.as_str() }).collect::<Vec<&str>>(), vec![about_url.as_str()]);
+    assert_eq!((changed[0].before.size, changed[0].after.size), (27, 19));
+}
+
+#[test]
+fn a_snapshot_keeps_unchanged_files_and_reports_changed_ones() {
+    download_against_a_snapshot("download-snapshot", |client| { client.download_all().unwrap(); });
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_downloads_keep_unchanged_files_as_well() {
+    download_against_a_snapshot("download-snapshot-async", |client| {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async { client.async_download_all().await.unwrap(); });
+    });
+}
@@ -0,0 +1,131 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::time::Duration;
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::config::ClientConfig;
+use simple_gopher_in_rust::gopher::export::ExportFormat;
+use simple_gopher_in_rust::gopher::types::{Item, ItemKind, Referer};
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fixture, Line, MockServer};
+
+// captions with every char the formats have to escape, which menu lines cannot carry is added by hand
+const AWKWARD: &str = "two\nlines\r\tand a \u{1}";
+
+fn export_fixture() -> Fixture {
+    return Fixture::new()
+        .menu("/", vec![
+            Line::info("Say \"hi\", then \\ leave"),
+            Line::text("Notes, \"draft\"", "/notes, v1.txt"),
+            Line::menu("Quoted", "/say \"hi\""),
+            Line::search("Search", "/search"),
+            Line::external('1', "Elsewhere", "/ext", "other.invalid", 7070),
+            Line::external('0', "Far text", "/far.txt", "far.invalid", 70),
+            Line::raw("not a menu line"),
+        ])
+        .menu("/say \"hi\"", vec![
+            Line::menu("Back to the top", "/"),
+        ])
+        .search_results("/search", "gopher", vec![
+            Line::text("Found", "/found.txt"),
+        ])
+}
+
+fn exported(format: ExportFormat) -> (MockServer, String) {
+    let server = MockServer::start(export_fixture());
+    let mut config = ClientConfig::new();
+    config
+        .update_verbose(false)
+        .update_resp_timeout(Duration::from_millis(500))
+        .update_search_queries(&[String::from("gopher")]);
+    let mut client = Client::from_url(&GopherUrl::parse(&server.host()).unwrap());
+    client.update_config(config);
+    client.scan_all();
+    let from = Referer::new("127.0.0.1", server.port, "/");
+    client.items.push(Item::INFO { kind: ItemKind::Info, from, message: String::from(AWKWARD), domain: String::from("localhost"), port: server.port });
+
+    let mut written: Vec<u8> = Vec::new();
+    client.export(format, &mut written).unwrap();
+    return (server, String::from_utf8(written).unwrap())
+}
+
+// records of a CSV file as RFC 4180 reads them, quoted fields may hold separators, quotes and line breaks
+fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+    let (mut records, mut record, mut field) = (Vec::new(), Vec::new(), String::new());
+    let (mut chars, mut quoted) = (csv.chars().peekable(), false);
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => { chars.next(); field.push('"'); }
+            ('"', _) => quoted = !quoted,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    return records
+}
+
+#[test]
+fn json_lines_escape_quotes_backslashes_and_control_chars() {
+    let (server, jsonl) = exported(ExportFormat::JsonLines);
+    let records = jsonl.lines().map(|line| { serde_json::from_str::<serde_json::Value>(line).unwrap() }).collect::<Vec<_>>();
+    let caption = |caption: &str| { records.iter().find(|record| { record["caption"] == caption }).unwrap() };
+
+    assert_eq!(caption("Say \"hi\", then \\ leave")["class"], "info");
+    let notes = caption("Notes, \"draft\"");
+    assert_eq!((notes["type"].as_str(), notes["selector"].as_str()), (Some("0"), Some("/notes, v1.txt")));
+    assert_eq!(notes["port"], server.port);
+    assert_eq!(caption("not a menu line")["port"], serde_json::Value::Null);
+    assert_eq!(caption(AWKWARD)["url"], serde_json::Value::Null);
+    assert!(jsonl.contains(r#""caption":"two\nlines\r\tand a \u0001""#), "{}", jsonl);
+    assert!(jsonl.contains(r#""caption":"Say \"hi\", then \\ leave""#), "{}", jsonl);
+    assert_eq!(jsonl.lines().count(), records.len());
+}
+
+#[test]
+fn csv_quotes_commas_quotes_and_line_breaks() {
+    let (server, csv) = exported(ExportFormat::Csv);
+    let records = parse_csv(&csv);
+    assert_eq!(records[0].join(","), "parent,type,class,caption,selector,host,port,size,url");
+    assert!(records.iter().all(|record| { record.len() == 9 }), "{:?}", records);
+
+    let notes = records.iter().find(|record| { record[3] == "Notes, \"draft\"" }).unwrap();
+    assert_eq!(notes[4], "/notes, v1.txt");
+    assert_eq!(notes[6], server.port.to_string());
+    assert!(records.iter().any(|record| { record[3] == AWKWARD }));
+    assert!(csv.contains("\"Notes, \"\"draft\"\"\",\"/notes, v1.txt\""), "{}", csv);
+    assert!(csv.contains(&format!(",\"{}\",", AWKWARD)), "{}", csv);
+    // fields without anything to quote are left bare
+    assert!(csv.contains(&format!(",0,text,Found,/found.txt,127.0.0.1,{},", server.port)), "{}", csv);
+}
+
+#[test]
+fn dot_graphs_put_external_servers_in_clusters() {
+    let (server, dot) = exported(ExportFormat::Dot);
+    let own = |kind: char, selector: &str| { format!("\"gopher://127.0.0.1:{}/{}{}\"", server.port, kind, selector) };
+    let (root, quoted) = (own('1', "/"), own('1', "/say%20%22hi%22"));
+    let (search, results) = (own('7', "/search"), own('7', "/search%09gopher"));
+
+    assert!(dot.starts_with(&format!("digraph gopher {{\n    label=\"127.0.0.1:{}\";\n    node [shape=box];\n", server.port)), "{}", dot);
+    assert!(dot.contains(&format!("\n    {} [label=\"/\"];\n", root)), "{}", dot);
+    assert!(dot.contains(&format!("\n    {} [label=\"/say \\\"hi\\\"\"];\n", quoted)), "{}", dot);
+    assert!(dot.contains(&format!("\n    {} [label=\"/search ? gopher\"];\n", results)), "{}", dot);
+
+    assert!(dot.contains("\n    subgraph cluster_0 {\n        label=\"other.invalid:7070\";\n        \"gopher://other.invalid:7070/1/ext\" [label=\"/ext\"];\n    }\n"), "{}", dot);
+    // only menus are nodes, the text file of the other server gets no cluster
+    assert!(!dot.contains("far.invalid") && !dot.contains("cluster_1"), "{}", dot);
+
+    assert!(dot.contains(&format!("\n    {} -> {};\n", root, quoted)), "{}", dot);
+    assert!(dot.contains(&format!("\n    {} -> \"gopher://other.invalid:7070/1/ext\";\n", root)), "{}", dot);
+    assert!(dot.contains(&format!("\n    {} -> {};\n", quoted, root)), "{}", dot);
+    // the results of a search hang off the search item
+    assert!(dot.contains(&format!("\n    {} -> {};\n", root, search)), "{}", dot);
+    assert!(dot.contains(&format!("\n    {} -> {};\n", search, results)), "{}", dot);
+    assert!(dot.ends_with("}\n"));
+}
@@ -0,0 +1,123 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::fs;
+use std::time::Duration;
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::config::ClientConfig;
+use simple_gopher_in_rust::gopher::io::local_path;
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fixture, Line, MockServer, TempDir};
+
+// a server whose files are named like the pages the mirror writes
+fn mirror_fixture() -> Fixture {
+    return Fixture::new()
+        .menu("/", vec![
+            Line::info("Welcome <home>"),
+            Line::menu("Documents", "/docs"),
+            Line::text("Index page", "/index.html"),
+            Line::item('h', "Web", "URL:https://example.org/?a=1&b=2"),
+            Line::item('h', "Script", "URL:javascript:alert(1)"),
+            Line::item('h', "Shouting", "URL:JavaScript:alert(2)"),
+            Line::search("Search", "/search"),
+            Line::external('1', "Elsewhere", "/", "example.invalid", 70),
+        ])
+        .menu("/docs", vec![
+            Line::text("Map", "/docs/gophermap"),
+            Line::menu("Back to the top", "/"),
+        ])
+        .search_results("/search", "gopher", vec![
+            Line::menu("Documents", "/docs"),
+        ])
+        .text("/index.html", "<p>served as text</p>\r\n")
+        .text("/docs/gophermap", "iserved as text\r\n")
+}
+
+fn mirror_client(server: &MockServer, download_root: &TempDir) -> Client {
+    let mut config = ClientConfig::new();
+    config
+        .update_verbose(false)
+        .update_resp_timeout(Duration::from_millis(500))
+        .update_search_queries(&[String::from("gopher")])
+        .update_download_root(Some(&download_root.path_str()))
+        .update_mirror(true);
+    let mut client = Client::from_url(&GopherUrl::parse(&server.host()).unwrap());
+    client.update_config(config);
+    return client
+}
+
+// scan and download the fixture with the mirror on
+fn mirrored(name: &str) -> (MockServer, TempDir) {
+    let server = MockServer::start(mirror_fixture());
+    let dir = TempDir::new(name);
+    let mut client = mirror_client(&server, &dir);
+    client.scan_all();
+    client.download_all().unwrap();
+    assert!(client.failures.is_empty());
+    return (server, dir)
+}
+
+#[test]
+fn mirror_names_are_kept_free_of_downloads() {
+    assert_eq!(local_path("/index.html"), "~index.html");
+    assert_eq!(local_path("/docs/gophermap/notes.txt"), "docs/~gophermap/notes.txt");
+    assert_eq!(local_path("/~index.html"), "~~index.html");
+    assert_eq!(local_path("/~notes.txt"), "~notes.txt");
+    assert_eq!(local_path("/docs/index.htm"), "docs/index.htm");
+}
+
+#[test]
+fn menus_are_mirrored_as_gophermaps() {
+    let (server, dir) = mirrored("mirror-gophermap");
+
+    let root = fs::read_to_string(dir.path.join("gophermap")).unwrap();
+    let lines = root.split("\r\n").collect::<Vec<&str>>();
+    assert_eq!(lines[0], format!("iWelcome <home>\t\tlocalhost\t{}", server.port));
+    assert!(lines.contains(&"1Documents\t/docs"), "{}", root);
+    assert!(lines.contains(&"0Index page\t/~index.html"), "{}", root);
+    assert!(lines.contains(&"1Search (gopher)\t/search/gopher"), "{}", root);
+    assert!(lines.contains(&"1Elsewhere\t/\texample.invalid\t70"), "{}", root);
+    assert!(root.ends_with("\r\n.\r\n"));
+
+    let docs = fs::read_to_string(dir.path.join("docs/gophermap")).unwrap();
+    assert_eq!(docs, "0Map\t/docs/~gophermap\r\n1Back to the top\t/\r\n.\r\n");
+    let results = fs::read_to_string(dir.path.join("search/gopher/gophermap")).unwrap();
+    assert_eq!(results, "1Documents\t/docs\r\n.\r\n");
+
+    // the downloaded files sit next to the mirrored menus instead of over them
+    assert_eq!(fs::read_to_string(dir.path.join("~index.html")).unwrap(), "<p>served as text</p>\r\n");
+    assert_eq!(fs::read_to_string(dir.path.join("docs/~gophermap")).unwrap(), "iserved as text\r\n");
+    assert!(fs::read_to_string(dir.path.join("index.html")).unwrap().starts_with("<!DOCTYPE html>"));
+}
+
+#[test]
+fn menus_are_mirrored_as_pages_with_relative_links() {
+    let (_server, dir) = mirrored("mirror-html");
+
+    let root = fs::read_to_string(dir.path.join("index.html")).unwrap();
+    assert!(root.contains("\nWelcome &lt;home&gt;\n"), "{}", root);
+    assert!(root.contains("[menu] <a href=\"docs/index.html\">Documents</a>"), "{}", root);
+    assert!(root.contains("[text] <a href=\"~index.html\">Index page</a>"), "{}", root);
+    assert!(root.contains("[results] <a href=\"search/gopher/index.html\">Search (gopher)</a>"), "{}", root);
+    assert!(root.contains("[menu] <a href=\"gopher://example.invalid/1/\">Elsewhere</a>"), "{}", root);
+
+    let docs = fs::read_to_string(dir.path.join("docs/index.html")).unwrap();
+    assert!(docs.contains("[text] <a href=\"~gophermap\">Map</a>"), "{}", docs);
+    assert!(docs.contains("[menu] <a href=\"../index.html\">Back to the top</a>"), "{}", docs);
+    let results = fs::read_to_string(dir.path.join("search/gopher/index.html")).unwrap();
+    assert!(results.contains("<a href=\"../../docs/index.html\">Documents</a>"), "{}", results);
+}
+
+#[test]
+fn only_web_and_gopher_links_are_made_live() {
+    let (_server, dir) = mirrored("mirror-links");
+
+    let root = fs::read_to_string(dir.path.join("index.html")).unwrap();
+    assert!(root.contains("[link] <a href=\"https://example.org/?a=1&amp;b=2\">Web</a>"), "{}", root);
+    assert!(root.contains("[link] Script javascript:alert(1)\n"), "{}", root);
+    assert!(root.contains("[link] Shouting JavaScript:alert(2)\n"), "{}", root);
+    assert!(!root.to_lowercase().contains("href=\"javascript"), "{}", root);
+}
@@ -0,0 +1,161 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::fs;
+use std::time::Duration;
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::config::ClientConfig;
+use simple_gopher_in_rust::gopher::error::GopherError;
+use simple_gopher_in_rust::gopher::io::Request;
+use simple_gopher_in_rust::gopher::plus::{PlusHeader, PlusLength, PlusRequest};
+use simple_gopher_in_rust::gopher::types::{Item, Referer};
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fixture, Line, MockServer, TempDir};
+
+// attribute blocks of the items of /, for a "$" request
+const DIRECTORY_ATTRIBUTES: &str = "+-1\r\n\
+    +INFO: 0Notes\t/notes.txt\t127.0.0.1\t{port}\t+\r\n\
+    +ADMIN:\r\n Admin: Mock Admin <admin@mock.invalid>\r\n Mod-Date: <20240101120000>\r\n\
+    +VIEWS:\r\n text/plain: <1k>\r\n\
+    +INFO: 9Image\t/image.bin\t127.0.0.1\t{port}\t+\r\n\
+    +VIEWS:\r\n application/octet-stream: <2k>\r\n\
+    +ABSTRACT:\r\n A picture\r\n\
+    .\r\n";
+
+fn plus_fixture() -> Fixture {
+    return Fixture::new()
+        .menu("/", vec![
+            Line::plus('0', "Notes", "/notes.txt"),
+            Line::plus('9', "Image", "/image.bin"),
+            Line::text("Plain", "/plain.txt"),
+        ])
+        .raw("/\t$", DIRECTORY_ATTRIBUTES)
+        .raw("/notes.txt\t!", "+-1\r\n+INFO: 0Notes\t/notes.txt\t127.0.0.1\t{port}\t+\r\n+VIEWS:\r\n text/plain: <1k>\r\n.\r\n")
+        .raw("/notes.txt\t+text/plain", "+-1\r\nfirst\r\n..dotted\r\n.\r\n")
+        // a "+-1" body is cut at its "." line, the lines before it are kept byte for byte
+        .raw("/image.bin\t+application/octet-stream", "+-1\r\n\u{1}.x\r\n..\r\nend\r\n.\r\n")
+        .text("/plain.txt", "plain\r\n")
+}
+
+fn plus_client(server: &MockServer, download_root: &TempDir) -> Client {
+    let mut config = ClientConfig::new();
+    config
+        .update_verbose(false)
+        .update_resp_timeout(Duration::from_millis(500))
+        .update_plus(true)
+        .update_download_root(Some(&download_root.path_str()));
+    let mut client = Client::from_url(&GopherUrl::parse(&server.host()).unwrap());
+    client.update_config(config);
+    return client
+}
+
+fn plus_request(server: &MockServer, selector: &str, plus: PlusRequest) -> Request {
+    let mut config = ClientConfig::new();
+    config.update_verbose(false).update_resp_timeout(Duration::from_millis(500));
+    let mut req = Request::new("127.0.0.1", server.port);
+    req.update_config(&config.request).update_path(selector).with_plus(plus);
+    return req
+}
+
+#[test]
+fn plus_headers_are_parsed() {
+    assert_eq!(PlusHeader::parse("+-1\r\n").unwrap(), PlusHeader { length: PlusLength::DotTerminated, success: true });
+    assert_eq!(PlusHeader::parse("+-2").unwrap().length, PlusLength::UntilClose);
+    assert_eq!(PlusHeader::parse("+1024\r\n").unwrap().size(), Some(1024));
+    assert!(!PlusHeader::parse("--1").unwrap().success);
+    for invalid in ["", "1024", "+ten", "+-3"] {
+        assert!(matches!(PlusHeader::parse(invalid), Err(GopherError::Parse { .. })), "{:?}", invalid);
+    }
+}
+
+#[test]
+fn plus_markers_and_directory_attributes_are_picked_up_by_the_scan() {
+    let server = MockServer::start(plus_fixture());
+    let dir = TempDir::new("plus-scan");
+    let mut client = plus_client(&server, &dir);
+    client.scan_all();
+
+    assert_eq!(server.hits("/"), 2);
+    assert!(server.requests().contains(&String::from("/\t$")));
+    let item = |selector: &str| {
+        return client.items.iter().find(|item| { item.url().is_some_and(|url| { url.selector == selector }) }).unwrap()
+    };
+    assert!(item("/notes.txt").is_plus() && item("/image.bin").is_plus() && !item("/plain.txt").is_plus());
+
+    let notes = item("/notes.txt").attributes().unwrap();
+    let admin = notes.admin.as_ref().unwrap();
+    assert_eq!(admin.admin.as_deref(), Some("Mock Admin <admin@mock.invalid>"));
+    assert_eq!(notes.mime(), Some("text/plain"));
+    let image = item("/image.bin").attributes().unwrap();
+    assert_eq!((image.mime(), image.views[0].bytes()), (Some("application/octet-stream"), Some(2048)));
+    assert_eq!(image.abstract_text.as_deref(), Some("A picture"));
+}
+
+#[test]
+fn single_item_attributes_are_asked_with_a_bang() {
+    let server = MockServer::start(plus_fixture());
+    let dir = TempDir::new("plus-info");
+    let client = plus_client(&server, &dir);
+    let notes = Item::from_str(&Referer::new("127.0.0.1", server.port, "/"),
+        &format!("0Notes\t/notes.txt\t127.0.0.1\t{}\t+", server.port)
+    ).unwrap();
+
+    let attributes = client.attributes(&notes).unwrap();
+    assert_eq!(server.requests(), vec![String::from("/notes.txt\t!")]);
+    assert_eq!(attributes.info.as_deref(), Some(format!("0Notes\t/notes.txt\t127.0.0.1\t{}\t+", server.port).as_str()));
+    assert_eq!(attributes.mime(), Some("text/plain"));
+}
+
+#[test]
+fn plus_downloads_end_at_the_terminator_line() {
+    let server = MockServer::start(plus_fixture());
+    let dir = TempDir::new("plus-download");
+    let mut client = plus_client(&server, &dir);
+    client.scan_all();
+    client.download_all().unwrap();
+
+    assert!(server.requests().contains(&String::from("/image.bin\t+application/octet-stream")));
+    assert_eq!(fs::read(dir.path.join("image.bin")).unwrap(), b"\x01.x\r\n..\r\nend\r\n");
+    assert_eq!(fs::read_to_string(dir.path.join("notes.txt")).unwrap(), "first\r\n.dotted\r\n");
+    assert_eq!(fs::read_to_string(dir.path.join("plain.txt")).unwrap(), "plain\r\n");
+    assert!(client.failures.is_empty());
+}
+
+#[test]
+fn sized_plus_bodies_are_cut_at_their_size() {
+    let fixture = Fixture::new()
+        .raw("/sized.bin\t+", "+5\r\nhelloEXTRA")
+        .raw("/short.bin\t+", "+10\r\nhello")
+        .raw("/refused.bin\t+", "--1\r\nNo such view\r\n");
+    let server = MockServer::start(fixture);
+    let dir = TempDir::new("plus-sized");
+
+    let sized = plus_request(&server, "/sized.bin", PlusRequest::Fetch(None));
+    assert_eq!(sized.download(&dir.path_str()).unwrap(), 5);
+    assert_eq!(fs::read(dir.path.join("sized.bin")).unwrap(), b"hello");
+    assert_eq!(sized.send().unwrap().plus.and_then(|header| { header.size() }), Some(5));
+
+    let short = plus_request(&server, "/short.bin", PlusRequest::Fetch(None)).download(&dir.path_str());
+    assert!(matches!(short, Err(GopherError::Read { .. })), "{:?}", short.err());
+    let refused = plus_request(&server, "/refused.bin", PlusRequest::Fetch(None)).send();
+    assert!(matches!(&refused, Err(GopherError::ServerError { item }) if item.to_string().contains("No such view")));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_plus_downloads_end_at_the_terminator_line() {
+    let server = MockServer::start(plus_fixture());
+    let dir = TempDir::new("plus-download-async");
+    let mut client = plus_client(&server, &dir);
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        client.async_scan_directories().await;
+        client.async_download_all().await.unwrap();
+    });
+
+    assert_eq!(fs::read(dir.path.join("image.bin")).unwrap(), b"\x01.x\r\n..\r\nend\r\n");
+    assert_eq!(fs::read_to_string(dir.path.join("notes.txt")).unwrap(), "first\r\n.dotted\r\n");
+}
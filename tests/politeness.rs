@@ -0,0 +1,169 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::time::{Duration, Instant};
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::config::{ClientConfig, HostPoliteness, PolitenessConfig, RequestConfig, MAX_INTERVAL};
+use simple_gopher_in_rust::gopher::error::GopherError;
+use simple_gopher_in_rust::gopher::io::Request;
+use simple_gopher_in_rust::gopher::politeness::Politeness;
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fixture, MockServer, TempDir};
+
+fn spaced(min_delay: Duration) -> PolitenessConfig {
+    let mut host = HostPoliteness::new();
+    host.update_min_delay(min_delay);
+    let mut politeness = PolitenessConfig::new();
+    politeness.update_host_default(host);
+    return politeness
+}
+
+fn request(server: &MockServer, selector: &str, config: &RequestConfig) -> Request {
+    let mut req = Request::new("127.0.0.1", server.port);
+    req.update_path(selector).update_config(config);
+    return req
+}
+
+fn request_config(politeness: PolitenessConfig) -> RequestConfig {
+    let mut config = RequestConfig::new();
+    config.update_verbose(false).update_resp_timeout(Duration::from_secs(2)).update_politeness(politeness);
+    return config
+}
+
+#[test]
+fn host_rules_come_from_the_politeness_file() {
+    let dir = TempDir::new("politeness-file");
+    let path = dir.path.join("politeness.txt");
+    std::fs::write(&path, "# slow servers\nslow.example delay=2000 conns=1\n\nSlow.example:7070 rate=0.5\n").unwrap();
+    let mut politeness = spaced(Duration::from_millis(100));
+    politeness.load_hosts(&path.to_string_lossy()).unwrap();
+
+    let slow = politeness.for_host("slow.example", 70);
+    assert_eq!((slow.min_delay, slow.max_conns), (Duration::from_secs(2), 1));
+    // settings a rule leaves out are those of every server
+    let slow_port = politeness.for_host("slow.example", 7070);
+    assert_eq!((slow_port.min_delay, slow_port.max_rate, slow_port.max_conns), (Duration::from_millis(100), Some(0.5), 2));
+    assert_eq!(slow_port.interval(), Duration::from_secs(2));
+    assert_eq!(politeness.for_host("other.example", 70).interval(), Duration::from_millis(100));
+
+    std::fs::write(&path, "bad.example delay=soon\n").unwrap();
+    let error = PolitenessConfig::new().load_hosts(&path.to_string_lossy()).err().unwrap();
+    assert!(matches!(&error, GopherError::Parse { message } if message.contains("line 1")), "{}", error);
+    let missing = PolitenessConfig::new().load_hosts(&dir.path.join("missing.txt").to_string_lossy()).err();
+    assert!(matches!(missing, Some(GopherError::InvalidPath { .. })));
+
+    // rates slower than a request a day, or no number at all, are refused
+    for rate in ["1e-300", "NaN", "inf"] {
+        std::fs::write(&path, format!("slow.example rate={}\n", rate)).unwrap();
+        let error = PolitenessConfig::new().load_hosts(&path.to_string_lossy()).err();
+        assert!(matches!(&error, Some(GopherError::Parse { message }) if message.contains("Invalid rate")), "{}", rate);
+    }
+}
+
+#[test]
+fn extreme_rates_do_not_overflow_the_interval() {
+    let mut host = HostPoliteness::new();
+    assert_eq!(host.update_max_rate(Some(1e-300)).interval(), MAX_INTERVAL);
+    assert_eq!(host.update_max_rate(Some(f64::NAN)).max_rate, None);
+    assert_eq!(host.update_max_rate(Some(f64::INFINITY)).interval(), Duration::ZERO);
+    assert_eq!(host.update_min_delay(Duration::MAX).interval(), MAX_INTERVAL);
+    assert!(HostPoliteness::valid_rate(0.5) && !HostPoliteness::valid_rate(1e-6) && !HostPoliteness::valid_rate(f64::NAN));
+
+    // a robots.txt asking for centuries between requests gets a day
+    let politeness = Politeness::new(PolitenessConfig::new());
+    politeness.update_crawl_delay("slow.example", 70, Duration::from_secs(u64::MAX / 2));
+    assert_eq!(politeness.interval("slow.example", 70), MAX_INTERVAL);
+    drop(politeness.try_acquire("slow.example", 70));
+    assert!(politeness.try_acquire("slow.example", 70).is_some());
+}
+
+#[test]
+fn requests_to_a_server_are_spaced() {
+    let server = MockServer::start(Fixture::standard());
+    let config = request_config(spaced(Duration::from_millis(150)));
+    let started = Instant::now();
+    for selector in ["/", "/docs", "/docs/deep"] {
+        request(&server, selector, &config).send().unwrap();
+    }
+    assert!(started.elapsed() >= Duration::from_millis(300), "{:?}", started.elapsed());
+
+    // another server keeps its own pace
+    let other = MockServer::start(Fixture::standard());
+    let started = Instant::now();
+    request(&other, "/", &config).send().unwrap();
+    assert!(started.elapsed() < Duration::from_millis(150), "{:?}", started.elapsed());
+}
+
+#[test]
+fn scans_and_downloads_share_the_pace() {
+    let server = MockServer::start(Fixture::standard());
+    let dir = TempDir::new("politeness-scan");
+    let mut config = ClientConfig::new();
+    config
+        .update_verbose(false)
+        .update_workers(4)
+        .update_download_root(Some(&dir.path_str()))
+        .update_politeness(spaced(Duration::from_millis(100)));
+    let mut client = Client::from_url(&GopherUrl::parse(&server.host()).unwrap());
+    client.update_config(config);
+
+    let started = Instant::now();
+    client.scan_all();
+    client.download_all().unwrap();
+    // robots.txt, caps.txt, 3 menus and 4 files, each request 100 ms after the one before
+    assert_eq!(server.requests().len(), 9);
+    assert!(started.elapsed() >= Duration::from_millis(800), "{:?}", started.elapsed());
+    assert!(client.failures.is_empty());
+}
+
+#[test]
+fn connections_per_server_are_capped() {
+    let mut host = HostPoliteness::new();
+    host.update_max_conns(1);
+    let mut config = PolitenessConfig::new();
+    config.update_host("busy.example", host);
+    let politeness = Politeness::new(config);
+
+    let permit = politeness.acquire("busy.example", 70);
+    assert!(politeness.try_acquire("busy.example", 70).is_none());
+    // servers without a rule have 2 connections
+    let others = (politeness.try_acquire("other.example", 70), politeness.try_acquire("other.example", 70));
+    assert!(others.0.is_some() && others.1.is_some());
+    assert!(politeness.try_acquire("other.example", 70).is_none());
+    drop(permit);
+    assert!(politeness.try_acquire("busy.example", 70).is_some());
+}
+
+#[test]
+fn the_bandwidth_cap_slows_reads_down() {
+    let blob: Vec<u8> = (0..3000).map(|i| { (i % 251) as u8 }).collect();
+    let server = MockServer::start(Fixture::new().binary("/blob.bin", &blob));
+    let mut politeness = PolitenessConfig::new();
+    politeness.update_bandwidth(Some(2000));
+    let config = request_config(politeness);
+
+    let started = Instant::now();
+    let received = request(&server, "/blob.bin", &config).send().unwrap();
+    assert_eq!(received.raw, blob);
+    // a second's worth is read right away, the other 1000 bytes take half a second
+    assert!(started.elapsed() >= Duration::from_millis(450), "{:?}", started.elapsed());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_requests_keep_to_the_pace() {
+    let server = MockServer::start(Fixture::standard());
+    let config = request_config(spaced(Duration::from_millis(150)));
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+    let started = Instant::now();
+    runtime.block_on(async {
+        for selector in ["/", "/docs", "/docs/deep"] {
+            request(&server, selector, &config).async_send().await.unwrap();
+        }
+    });
+    assert!(started.elapsed() >= Duration::from_millis(300), "{:?}", started.elapsed());
+}
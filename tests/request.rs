@@ -0,0 +1,201 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::fs;
+use std::time::Duration;
+
+use simple_gopher_in_rust::gopher::config::RequestConfig;
+use simple_gopher_in_rust::gopher::error::GopherError;
+use simple_gopher_in_rust::gopher::io::{local_path, Request};
+use simple_gopher_in_rust::gopher::types::{Item, ItemClass, ItemKind};
+use support::{Fault, Fixture, Line, MockServer, TempDir};
+
+fn request(server: &MockServer, selector: &str) -> Request {
+    let mut config = RequestConfig::new();
+    config.update_verbose(false).update_resp_timeout(Duration::from_millis(500));
+    let mut req = Request::new("127.0.0.1", server.port);
+    req.update_path(selector).update_config(&config);
+    return req
+}
+
+#[test]
+fn menu_items_are_parsed() {
+    let server = MockServer::start(Fixture::standard());
+    let items = request(&server, "/").send().unwrap().as_items().unwrap();
+
+    let kinds = items.iter().map(|item| { item.kind().unwrap() }).collect::<Vec<ItemKind>>();
+    assert_eq!(kinds, vec![
+        ItemKind::Info, ItemKind::Menu, ItemKind::Text, ItemKind::Binary, ItemKind::Search, ItemKind::Error, ItemKind::Menu,
+    ]);
+    let Item::DATA { location, domain, port, .. } = &items[1] else { panic!("not a data item: {:?}", items[1]) };
+    assert_eq!((location.as_str(), domain.as_str(), *port), ("/docs", "127.0.0.1", server.port));
+    assert_eq!(server.requests(), vec![String::from("/")]);
+}
+
+#[test]
+fn menu_reader_streams_items() {
+    let server = MockServer::start(Fixture::standard());
+    let items = request(&server, "/docs").menu().unwrap().collect::<Result<Vec<Item>, GopherError>>().unwrap();
+    assert_eq!(items.len(), 4);
+    assert_eq!(items[3].url().unwrap().selector, "/");
+}
+
+#[test]
+fn missing_selector_is_a_server_error() {
+    let server = MockServer::start(Fixture::standard());
+    let result = request(&server, "/missing").send().unwrap().as_items();
+    assert!(matches!(result, Err(GopherError::ServerError { item }) if item.class() == Some(ItemClass::Error)));
+}
+
+#[test]
+fn search_sends_the_query() {
+    let server = MockServer::start(Fixture::standard());
+    let items = request(&server, "/search").with_query("gopher").send().unwrap().as_items().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(server.requests(), vec![String::from("/search\tgopher")]);
+}
+
+#[test]
+fn searches_with_extra_fields_or_lines_are_refused() {
+    let server = MockServer::start(Fixture::standard());
+    for (selector, query) in [("/search", "gopher\t+"), ("/search", "gopher\r\n/docs"), ("/search", "go\rpher"), ("/sea\trch", "gopher"), ("/search\n", "gopher")] {
+        let refused = request(&server, selector).with_query(query).send();
+        assert!(matches!(&refused, Err(GopherError::InvalidRequest { selector: sent, query: asked }) if sent == selector && asked == query), "{:?}", query);
+        assert_eq!(refused.err().unwrap().kind().name(), "invalid-request");
+    }
+    // nothing reached the server
+    assert!(server.requests().is_empty());
+
+    // without a query the selector is sent as it is
+    assert!(request(&server, "/docs\tdeep").send().is_ok());
+    assert_eq!(server.requests(), vec![String::from("/docs\tdeep")]);
+}
+
+#[test]
+fn text_download_drops_terminator_and_dot_stuffing() {
+    let server = MockServer::start(Fixture::standard());
+    let dir = TempDir::new("text");
+    let size = request(&server, "/docs/readme.txt").download_txt(&dir.path_str()).unwrap();
+
+    let saved = fs::read_to_string(dir.path.join("docs/readme.txt")).unwrap();
+    assert_eq!(saved, "first line\r\n.starts with a dot\r\nlast line\r\n");
+    assert_eq!(size, saved.len() as u64);
+}
+
+#[test]
+fn binary_download_is_byte_exact() {
+    let server = MockServer::start(Fixture::standard());
+    let dir = TempDir::new("binary");
+    let size = request(&server, "/blob.bin").download(&dir.path_str()).unwrap();
+
+    let saved = fs::read(dir.path.join("blob.bin")).unwrap();
+    assert_eq!(saved, vec![0, 1, 2, 255, b'\n', b'.', b'\r', b'\n', 7]);
+    assert_eq!(size, 9);
+}
+
+#[test]
+fn slow_response_times_out() {
+    let server = MockServer::start(Fixture::standard().fault("/", Fault::Delay(Duration::from_secs(2))));
+    let result = request(&server, "/").send();
+    assert!(matches!(result, Err(GopherError::ReadTimeout { .. })), "{:?}", result.err());
+}
+
+#[test]
+fn slow_response_within_timeout_succeeds() {
+    let server = MockServer::start(Fixture::standard().fault("/", Fault::Delay(Duration::from_millis(100))));
+    assert_eq!(request(&server, "/").send().unwrap().as_items().unwrap().len(), 7);
+}
+
+#[test]
+fn truncated_menu_is_a_parse_error() {
+    let server = MockServer::start(Fixture::standard().fault("/", Fault::Truncate(40)));
+    let result = request(&server, "/").send().unwrap().as_items();
+    assert!(matches!(result, Err(GopherError::Parse { .. })), "{:?}", result);
+}
+
+#[test]
+fn truncated_binary_is_saved_short() {
+    let server = MockServer::start(Fixture::standard().fault("/blob.bin", Fault::Truncate(4)));
+    let dir = TempDir::new("truncated");
+    assert_eq!(request(&server, "/blob.bin").download(&dir.path_str()).unwrap(), 4);
+}
+
+#[test]
+fn menu_without_terminator_is_a_parse_error() {
+    let server = MockServer::start(Fixture::standard().fault("/docs", Fault::NoTerminator));
+    let items = request(&server, "/docs").menu().unwrap().collect::<Vec<Result<Item, GopherError>>>();
+    assert_eq!(items.iter().filter(|item| { item.is_ok() }).count(), 4);
+    assert!(matches!(items.last(), Some(Err(GopherError::Parse { .. }))));
+}
+
+#[test]
+fn text_without_terminator_is_saved_whole() {
+    let server = MockServer::start(Fixture::standard().fault("/about.txt", Fault::NoTerminator));
+    let dir = TempDir::new("unterminated");
+    request(&server, "/about.txt").download_txt(&dir.path_str()).unwrap();
+    assert_eq!(fs::read_to_string(dir.path.join("about.txt")).unwrap(), "About this server\r\n");
+}
+
+#[test]
+fn reset_connection_is_a_read_error() {
+    let server = MockServer::start(Fixture::standard().fault("/", Fault::Reset));
+    let result = request(&server, "/").send();
+    assert!(matches!(result, Err(GopherError::Read { .. })), "{:?}", result.err());
+}
+
+#[test]
+fn raw_lines_are_kept_unknown() {
+    let fixture = Fixture::new().menu("/", vec![Line::raw("not a menu line"), Line::menu("Fine", "/fine")]);
+    let server = MockServer::start(fixture);
+    let items = request(&server, "/").send().unwrap().as_items().unwrap();
+    assert!(matches!(&items[0], Item::UNKNOWN { unparsed, .. } if unparsed == "not a menu line"));
+    assert!(items[1].is_menu());
+}
+
+#[test]
+fn lines_with_a_bad_port_do_not_lose_the_menu() {
+    let lines = vec![Line::raw("0foo\t/x\th\t99999"), Line::raw("1bar\t/y\th\t"), Line::menu("Fine", "/fine")];
+    let server = MockServer::start(Fixture::new().menu("/", lines));
+    let items = request(&server, "/").menu().unwrap().collect::<Result<Vec<Item>, GopherError>>().unwrap();
+    assert!(matches!(&items[0], Item::UNKNOWN { unparsed, .. } if unparsed == "0foo\t/x\th\t99999"));
+    assert!(matches!(&items[1], Item::UNKNOWN { unparsed, .. } if unparsed == "1bar\t/y\th\t"));
+    assert!(items[2].is_menu());
+}
+
+#[test]
+fn selectors_are_sent_byte_for_byte() {
+    let selectors = ["/docs/../about.txt", "/docs/", "docs\\readme.txt", "URL:http://example.org/", "GET /", ""];
+    let fixture = selectors.iter().fold(Fixture::new(), |fixture, selector| { fixture.text(selector, "found\r\n") });
+    let server = MockServer::start(fixture);
+    for selector in selectors {
+        let found = request(&server, selector).send().unwrap();
+        assert_eq!(found.referer.path, selector);
+        assert_eq!(found.as_utf8_str().unwrap(), "found\r\n.\r\n");
+    }
+    assert_eq!(server.requests(), selectors.map(String::from).to_vec());
+
+    // items keep their selector as the menu lists it
+    let server = MockServer::start(Fixture::new().menu("/", vec![Line::menu("Up", "/docs/../"), Line::text("Dos", "\\readme.txt")]));
+    let items = request(&server, "/").send().unwrap().as_items().unwrap();
+    let referers = items.iter().map(|item| { Request::from_item(item).unwrap().referer().path }).collect::<Vec<String>>();
+    assert_eq!(referers, vec!["/docs/../", "\\readme.txt"]);
+}
+
+#[test]
+fn selectors_map_to_paths_inside_the_download_root() {
+    assert_eq!(local_path("/docs/readme.txt"), "docs/readme.txt");
+    assert_eq!(local_path("docs\\deep\\bottom.txt"), "docs/deep/bottom.txt");
+    assert_eq!(local_path("/../../etc/passwd"), "etc/passwd");
+    assert_eq!(local_path("/docs/./old/../readme.txt"), "docs/readme.txt");
+    assert_eq!(local_path("URL:http://example.org/"), "URL:http:/example.org");
+    assert_eq!(local_path("/"), "");
+
+    let server = MockServer::start(Fixture::new().text("/docs/../../secret.txt", "kept inside\r\n").text("/", "no file name\r\n"));
+    let dir = TempDir::new("local-path");
+    request(&server, "/docs/../../secret.txt").download_txt(&dir.path_str()).unwrap();
+    assert_eq!(fs::read_to_string(dir.path.join("secret.txt")).unwrap(), "kept inside\r\n");
+    let nameless = request(&server, "/").download_txt(&dir.path_str());
+    assert!(matches!(nameless, Err(GopherError::InvalidPath { .. })), "{:?}", nameless.err());
+}
@@ -0,0 +1,153 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::time::Duration;
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::config::{ClientConfig, RetryPolicy};
+use simple_gopher_in_rust::gopher::error::{FailureKind, GopherError};
+use simple_gopher_in_rust::gopher::retry::{self, FailureStage};
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fault, Fixture, Line, MockServer, TempDir};
+
+fn fast_retries() -> RetryPolicy {
+    let mut retry = RetryPolicy::new();
+    retry.update_base_delay(Duration::from_millis(10));
+    return retry
+}
+
+fn client(server: &MockServer, retry: RetryPolicy, download_root: Option<&TempDir>) -> Client {
+    let mut config = ClientConfig::new();
+    config
+        .update_verbose(false)
+        .update_resp_timeout(Duration::from_millis(500))
+        .update_retry(retry)
+        .update_download_root(download_root.map(|dir| { dir.path_str() }).as_deref());
+    let mut client = Client::from_url(&GopherUrl::parse(&server.host()).unwrap());
+    client.update_config(config);
+    return client
+}
+
+#[test]
+fn transient_faults_are_retried_until_they_pass() {
+    let fixture = Fixture::standard()
+        .fault_times("/docs", Fault::Reset, 2)
+        .fault_times("/about.txt", Fault::Reset, 1);
+    let server = MockServer::start(fixture);
+    let dir = TempDir::new("retry-transient");
+    let mut client = client(&server, fast_retries(), Some(&dir));
+    client.scan_all();
+    client.download_all().unwrap();
+
+    assert!(client.failures.is_empty());
+    assert_eq!(server.hits("/docs"), 3);
+    assert!(client.referers.iter().any(|referer| { referer.path == "/docs/deep" }));
+    assert_eq!(std::fs::read_to_string(dir.path.join("about.txt")).unwrap(), "About this server\r\n");
+}
+
+#[test]
+fn failures_are_recorded_once_retries_run_out() {
+    let server = MockServer::start(Fixture::standard().fault("/docs", Fault::Reset).fault("/blob.bin", Fault::Reset));
+    let dir = TempDir::new("retry-exhausted");
+    let mut retry = fast_retries();
+    retry.update_max_attempts(4);
+    let mut client = client(&server, retry, Some(&dir));
+    client.scan_all();
+    client.download_all().unwrap();
+
+    let menu = client.failures.iter().find(|failure| { failure.referer.path == "/docs" }).unwrap();
+    assert_eq!((menu.stage, menu.attempts, menu.kind()), (FailureStage::Menu, 4, FailureKind::Read));
+    let download = client.failures.iter().find(|failure| { failure.referer.path == "/blob.bin" }).unwrap();
+    assert_eq!((download.stage, download.attempts, download.kind()), (FailureStage::Download, 4, FailureKind::Read));
+    assert_eq!(server.hits("/blob.bin"), 4);
+
+    let logged = download.to_string();
+    assert!(logged.contains("Stage: download\n") && logged.contains("Kind: read\n") && logged.contains("Attempts: 4\n"), "{}", logged);
+}
+
+#[test]
+fn a_single_attempt_policy_gives_up_at_once() {
+    let server = MockServer::start(Fixture::standard().fault("/docs", Fault::Reset));
+    let mut client = client(&server, RetryPolicy::none(), None);
+    client.scan_all();
+
+    assert_eq!(server.hits("/docs"), 1);
+    assert_eq!(client.failures.len(), 1);
+    assert_eq!(client.failures[0].attempts, 1);
+}
+
+#[test]
+fn only_the_listed_kinds_are_retried() {
+    let fixture = Fixture::standard()
+        .menu("/", vec![Line::menu("Documents", "/docs"), Line::menu("Missing", "/missing")])
+        .fault("/docs", Fault::Reset);
+    let server = MockServer::start(fixture);
+    let mut retry = fast_retries();
+    retry.update_retry_on(&[FailureKind::ServerError]);
+    let mut client = client(&server, retry, None);
+    client.scan_all();
+
+    let attempts = |path: &str| {
+        return client.failures.iter().find(|failure| { failure.referer.path == path }).map(|failure| { failure.attempts })
+    };
+    assert_eq!(attempts("/docs"), Some(1));
+    assert_eq!(attempts("/missing"), Some(3));
+    assert_eq!(server.hits("/missing"), 3);
+}
+
+#[test]
+fn backoff_doubles_up_to_the_max_delay() {
+    let mut retry = RetryPolicy::new();
+    retry.update_base_delay(Duration::from_millis(100)).update_max_delay(Duration::from_millis(350)).update_jitter(0.5);
+    assert_eq!(retry.backoff(1), Duration::from_millis(100));
+    assert_eq!(retry.backoff(2), Duration::from_millis(200));
+    assert_eq!(retry.backoff(3), Duration::from_millis(350));
+    assert_eq!(retry.backoff(40), Duration::from_millis(350));
+
+    for _ in 0..20 {
+        let delay = retry::delay(&retry, 2);
+        assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200), "{:?}", delay);
+    }
+    retry.update_jitter(0.0);
+    assert_eq!(retry::delay(&retry, 2), Duration::from_millis(200));
+    assert_eq!(RetryPolicy::new().update_jitter(3.0).jitter, 1.0);
+    assert_eq!(RetryPolicy::new().update_jitter(f64::NAN).jitter, 0.0);
+
+    // a jitter set by hand outside 0..=1 does not break the wait
+    retry.jitter = f64::NAN;
+    assert_eq!(retry::delay(&retry, 2), Duration::from_millis(200));
+    retry.jitter = -4.0;
+    assert_eq!(retry::delay(&retry, 2), Duration::from_millis(200));
+}
+
+#[test]
+fn failure_kinds_parse_by_name() {
+    assert_eq!("read-timeout".parse::<FailureKind>(), Ok(FailureKind::ReadTimeout));
+    assert_eq!("DNS".parse::<FailureKind>(), Ok(FailureKind::Dns));
+    assert!("flaky".parse::<FailureKind>().is_err());
+    for kind in FailureKind::ALL {
+        assert_eq!(kind.to_string().parse::<FailureKind>(), Ok(kind));
+    }
+    let error = GopherError::Dns { domain: String::from("example.invalid"), port: 70, source: None };
+    assert!(!RetryPolicy::new().retries(&error, 1));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_menus_and_downloads_are_retried() {
+    let server = MockServer::start(Fixture::standard().fault_times("/docs", Fault::Reset, 1).fault_times("/blob.bin", Fault::Reset, 2));
+    let dir = TempDir::new("retry-async");
+    let mut client = client(&server, fast_retries(), Some(&dir));
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        client.async_scan_directories().await;
+        client.async_download_all().await.unwrap();
+    });
+
+    assert!(client.failures.is_empty());
+    assert_eq!(server.hits("/docs"), 2);
+    assert_eq!(server.hits("/blob.bin"), 3);
+    assert!(dir.path.join("blob.bin").is_file());
+}
@@ -0,0 +1,160 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::time::{Duration, Instant};
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::caps::Caps;
+use simple_gopher_in_rust::gopher::config::ClientConfig;
+use simple_gopher_in_rust::gopher::robots::Robots;
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fixture, Line, MockServer, TempDir};
+
+const ROBOTS: &str = "User-agent: *\nDisallow: /docs/deep\nDisallow: /*.bin$\n";
+
+fn client(server: &MockServer, download_root: Option<&TempDir>) -> Client {
+    let mut config = ClientConfig::new();
+    config
+        .update_verbose(false)
+        .update_resp_timeout(Duration::from_millis(500))
+        .update_download_root(download_root.map(|dir| { dir.path_str() }).as_deref());
+    let mut client = Client::from_url(&GopherUrl::parse(&server.host()).unwrap());
+    client.update_config(config);
+    return client
+}
+
+#[test]
+fn robots_rules_follow_the_group_of_the_client() {
+    let robots = Robots::parse(
+        "# everyone\nUser-agent: *\nDisallow: /\n\nUser-agent: other-bot\nUser-Agent: Simple_Gopher_In_Rust\n\
+        Disallow: /private # for staff\nAllow: /private/public\nDisallow: /*.zip$\nCrawl-delay: 1.5\n"
+    );
+    assert!(robots.allows("/"));
+    assert!(!robots.allows("/private/notes"));
+    assert!(robots.allows("/private/public/notes"));
+    assert!(!robots.allows("/files/archive.zip"));
+    assert!(robots.allows("/files/archive.zip.txt"));
+    // selectors without a leading slash are matched as if they had one
+    assert!(!robots.allows("private"));
+    assert_eq!(robots.crawl_delay, Some(Duration::from_millis(1500)));
+
+    let anyone = Robots::parse("User-agent: *\nDisallow: /tmp\nAllow: /tmp\nDisallow:\n");
+    // allow wins a tie, an empty disallow allows everything
+    assert!(anyone.allows("/tmp/file") && anyone.allows("/"));
+    assert_eq!(anyone.crawl_delay, None);
+    assert!(Robots::parse("User-agent: other-bot\nDisallow: /\n").allows("/anything"));
+
+    // delays no Duration can hold are ignored instead of ending the crawl
+    for delay in ["1e30", "-1", "NaN", "inf", "soon"] {
+        let robots = Robots::parse(&format!("User-agent: *\nCrawl-delay: {}\nDisallow: /tmp\n", delay));
+        assert_eq!(robots.crawl_delay, None, "{}", delay);
+        assert!(!robots.allows("/tmp"));
+    }
+}
+
+#[test]
+fn caps_path_rules_normalise_selectors() {
+    let caps = Caps::parse("CAPS\n# path rules\nCapsVersion=1\nPathDelimeter=\\\nPathParent=^\nPathParentDouble=TRUE\nServerSoftware=MockGopher\n");
+    assert_eq!(caps.path_delimiter(), "\\");
+    assert_eq!(caps.normalize("\\docs\\.\\deep\\^\\file"), "\\docs\\file");
+    assert_eq!(caps.normalize("\\^\\^\\docs"), "\\docs");
+    // with PathParentDouble a doubled delimiter goes up, a trailing one stays
+    assert_eq!(caps.normalize("\\docs\\deep\\\\file\\"), "\\docs\\file\\");
+    assert_eq!(caps.normalize("\\docs\\\\\\\\file"), "\\file");
+    assert_eq!(caps.normalize("\\docs\\\\"), "\\");
+    assert_eq!(caps.normalize("\\\\docs"), "\\docs");
+    assert_eq!(caps.normalize("/docs/../file"), "/docs/../file");
+    assert_eq!(caps.server_software(), Some("MockGopher"));
+    assert_eq!(caps.server_description(), None);
+
+    // the usual rules when none are listed
+    let defaults = Caps::parse("CAPS\nCapsVersion=1\n");
+    assert_eq!(defaults.normalize("/docs/./deep/../file"), "/docs/file");
+    assert_eq!(defaults.normalize("/docs/deep/.."), "/docs/");
+    assert!(!defaults.path_parent_double() && !Caps::parse("PathParentDouble=FALSE\n").path_parent_double());
+    assert_eq!(Caps::parse("PathParentDouble=FALSE\n").normalize("/docs//file"), "/docs//file");
+    assert_eq!(Caps::parse("PathParentDouble=true\n").normalize("/docs/deep//file"), "/docs/file");
+    // selectors that are no paths are left alone
+    for selector in ["/docs//file", "/dir/", "docs/..", "URL:http://example.org/a/../b", "GET /", ""] {
+        assert_eq!(defaults.normalize(selector), selector);
+    }
+}
+
+#[test]
+fn disallowed_menus_and_files_are_left_alone() {
+    let server = MockServer::start(Fixture::standard().text("/robots.txt", ROBOTS));
+    let dir = TempDir::new("robots-disallow");
+    let mut client = client(&server, Some(&dir));
+    client.scan_all();
+    client.download_all().unwrap();
+
+    assert_eq!(server.hits("/docs/deep"), 0);
+    assert_eq!(server.hits("/blob.bin"), 0);
+    assert!(dir.path.join("about.txt").is_file() && !dir.path.join("blob.bin").exists());
+    let disallowed = client.disallowed.iter().map(|referer| { referer.path.as_str() }).collect::<Vec<&str>>();
+    assert_eq!(disallowed, vec!["/docs/deep", "/blob.bin"]);
+    assert_eq!(server.hits("/robots.txt"), 1);
+    assert!(client.robots().is_some_and(|robots| { robots.rules.len() == 2 }));
+    // the mock server answers caps.txt with an error item
+    assert!(client.caps().is_none());
+}
+
+#[test]
+fn robots_can_be_ignored() {
+    let server = MockServer::start(Fixture::standard().text("/robots.txt", ROBOTS));
+    let mut client = client(&server, None);
+    client.config.update_robots(false).update_caps(false);
+    client.scan_all();
+
+    assert_eq!(server.hits("/docs/deep"), 1);
+    assert_eq!(server.hits("/robots.txt") + server.hits("caps.txt"), 0);
+    assert!(client.disallowed.is_empty() && client.robots().is_none());
+}
+
+#[test]
+fn the_crawl_delay_paces_the_scan() {
+    let server = MockServer::start(Fixture::standard().text("/robots.txt", "User-agent: *\nCrawl-delay: 0.2\n"));
+    let mut client = client(&server, None);
+    let started = Instant::now();
+    client.scan_all();
+
+    // the 3 menus start 200 ms apart
+    assert_eq!(client.referers.len(), 3);
+    assert!(started.elapsed() >= Duration::from_millis(400), "{:?}", started.elapsed());
+    assert_eq!(client.robots().and_then(|robots| { robots.crawl_delay }), Some(Duration::from_millis(200)));
+}
+
+#[test]
+fn menus_are_followed_by_the_caps_path_rules() {
+    let fixture = Fixture::new()
+        .menu("/", vec![Line::menu("Documents", "\\old\\.\\..\\docs")])
+        .menu("\\docs", vec![Line::text("Readme", "\\docs\\readme.txt")])
+        .text("caps.txt", "CAPS\nPathDelimiter=\\\nPathParent=..\nServerSoftware=MockGopher\nServerSoftwareVersion=1.0\n");
+    let server = MockServer::start(fixture);
+    let mut client = client(&server, None);
+    client.scan_all();
+
+    assert_eq!(server.hits("\\docs"), 1);
+    assert!(client.referers.iter().any(|referer| { referer.path == "\\docs" }));
+    let caps = client.caps().unwrap();
+    assert_eq!((caps.server_software(), caps.server_software_version()), (Some("MockGopher"), Some("1.0")));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_scans_honour_robots() {
+    let server = MockServer::start(Fixture::standard().text("/robots.txt", ROBOTS));
+    let dir = TempDir::new("robots-async");
+    let mut client = client(&server, Some(&dir));
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        client.async_scan_directories().await;
+        client.async_download_all().await.unwrap();
+    });
+
+    assert_eq!(server.hits("/docs/deep") + server.hits("/blob.bin"), 0);
+    assert_eq!(client.disallowed.len(), 2);
+    assert!(dir.path.join("about.txt").is_file());
+}
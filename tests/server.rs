@@ -0,0 +1,172 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::config::{ClientConfig, RequestConfig};
+use simple_gopher_in_rust::gopher::error::GopherError;
+use simple_gopher_in_rust::gopher::io::Request;
+use simple_gopher_in_rust::gopher::server::Server;
+use simple_gopher_in_rust::gopher::types::{Item, ItemKind};
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::TempDir;
+
+// a folder with a gophermap, a dot-stuffed text, a binary, a dot file and symlinks out of it
+fn served_folder(root: &TempDir, outside: &TempDir) {
+    fs::write(root.path.join("hello.txt"), "hello\n.starts with a dot\nlast line").unwrap();
+    fs::write(root.path.join("data.bin"), [0u8, 1, 2, 255]).unwrap();
+    fs::write(root.path.join(".hidden"), "not listed\n").unwrap();
+    fs::create_dir(root.path.join("docs")).unwrap();
+    fs::write(root.path.join("docs/notes.txt"), "notes\n").unwrap();
+    fs::write(root.path.join("docs/gophermap"), "Welcome to the docs\n0Notes\tnotes.txt\n1Up\t/\n1Elsewhere\t/\texample.invalid\n").unwrap();
+
+    fs::write(outside.path.join("secret.txt"), "secret\n").unwrap();
+    std::os::unix::fs::symlink(outside.path.join("secret.txt"), root.path.join("secret.txt")).unwrap();
+    std::os::unix::fs::symlink(&outside.path, root.path.join("outside")).unwrap();
+}
+
+// the folder served on an ephemeral port, with the server thread left running for the test
+fn serve(root: &TempDir, max_connections: usize, req_timeout: Duration) -> u16 {
+    let mut server = Server::new(&root.path_str());
+    server.update_port(0).update_host("127.0.0.1").update_max_connections(max_connections).update_req_timeout(req_timeout);
+    let listener = server.listen().unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || { server.serve_listener(listener) });
+    return port
+}
+
+fn request(port: u16, selector: &str) -> Request {
+    let mut config = RequestConfig::new();
+    config.update_verbose(false).update_resp_timeout(Duration::from_secs(2));
+    let mut req = Request::new("127.0.0.1", port);
+    req.update_config(&config).update_path(selector);
+    return req
+}
+
+fn items(req: &Request) -> Result<Vec<Item>, GopherError> {
+    return req.send()?.as_items()
+}
+
+fn selectors(items: &[Item]) -> Vec<String> {
+    return items.iter().filter_map(|item| { item.url().map(|url| { url.selector }) }).collect()
+}
+
+// the bytes sent for a request line, as they come over the wire
+fn raw(port: u16, line: &str) -> Vec<u8> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.write_all(line.as_bytes()).unwrap();
+    let mut response: Vec<u8> = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    return response
+}
+
+#[test]
+fn folders_are_listed_by_name_behind_a_search() {
+    let (root, outside) = (TempDir::new("serve-listing"), TempDir::new("serve-listing-outside"));
+    served_folder(&root, &outside);
+    let port = serve(&root, 4, Duration::from_secs(2));
+
+    let listing = items(&request(port, "/")).unwrap();
+    assert_eq!(selectors(&listing), vec!["/", "/data.bin", "/docs", "/hello.txt"]);
+    let kinds = listing.iter().filter_map(|item| { item.url().map(|url| { url.kind }) }).collect::<Vec<ItemKind>>();
+    assert_eq!(kinds, vec![ItemKind::Search, ItemKind::Binary, ItemKind::Menu, ItemKind::Text]);
+}
+
+#[test]
+fn gophermap_lines_are_completed() {
+    let (root, outside) = (TempDir::new("serve-gophermap"), TempDir::new("serve-gophermap-outside"));
+    served_folder(&root, &outside);
+    let port = serve(&root, 4, Duration::from_secs(2));
+
+    let menu = items(&request(port, "/docs")).unwrap();
+    let lines = menu.iter().map(|item| { item.menu_line() }).collect::<Vec<String>>();
+    assert_eq!(lines, vec![
+        format!("iWelcome to the docs\t\t127.0.0.1\t{}", port),
+        format!("0Notes\t/docs/notes.txt\t127.0.0.1\t{}", port),
+        format!("1Up\t/\t127.0.0.1\t{}", port),
+        String::from("1Elsewhere\t/\texample.invalid\t70"),
+    ]);
+}
+
+#[test]
+fn searches_match_file_names_below_the_folder() {
+    let (root, outside) = (TempDir::new("serve-search"), TempDir::new("serve-search-outside"));
+    served_folder(&root, &outside);
+    let port = serve(&root, 4, Duration::from_secs(2));
+
+    let mut search = request(port, "/");
+    search.with_query("NOTES");
+    assert_eq!(selectors(&items(&search).unwrap()), vec!["/docs/notes.txt"]);
+
+    let mut nothing = request(port, "/");
+    nothing.with_query("secret");
+    let found = items(&nothing).unwrap();
+    assert_eq!(found.len(), 1);
+    assert!(matches!(&found[0], Item::INFO { message, .. } if message == "No file names match"));
+}
+
+#[test]
+fn text_files_are_dot_stuffed_and_binaries_sent_as_they_are() {
+    let (root, outside) = (TempDir::new("serve-text"), TempDir::new("serve-text-outside"));
+    served_folder(&root, &outside);
+    let port = serve(&root, 4, Duration::from_secs(2));
+
+    assert_eq!(raw(port, "/hello.txt\r\n"), b"hello\n..starts with a dot\nlast line\r\n.\r\n");
+    assert_eq!(raw(port, "/data.bin\r\n"), vec![0u8, 1, 2, 255]);
+}
+
+#[test]
+fn symlinks_out_of_the_root_are_refused() {
+    let (root, outside) = (TempDir::new("serve-symlink"), TempDir::new("serve-symlink-outside"));
+    served_folder(&root, &outside);
+    let port = serve(&root, 4, Duration::from_secs(2));
+
+    for selector in ["/secret.txt", "/outside", "/outside/secret.txt"] {
+        let refused = items(&request(port, selector));
+        assert!(matches!(&refused, Err(GopherError::ServerError { item }) if item.to_string().contains("not found")), "{}", selector);
+        assert!(!String::from_utf8_lossy(&raw(port, &format!("{}\r\n", selector))).contains("secret\n"), "{}", selector);
+    }
+}
+
+#[test]
+fn a_crawl_of_the_served_folder_downloads_its_files() {
+    let (root, outside) = (TempDir::new("serve-crawl"), TempDir::new("serve-crawl-outside"));
+    served_folder(&root, &outside);
+    let port = serve(&root, 4, Duration::from_secs(2));
+    let dir = TempDir::new("serve-crawl-download");
+
+    let mut config = ClientConfig::new();
+    config.update_verbose(false).update_resp_timeout(Duration::from_secs(2)).update_download_root(Some(&dir.path_str()));
+    let mut client = Client::from_url(&GopherUrl::parse(&format!("127.0.0.1:{}", port)).unwrap());
+    client.update_config(config);
+    client.scan_all();
+    client.download_all().unwrap();
+
+    assert!(client.failures.is_empty());
+    assert_eq!(fs::read_to_string(dir.path.join("hello.txt")).unwrap(), "hello\n.starts with a dot\nlast line\r\n");
+    assert_eq!(fs::read(dir.path.join("data.bin")).unwrap(), vec![0u8, 1, 2, 255]);
+    assert_eq!(fs::read_to_string(dir.path.join("docs/notes.txt")).unwrap(), "notes\n");
+    assert!(!dir.path.join("secret.txt").exists() && !dir.path.join("outside").exists());
+}
+
+#[test]
+fn connections_beyond_the_limit_wait_for_a_worker() {
+    let (root, outside) = (TempDir::new("serve-bounded"), TempDir::new("serve-bounded-outside"));
+    served_folder(&root, &outside);
+    let port = serve(&root, 1, Duration::from_millis(500));
+
+    // the only worker waits on a client that never sends its request
+    let idle = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let started = Instant::now();
+    assert_eq!(raw(port, "/data.bin\r\n"), vec![0u8, 1, 2, 255]);
+    assert!(started.elapsed() >= Duration::from_millis(300), "{:?}", started.elapsed());
+    drop(idle);
+}
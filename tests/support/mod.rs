@@ -0,0 +1,546 @@
+// shared by every test crate, each one uses part of it
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Mock gopher server
+// ======
+// Serves a Fixture on an ephemeral port of 127.0.0.1 from a thread of its own, until it is dropped. A request
+// "selector\tsearch" is answered with the resource registered for the whole line, or else for the selector,
+// or else with a "not found" error item. Menu lines without a host point back at the mock server. Every
+// request line received is recorded, and a fault registered for a selector changes how it is answered
+//     Delay(d)        wait d before answering
+//     Truncate(n)     send the first n bytes of the answer and close
+//     NoTerminator    leave out the "." line of menus and text files
+//     Reset           close without reading the request, the client sees a connection reset
+
+#[derive(Clone, Debug)]
+pub enum Line {
+    Info(String),
+    Error(String),
+    // item on the mock server
+    Local { kind: char, caption: String, selector: String },
+    // gopher+ item on the mock server, its line ends with a "+" field
+    Plus { kind: char, caption: String, selector: String },
+    // item on another server
+    External { kind: char, caption: String, selector: String, host: String, port: u16 },
+    // sent as it is
+    Raw(String),
+}
+
+impl Line {
+    pub fn info(message: &str) -> Self {
+        return Self::Info(String::from(message))
+    }
+
+    pub fn error(message: &str) -> Self {
+        return Self::Error(String::from(message))
+    }
+
+    pub fn item(kind: char, caption: &str, selector: &str) -> Self {
+        return Self::Local { kind, caption: String::from(caption), selector: String::from(selector) }
+    }
+
+    pub fn menu(caption: &str, selector: &str) -> Self {
+        return Self::item('1', caption, selector)
+    }
+
+    pub fn text(caption: &str, selector: &str) -> Self {
+        return Self::item('0', caption, selector)
+    }
+
+    pub fn binary(caption: &str, selector: &str) -> Self {
+        return Self::item('9', caption, selector)
+    }
+
+    pub fn search(caption: &str, selector: &str) -> Self {
+        return Self::item('7', caption, selector)
+    }
+
+    pub fn plus(kind: char, caption: &str, selector: &str) -> Self {
+        return Self::Plus { kind, caption: String::from(caption), selector: String::from(selector) }
+    }
+
+    pub fn external(kind: char, caption: &str, selector: &str, host: &str, port: u16) -> Self {
+        return Self::External {
+            kind, caption: String::from(caption), selector: String::from(selector), host: String::from(host), port,
+        }
+    }
+
+    pub fn raw(line: &str) -> Self {
+        return Self::Raw(String::from(line))
+    }
+
+    fn render(&self, port: u16) -> String {
+        return match self {
+            Self::Info(message) => format!("i{}\t\tlocalhost\t{}", message, port),
+            Self::Error(message) => format!("3{}\t\terror.host\t1", message),
+            Self::Local { kind, caption, selector } => format!("{}{}\t{}\t127.0.0.1\t{}", kind, caption, selector, port),
+            Self::Plus { kind, caption, selector } => format!("{}{}\t{}\t127.0.0.1\t{}\t+", kind, caption, selector, port),
+            Self::External { kind, caption, selector, host, port } => format!("{}{}\t{}\t{}\t{}", kind, caption, selector, host, port),
+            Self::Raw(line) => line.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Resource {
+    Menu(Vec<Line>),
+    // dot-stuffed and ended with a "." line when served
+    Text(String),
+    Binary(Vec<u8>),
+    // sent as it is, "{port}" replaced by the port of the mock server
+    Raw(String),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    Delay(Duration),
+    Truncate(usize),
+    NoTerminator,
+    Reset,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Fixture {
+    resources: HashMap<String, Resource>,
+    // fault of a selector and the number of requests it spoils
+    faults: HashMap<String, (Fault, usize)>,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        return Self::default()
+    }
+
+    pub fn menu(mut self, selector: &str, lines: Vec<Line>) -> Self {
+        self.resources.insert(String::from(selector), Resource::Menu(lines));
+        return self
+    }
+
+    // results of sending query to the search item at selector
+    pub fn search_results(mut self, selector: &str, query: &str, lines: Vec<Line>) -> Self {
+        self.resources.insert(format!("{}\t{}", selector, query), Resource::Menu(lines));
+        return self
+    }
+
+    pub fn text(mut self, selector: &str, content: &str) -> Self {
+        self.resources.insert(String::from(selector), Resource::Text(String::from(content)));
+        return self
+    }
+
+    pub fn binary(mut self, selector: &str, content: &[u8]) -> Self {
+        self.resources.insert(String::from(selector), Resource::Binary(content.to_vec()));
+        return self
+    }
+
+    // answer to a request line, i.e. "/file\t+" for a gopher+ fetch, headers and terminators included
+    pub fn raw(mut self, line: &str, content: &str) -> Self {
+        self.resources.insert(String::from(line), Resource::Raw(String::from(content)));
+        return self
+    }
+
+    pub fn fault(mut self, selector: &str, fault: Fault) -> Self {
+        self.faults.insert(String::from(selector), (fault, usize::MAX));
+        return self
+    }
+
+    // fault only the first times requests for selector, later ones are answered normally
+    pub fn fault_times(mut self, selector: &str, fault: Fault, times: usize) -> Self {
+        self.faults.insert(String::from(selector), (fault, times));
+        return self
+    }
+
+    // a small server with every kind of item
+    //     /               info line, menus, a text file, a binary, a search, an error item and an external menu
+    //     /docs           a text file with a dot-stuffed line, a nested menu and a link back to / (a cycle)
+    //     /docs/deep      a text file
+    //     /search?gopher  results pointing at /docs
+    pub fn standard() -> Self {
+        return Self::new()
+            .menu("/", vec![
+                Line::info("Welcome to the mock server"),
+                Line::menu("Documents", "/docs"),
+                Line::text("About", "/about.txt"),
+                Line::binary("Blob", "/blob.bin"),
+                Line::search("Search", "/search"),
+                Line::error("Something went wrong here"),
+                Line::external('1', "Elsewhere", "/", "example.invalid", 70),
+            ])
+            .menu("/docs", vec![
+                Line::info("Documents"),
+                Line::text("Readme", "/docs/readme.txt"),
+                Line::menu("Deeper", "/docs/deep"),
+                Line::menu("Back to the top", "/"),
+            ])
+            .menu("/docs/deep", vec![
+                Line::text("Bottom", "/docs/deep/bottom.txt"),
+            ])
+            .search_results("/search", "gopher", vec![
+                Line::menu("Documents", "/docs"),
+            ])
+            .text("/about.txt", "About this server\r\n")
+            .text("/docs/readme.txt", "first line\r\n.starts with a dot\r\nlast line\r\n")
+            .text("/docs/deep/bottom.txt", "bottom\r\n")
+            .binary("/blob.bin", &[0, 1, 2, 255, b'\n', b'.', b'\r', b'\n', 7])
+    }
+}
+
+// a text file as sent, lines starting with "." get another one and a "." line ends it
+fn dot_stuff(content: &str) -> String {
+    let mut stuffed = content.split_inclusive('\n')
+        .map(|line| { if line.starts_with('.') { format!(".{}", line) } else { String::from(line) } })
+        .collect::<String>();
+    if !stuffed.is_empty() && !stuffed.ends_with('\n') {
+        stuffed.push_str("\r\n");
+    }
+    return stuffed
+}
+
+pub struct MockServer {
+    pub port: u16,
+    requests: Arc<Mutex<Vec<String>>>,
+    connections: Arc<AtomicUsize>,
+    stopped: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    pub fn start(fixture: Fixture) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("mock server could not bind");
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let connections = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let fixture = Arc::new(fixture);
+        let handle = {
+            let (requests, connections, stopped) = (requests.clone(), connections.clone(), stopped.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    connections.fetch_add(1, Ordering::SeqCst);
+                    let (fixture, requests) = (fixture.clone(), requests.clone());
+                    thread::spawn(move || { answer(stream, &fixture, port, &requests) });
+                }
+            })
+        };
+        return Self { port, requests, connections, stopped, handle: Some(handle) }
+    }
+
+    pub fn host(&self) -> String {
+        return format!("127.0.0.1:{}", self.port)
+    }
+
+    pub fn url(&self, selector: &str) -> String {
+        return format!("gopher://127.0.0.1:{}/1{}", self.port, selector)
+    }
+
+    // request lines received so far, without their line ending
+    pub fn requests(&self) -> Vec<String> {
+        return self.requests.lock().unwrap().clone()
+    }
+
+    // times a request for selector was received, with or without search terms
+    pub fn hits(&self, selector: &str) -> usize {
+        return self.requests().iter().filter(|line| { line.split('\t').next() == Some(selector) }).count()
+    }
+
+    pub fn connections(&self) -> usize {
+        return self.connections.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // wake the accept loop up so it sees the flag
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+// the request line of stream, read without taking it off the socket when peek is set
+fn request_line(stream: &TcpStream, peek: bool) -> Option<String> {
+    stream.set_read_timeout(Some(Duration::from_secs(5))).ok()?;
+    if peek {
+        let mut buf = [0u8; 4096];
+        for _ in 0..100 {
+            let size = stream.peek(&mut buf).ok()?;
+            if let Some(end) = buf[..size].iter().position(|byte| { *byte == b'\n' }) {
+                return Some(String::from_utf8_lossy(&buf[..end]).trim_end_matches('\r').to_string())
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        return None
+    }
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).ok()?;
+    return Some(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn answer(mut stream: TcpStream, fixture: &Fixture, port: u16, requests: &Mutex<Vec<String>>) {
+    // peek first so a reset leaves the request unread
+    let Some(line) = request_line(&stream, true) else {
+        return
+    };
+    let selector = line.split('\t').next().unwrap_or_default().to_string();
+    let hits = {
+        let mut requests = requests.lock().unwrap();
+        requests.push(line.clone());
+        requests.iter().filter(|line| { line.split('\t').next() == Some(selector.as_str()) }).count()
+    };
+
+    let fault = fixture.faults.get(&selector).filter(|(_, times)| { hits <= *times }).map(|(fault, _)| { *fault });
+    if let Some(Fault::Reset) = fault {
+        // closing with unread data sends a reset instead of a clean end of stream
+        return
+    }
+    if request_line(&stream, false).is_none() {
+        return
+    }
+
+    let terminated = !matches!(fault, Some(Fault::NoTerminator));
+    let mut body: Vec<u8> = match fixture.resources.get(&line).or(fixture.resources.get(&selector)) {
+        Some(Resource::Menu(lines)) => {
+            let mut menu = lines.iter().map(|line| { format!("{}\r\n", line.render(port)) }).collect::<String>();
+            if terminated {
+                menu.push_str(".\r\n");
+            }
+            menu.into_bytes()
+        }
+        Some(Resource::Text(content)) => {
+            let mut text = dot_stuff(content);
+            if terminated {
+                text.push_str(".\r\n");
+            }
+            text.into_bytes()
+        }
+        Some(Resource::Binary(content)) => content.clone(),
+        Some(Resource::Raw(content)) => content.replace("{port}", &port.to_string()).into_bytes(),
+        None => format!("{}\r\n.\r\n", Line::error(&format!("'{}' not found", selector)).render(port)).into_bytes(),
+    };
+
+    match fault {
+        Some(Fault::Delay(delay)) => thread::sleep(delay),
+        Some(Fault::Truncate(size)) => body.truncate(size),
+        _ => {}
+    }
+    let _ = stream.write_all(&body);
+    let _ = stream.flush();
+    let _ = stream.shutdown(Shutdown::Write);
+}
+
+// a fresh folder under the system temp folder, removed when dropped
+pub struct TempDir {
+    pub path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "gopher-test-{}-{}-{}", name, std::process::id(), COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        return Self { path }
+    }
+
+    pub fn path_str(&self) -> String {
+        return self.path.to_string_lossy().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+// copy bytes both ways between two sockets until either side closes
+fn relay(client: TcpStream, server: TcpStream) {
+    let (mut client_read, mut server_write) = (client.try_clone().unwrap(), server.try_clone().unwrap());
+    let upstream = thread::spawn(move || {
+        let _ = std::io::copy(&mut client_read, &mut server_write);
+        let _ = server_write.shutdown(Shutdown::Write);
+    });
+    let (mut server_read, mut client_write) = (server, client);
+    let _ = std::io::copy(&mut server_read, &mut client_write);
+    let _ = client_write.shutdown(Shutdown::Write);
+    let _ = upstream.join();
+}
+
+// SOCKS5 proxy on an ephemeral port of 127.0.0.1, CONNECT only. Domains in hosts are resolved by the proxy
+// to the port given, any other address is connected to as it is. With credentials set only a matching
+// username and password are let through. Every (address, port) asked for is recorded.
+pub struct MockSocks5 {
+    pub port: u16,
+    targets: Arc<Mutex<Vec<(String, u16)>>>,
+}
+
+impl MockSocks5 {
+    pub fn start(hosts: HashMap<String, u16>, credentials: Option<(String, String)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("mock proxy could not bind");
+        let port = listener.local_addr().unwrap().port();
+        let targets = Arc::new(Mutex::new(Vec::new()));
+        let (hosts, credentials) = (Arc::new(hosts), Arc::new(credentials));
+        {
+            let targets = targets.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let (hosts, credentials, targets) = (hosts.clone(), credentials.clone(), targets.clone());
+                    thread::spawn(move || { let _ = socks_session(stream, &hosts, &credentials, &targets); });
+                }
+            });
+        }
+        return Self { port, targets }
+    }
+
+    pub fn targets(&self) -> Vec<(String, u16)> {
+        return self.targets.lock().unwrap().clone()
+    }
+}
+
+fn socks_session(
+    mut client: TcpStream,
+    hosts: &HashMap<String, u16>,
+    credentials: &Option<(String, String)>,
+    targets: &Mutex<Vec<(String, u16)>>,
+) -> std::io::Result<()> {
+    use std::io::Read;
+
+    let mut head = [0u8; 2];
+    client.read_exact(&mut head)?;
+    let mut methods = vec![0u8; head[1] as usize];
+    client.read_exact(&mut methods)?;
+    let method = if credentials.is_some() { 0x02 } else { 0x00 };
+    if !methods.contains(&method) {
+        return client.write_all(&[0x05, 0xff])
+    }
+    client.write_all(&[0x05, method])?;
+
+    if let Some((username, password)) = credentials {
+        let mut size = [0u8; 2];
+        client.read_exact(&mut size)?;
+        let mut given_username = vec![0u8; size[1] as usize];
+        client.read_exact(&mut given_username)?;
+        let mut password_size = [0u8; 1];
+        client.read_exact(&mut password_size)?;
+        let mut given_password = vec![0u8; password_size[0] as usize];
+        client.read_exact(&mut given_password)?;
+        if given_username != username.as_bytes() || given_password != password.as_bytes() {
+            return client.write_all(&[0x01, 0x01])
+        }
+        client.write_all(&[0x01, 0x00])?;
+    }
+
+    let mut request = [0u8; 4];
+    client.read_exact(&mut request)?;
+    let address = match request[3] {
+        0x01 => {
+            let mut ip = [0u8; 4];
+            client.read_exact(&mut ip)?;
+            std::net::Ipv4Addr::from(ip).to_string()
+        }
+        0x04 => {
+            let mut ip = [0u8; 16];
+            client.read_exact(&mut ip)?;
+            std::net::Ipv6Addr::from(ip).to_string()
+        }
+        _ => {
+            let mut size = [0u8; 1];
+            client.read_exact(&mut size)?;
+            let mut domain = vec![0u8; size[0] as usize];
+            client.read_exact(&mut domain)?;
+            String::from_utf8_lossy(&domain).to_string()
+        }
+    };
+    let mut port = [0u8; 2];
+    client.read_exact(&mut port)?;
+    let port = u16::from_be_bytes(port);
+    targets.lock().unwrap().push((address.clone(), port));
+
+    let connected = match hosts.get(&address) {
+        Some(local_port) => TcpStream::connect(("127.0.0.1", *local_port)),
+        None => TcpStream::connect((address.as_str(), port)),
+    };
+    let Ok(server) = connected else {
+        // host unreachable
+        return client.write_all(&[0x05, 0x04, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+    };
+    client.write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0, 0])?;
+    relay(client, server);
+    return Ok(())
+}
+
+// DER certificate and PKCS#8 key of a self-signed certificate for localhost and 127.0.0.1
+pub fn test_certificate() -> (Vec<u8>, Vec<u8>) {
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    return (
+        std::fs::read(fixtures.join("localhost.cert.der")).unwrap(),
+        std::fs::read(fixtures.join("localhost.key.der")).unwrap(),
+    )
+}
+
+// TLS in front of a MockServer on an ephemeral port of 127.0.0.1, with the test certificate
+#[cfg(feature = "tls")]
+pub struct MockTls {
+    pub port: u16,
+}
+
+#[cfg(feature = "tls")]
+impl MockTls {
+    pub fn start(backend_port: u16) -> Self {
+        use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+
+        let (cert, key) = test_certificate();
+        let config = Arc::new(rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![CertificateDer::from(cert)], PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key)))
+            .unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").expect("mock TLS front could not bind");
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let config = config.clone();
+                thread::spawn(move || { let _ = tls_session(stream, config, backend_port); });
+            }
+        });
+        return Self { port }
+    }
+}
+
+// read the request line over TLS, pass it on to the backend and send its answer back over TLS
+#[cfg(feature = "tls")]
+fn tls_session(stream: TcpStream, config: Arc<rustls::ServerConfig>, backend_port: u16) -> std::io::Result<()> {
+    use std::io::Read;
+
+    let session = rustls::ServerConnection::new(config).map_err(std::io::Error::other)?;
+    let mut tls = rustls::StreamOwned::new(session, stream);
+    let mut line: Vec<u8> = Vec::new();
+    let mut byte = [0u8; 1];
+    while !line.ends_with(b"\n") && tls.read(&mut byte)? == 1 {
+        line.push(byte[0]);
+    }
+
+    let mut backend = TcpStream::connect(("127.0.0.1", backend_port))?;
+    backend.write_all(&line)?;
+    let mut answer: Vec<u8> = Vec::new();
+    backend.read_to_end(&mut answer)?;
+    tls.write_all(&answer)?;
+    tls.conn.send_close_notify();
+    tls.flush()?;
+    return Ok(())
+}
@@ -0,0 +1,168 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::time::Duration;
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::config::{RequestConfig, TlsConfig, TlsMode};
+use simple_gopher_in_rust::gopher::error::GopherError;
+use simple_gopher_in_rust::gopher::io::Request;
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fixture, MockServer};
+
+fn request(domain: &str, port: u16, selector: &str, tls: &TlsConfig) -> Request {
+    let mut config = RequestConfig::new();
+    config
+        .update_verbose(false)
+        .update_conn_timeout(Duration::from_millis(500))
+        .update_resp_timeout(Duration::from_millis(500))
+        .update_tls(tls.clone());
+    let mut req = Request::new(domain, port);
+    req.update_path(selector).update_config(&config);
+    return req
+}
+
+#[test]
+fn gophers_urls_round_trip() {
+    let url = GopherUrl::parse("gophers://example.org:7070/1/docs").unwrap();
+    assert!(url.tls);
+    assert_eq!(url.to_string(), "gophers://example.org:7070/1/docs");
+    assert!(!GopherUrl::parse("gopher://example.org/1/docs").unwrap().tls);
+    // non-ASCII hosts are not sliced through while looking for the scheme
+    for (raw, domain, tls) in [("aééééé", "aééééé", false), ("gopher://ééééé", "ééééé", false), ("gophers://ééééé:7070/1/docs", "ééééé", true)] {
+        let url = GopherUrl::parse(raw).unwrap();
+        assert_eq!((url.domain.as_str(), url.tls), (domain, tls));
+    }
+
+    // the server of a gophers:// URL is reached over TLS, and is told apart from the same host on other ports
+    let client = Client::from_url(&url);
+    assert_eq!(client.config.request.tls.mode_for("example.org", 7070), TlsMode::On);
+    assert_eq!(client.config.request.tls.mode_for("example.org", 70), TlsMode::Off);
+    assert!(client.url().tls);
+}
+
+#[test]
+fn host_modes_override_the_default() {
+    let mut tls = TlsConfig::new();
+    tls.update_mode(TlsMode::Auto).update_host("Plain.example", TlsMode::Off).update_host("plain.example:7443", TlsMode::On);
+    assert_eq!(tls.mode_for("plain.example", 70), TlsMode::Off);
+    assert_eq!(tls.mode_for("plain.example", 7443), TlsMode::On);
+    assert_eq!(tls.mode_for("other.example", 70), TlsMode::Auto);
+
+    assert_eq!("tls".parse::<TlsMode>(), Ok(TlsMode::On));
+    assert_eq!("plain".parse::<TlsMode>(), Ok(TlsMode::Off));
+    assert!("sometimes".parse::<TlsMode>().is_err());
+}
+
+#[test]
+fn plain_servers_fail_when_tls_is_on() {
+    let server = MockServer::start(Fixture::standard());
+    let mut tls = TlsConfig::new();
+    tls.update_mode(TlsMode::On).update_verify(false);
+    let result = request("127.0.0.1", server.port, "/", &tls).send();
+    assert!(matches!(result, Err(GopherError::Handshake { .. })), "{:?}", result.err());
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use super::*;
+    use simple_gopher_in_rust::gopher::config::ClientConfig;
+    use support::{MockTls, TempDir};
+
+    fn insecure(mode: TlsMode) -> TlsConfig {
+        let mut tls = TlsConfig::new();
+        tls.update_mode(mode).update_verify(false);
+        return tls
+    }
+
+    #[test]
+    fn unverified_tls_takes_self_signed_certificates() {
+        let server = MockServer::start(Fixture::standard());
+        let front = MockTls::start(server.port);
+        let items = request("localhost", front.port, "/docs", &insecure(TlsMode::On)).send().unwrap().as_items().unwrap();
+        assert_eq!(items.len(), 4);
+    }
+
+    #[test]
+    fn verified_tls_refuses_self_signed_certificates() {
+        let server = MockServer::start(Fixture::standard());
+        let front = MockTls::start(server.port);
+        let mut tls = TlsConfig::new();
+        tls.update_mode(TlsMode::On);
+        let result = request("localhost", front.port, "/", &tls).send();
+        assert!(matches!(result, Err(GopherError::Handshake { .. })), "{:?}", result.err());
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn certificates_are_pinned_on_first_use() {
+        let server = MockServer::start(Fixture::standard());
+        let front = MockTls::start(server.port);
+        let dir = TempDir::new("pins");
+        let pin_file = dir.path.join("nested/pins.txt").to_string_lossy().to_string();
+        let mut tls = insecure(TlsMode::On);
+        tls.update_pin_file(Some(&pin_file));
+
+        request("localhost", front.port, "/", &tls).send().unwrap();
+        let pins = std::fs::read_to_string(&pin_file).unwrap();
+        let pin = pins.lines().find(|line| { !line.starts_with('#') }).unwrap();
+        let (host, fingerprint) = pin.split_once(' ').unwrap();
+        assert_eq!(host, format!("localhost:{}", front.port));
+        assert_eq!(fingerprint.len(), 64);
+
+        // the same certificate again is fine
+        request("localhost", front.port, "/docs", &tls).send().unwrap();
+        assert_eq!(server.hits("/docs"), 1);
+    }
+
+    #[test]
+    fn changed_certificates_fail_the_handshake() {
+        let server = MockServer::start(Fixture::standard());
+        let front = MockTls::start(server.port);
+        let dir = TempDir::new("pins-changed");
+        let pin_file = dir.path.join("pins.txt");
+        std::fs::write(&pin_file, format!("localhost:{} {}\n", front.port, "0".repeat(64))).unwrap();
+        let mut tls = insecure(TlsMode::On);
+        tls.update_pin_file(Some(&pin_file.to_string_lossy()));
+
+        let result = request("localhost", front.port, "/", &tls).send();
+        match result {
+            Err(GopherError::Handshake { source, .. }) => assert!(source.to_string().contains("changed"), "{}", source),
+            other => panic!("expected a handshake failure, got {:?}", other.err()),
+        }
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn auto_falls_back_to_plain_tcp() {
+        let server = MockServer::start(Fixture::standard());
+        let front = MockTls::start(server.port);
+
+        let items = request("127.0.0.1", server.port, "/docs", &insecure(TlsMode::Auto)).send().unwrap().as_items().unwrap();
+        assert_eq!(items.len(), 4);
+        // servers that do speak TLS are not given up on for a bad certificate
+        let mut verified = TlsConfig::new();
+        verified.update_mode(TlsMode::Auto);
+        let result = request("localhost", front.port, "/", &verified).send();
+        assert!(matches!(result, Err(GopherError::Handshake { .. })), "{:?}", result.err());
+    }
+
+    #[test]
+    fn gophers_clients_scan_over_tls() {
+        let server = MockServer::start(Fixture::standard());
+        let front = MockTls::start(server.port);
+        let mut client = Client::from_url(&GopherUrl::parse(&format!("gophers://localhost:{}", front.port)).unwrap());
+        let mut tls = client.config.request.tls.clone();
+        tls.update_verify(false);
+        let mut config = ClientConfig::new();
+        config.update_verbose(false).update_resp_timeout(Duration::from_millis(500)).update_tls(tls);
+        client.update_config(config).scan_all();
+
+        assert!(client.failures.is_empty());
+        assert_eq!(client.referers.len(), 1);
+        assert_eq!(server.hits("/"), 1);
+        assert!(client.items.iter().any(|item| { item.url().is_some_and(|url| { url.selector == "/docs" }) }));
+    }
+}
@@ -0,0 +1,151 @@
+// explicit returns are house style
+#![allow(clippy::needless_return)]
+
+mod support;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use simple_gopher_in_rust::gopher::Client;
+use simple_gopher_in_rust::gopher::config::{ClientConfig, RequestConfig};
+use simple_gopher_in_rust::gopher::error::GopherError;
+use simple_gopher_in_rust::gopher::io::Request;
+use simple_gopher_in_rust::gopher::transport::{MemoryConnector, Socks5Connector, Transport};
+use simple_gopher_in_rust::gopher::url::GopherUrl;
+use support::{Fixture, MockServer, MockSocks5};
+
+fn request(domain: &str, port: u16, selector: &str, transport: Transport) -> Request {
+    let mut config = RequestConfig::new();
+    config.update_verbose(false).update_resp_timeout(Duration::from_millis(500)).update_transport(transport);
+    let mut req = Request::new(domain, port);
+    req.update_path(selector).update_config(&config);
+    return req
+}
+
+fn recorded() -> MemoryConnector {
+    let memory = MemoryConnector::new();
+    memory
+        .session("recorded.invalid", 70, "/", b"iHello\t\trecorded.invalid\t70\r\n1Inner\t/inner\trecorded.invalid\t70\r\n.\r\n")
+        .session("recorded.invalid", 70, "/inner", b"0File\t/inner/file.txt\trecorded.invalid\t70\r\n.\r\n")
+        .session("recorded.invalid", 70, "/inner/file.txt", b"replayed\r\n.\r\n");
+    return memory
+}
+
+#[test]
+fn memory_sessions_are_replayed() {
+    let memory = recorded();
+    let items = request("recorded.invalid", 70, "/", Transport::new(memory.clone())).send().unwrap().as_items().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(memory.requests(), vec![(String::from("recorded.invalid"), 70, String::from("/"))]);
+}
+
+#[test]
+fn memory_scan_and_download_stay_offline() {
+    let memory = recorded();
+    let dir = support::TempDir::new("memory");
+    let mut config = ClientConfig::new();
+    config.update_verbose(false).update_transport(Transport::new(memory.clone()));
+    let mut client = Client::new("recorded.invalid");
+    client.update_config(config).scan_all().download_all_to(&dir.path_str()).unwrap();
+
+    assert_eq!(client.referers.len(), 2);
+    assert_eq!(std::fs::read_to_string(dir.path.join("inner/file.txt")).unwrap(), "replayed\r\n");
+    // robots.txt and caps.txt are asked for as well, the recording has neither
+    assert_eq!(memory.requests().len(), 5);
+}
+
+#[test]
+fn memory_refuses_unknown_hosts_and_requests() {
+    let memory = recorded();
+    let unknown_host = request("elsewhere.invalid", 70, "/", Transport::new(memory.clone())).send();
+    assert!(matches!(unknown_host, Err(GopherError::Connect { .. })));
+    let unknown_request = request("recorded.invalid", 70, "/missing", Transport::new(memory)).send();
+    assert!(matches!(unknown_request, Err(GopherError::Read { .. })));
+}
+
+#[test]
+fn socks5_resolves_at_the_proxy() {
+    let server = MockServer::start(Fixture::standard());
+    let proxy = MockSocks5::start(HashMap::from([(String::from("hidden.onion"), server.port)]), None);
+    let socks = Socks5Connector::new("127.0.0.1", proxy.port);
+
+    let items = request("hidden.onion", 70, "/docs", Transport::new(socks)).send().unwrap().as_items().unwrap();
+    assert_eq!(items.len(), 4);
+    assert_eq!(proxy.targets(), vec![(String::from("hidden.onion"), 70)]);
+    assert_eq!(server.requests(), vec![String::from("/docs")]);
+}
+
+#[test]
+fn socks5_sends_credentials() {
+    let server = MockServer::start(Fixture::standard());
+    let credentials = Some((String::from("user"), String::from("secret")));
+    let proxy = MockSocks5::start(HashMap::new(), credentials);
+
+    let mut socks = Socks5Connector::new("127.0.0.1", proxy.port);
+    socks.update_credentials("user", "secret");
+    assert!(request("127.0.0.1", server.port, "/", Transport::new(socks.clone())).send().is_ok());
+
+    socks.update_credentials("user", "wrong");
+    let refused = request("127.0.0.1", server.port, "/", Transport::new(socks)).send();
+    assert!(matches!(refused, Err(GopherError::Handshake { .. })), "{:?}", refused.err());
+}
+
+#[test]
+fn socks5_reports_unreachable_hosts() {
+    let proxy = MockSocks5::start(HashMap::new(), None);
+    let socks = Socks5Connector::new("127.0.0.1", proxy.port);
+    let result = request("unreachable.invalid", 70, "/", Transport::new(socks)).send();
+    assert!(matches!(result, Err(GopherError::Handshake { .. })), "{:?}", result.err());
+}
+
+#[test]
+fn scan_through_socks5() {
+    let server = MockServer::start(Fixture::standard());
+    let proxy = MockSocks5::start(HashMap::new(), None);
+    let mut config = ClientConfig::new();
+    config.update_verbose(false).update_transport(Transport::new(Socks5Connector::new("127.0.0.1", proxy.port)));
+    let mut client = Client::from_url(&GopherUrl::parse(&server.host()).unwrap());
+    client.update_config(config).scan_all();
+
+    assert_eq!(client.referers.len(), 3);
+    // the 3 menus along with robots.txt and caps.txt
+    assert_eq!(proxy.targets().len(), 5);
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use super::*;
+    use rustls::pki_types::CertificateDer;
+    use simple_gopher_in_rust::gopher::transport::TlsConnector;
+    use support::{test_certificate, MockTls};
+
+    fn trusting_test_certificate() -> TlsConnector {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(CertificateDer::from(test_certificate().0)).unwrap();
+        return TlsConnector::with_config(
+            rustls::ClientConfig::builder().with_root_certificates(roots).with_no_client_auth()
+        )
+    }
+
+    #[test]
+    fn tls_request() {
+        let server = MockServer::start(Fixture::standard());
+        let front = MockTls::start(server.port);
+        let tls = Transport::new(trusting_test_certificate());
+
+        let items = request("localhost", front.port, "/docs", tls.clone()).send().unwrap().as_items().unwrap();
+        assert_eq!(items.len(), 4);
+        let dir = support::TempDir::new("tls");
+        request("127.0.0.1", front.port, "/blob.bin", tls).download(&dir.path_str()).unwrap();
+        assert_eq!(std::fs::read(dir.path.join("blob.bin")).unwrap(), vec![0, 1, 2, 255, b'\n', b'.', b'\r', b'\n', 7]);
+    }
+
+    #[test]
+    fn untrusted_certificate_fails_the_handshake() {
+        let server = MockServer::start(Fixture::standard());
+        let front = MockTls::start(server.port);
+        let result = request("localhost", front.port, "/", Transport::new(TlsConnector::new())).send();
+        assert!(matches!(result, Err(GopherError::Handshake { .. })), "{:?}", result.err());
+        assert!(server.requests().is_empty());
+    }
+}